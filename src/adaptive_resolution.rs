@@ -0,0 +1,102 @@
+//! Continuous render scale for `ResolutionScale::Auto`, steered by measured
+//! `raymarch_ms`.
+
+pub const DEFAULT_RAYMARCH_BUDGET_MS: f32 = 8.0;
+pub(crate) const MIN_RAYMARCH_BUDGET_MS: f32 = 1.0;
+pub(crate) const MAX_RAYMARCH_BUDGET_MS: f32 = 100.0;
+pub const MIN_AUTO_SCALE: f32 = 1.0 / 32.0;
+pub const MAX_AUTO_SCALE: f32 = 1.0;
+
+/// Frames above the budget before shrinking; one slow frame is not enough.
+const SHRINK_AFTER_FRAMES: u32 = 3;
+/// Frames below `GROW_BELOW` of the budget before growing back.
+const GROW_AFTER_FRAMES: u32 = 30;
+/// Raymarch time between these fractions of the budget leaves the scale alone.
+const GROW_BELOW: f32 = 0.7;
+const AIM_AT: f32 = 0.85;
+/// Largest factor one grow step may apply, so a cheap frame cannot overshoot.
+const MAX_GROW_STEP: f32 = 1.25;
+
+#[derive(Debug, Clone)]
+pub struct AdaptiveResolution {
+    pub budget_ms: f32,
+    /// Render size as a fraction of the window, per axis.
+    pub scale: f32,
+    frames_over: u32,
+    frames_under: u32,
+}
+
+impl AdaptiveResolution {
+    pub fn new(budget_ms: f32) -> Self {
+        Self {
+            budget_ms,
+            scale: 0.5,
+            frames_over: 0,
+            frames_under: 0,
+        }
+    }
+
+    /// Feeds one rendered frame's raymarch time; returns whether `scale` changed.
+    /// Raymarch cost follows the pixel count, so the scale moves with the square
+    /// root of the time ratio.
+    pub fn observe(&mut self, raymarch_ms: f32) -> bool {
+        if raymarch_ms <= 0.0 {
+            return false;
+        }
+        if raymarch_ms > self.budget_ms {
+            self.frames_over += 1;
+            self.frames_under = 0;
+        } else if raymarch_ms < self.budget_ms * GROW_BELOW {
+            self.frames_under += 1;
+            self.frames_over = 0;
+        } else {
+            self.frames_over = 0;
+            self.frames_under = 0;
+            return false;
+        }
+
+        let ratio = (self.budget_ms * AIM_AT / raymarch_ms).sqrt();
+        let scale = if self.frames_over >= SHRINK_AFTER_FRAMES {
+            self.scale * ratio
+        } else if self.frames_under >= GROW_AFTER_FRAMES {
+            self.scale * ratio.min(MAX_GROW_STEP)
+        } else {
+            return false;
+        };
+        self.frames_over = 0;
+        self.frames_under = 0;
+
+        let scale = scale.clamp(MIN_AUTO_SCALE, MAX_AUTO_SCALE);
+        let changed = scale != self.scale;
+        self.scale = scale;
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn settles_under_budget_without_oscillating() {
+        // Raymarch time of a full-resolution frame; cost scales with pixel count.
+        let full_ms = 40.0;
+        let mut adaptive = AdaptiveResolution::new(DEFAULT_RAYMARCH_BUDGET_MS);
+        adaptive.scale = 1.0;
+
+        let mut changes = 0;
+        for _ in 0..600 {
+            let ms = full_ms * adaptive.scale * adaptive.scale;
+            changes += adaptive.observe(ms) as u32;
+        }
+        let settled_ms = full_ms * adaptive.scale * adaptive.scale;
+        assert!(settled_ms <= DEFAULT_RAYMARCH_BUDGET_MS, "{}", settled_ms);
+        assert!(settled_ms >= DEFAULT_RAYMARCH_BUDGET_MS * GROW_BELOW);
+        assert!(changes <= 2, "{} scale changes", changes);
+
+        let scale = adaptive.scale;
+        assert!(!adaptive.observe(DEFAULT_RAYMARCH_BUDGET_MS * 3.0));
+        assert!(!adaptive.observe(DEFAULT_RAYMARCH_BUDGET_MS * 0.5));
+        assert_eq!(adaptive.scale, scale);
+    }
+}
@@ -0,0 +1,339 @@
+//! Offline raymarch benchmark over a fixed camera flythrough.
+//!
+//! `voxel-raymarcher-rust bench [out.json]` records a run;
+//! `voxel-raymarcher-rust bench-compare base.json new.json [threshold_pct]`
+//! flags runs that got slower than the threshold.
+
+use std::fmt::Write as _;
+use std::fs;
+use std::time::Instant;
+
+use glam::{Vec2, Vec3};
+use serde::{Deserialize, Serialize};
+
+use crate::camera::Camera;
+use crate::raymarch::{self, DebugView, RaymarchInput, Supersampling};
+use crate::rendering::scaled_dimension;
+use crate::state::{build_demo_world, ResolutionScale};
+use crate::stats::percentile;
+use crate::viewplane::{Projection, Viewplane};
+use crate::world::World;
+use crate::{DIMS, VOXEL_STEP_BUDGET, WORLD_SIZE};
+
+const BENCH_FORMAT_VERSION: u32 = 1;
+const WARMUP_FRAMES: usize = 5;
+const BENCH_FRAMES: usize = 90;
+const BENCH_SCALES: [ResolutionScale; 3] = [
+    ResolutionScale::X1,
+    ResolutionScale::XHalf,
+    ResolutionScale::XQuarter,
+];
+const BENCH_DRAW_DISTANCES: [f32; 3] = [64.0, 128.0, 256.0];
+const DEFAULT_REGRESSION_PCT: f64 = 10.0;
+const PATH_HEIGHT_ABOVE_FLOOR: f32 = 36.0;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct BenchRun {
+    name: String,
+    render_width: u32,
+    render_height: u32,
+    draw_distance: f32,
+    frames: usize,
+    mean_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    avg_steps_per_ray: f64,
+    empty_chunk_skips_per_frame: f64,
+}
+
+/// What `bench` writes and `bench-compare` reads back.
+#[derive(Serialize, Deserialize)]
+struct BenchFile {
+    version: u32,
+    world_size: usize,
+    runs: Vec<BenchRun>,
+}
+
+/// Handles the benchmark subcommands; `None` when `args` is not one of them.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    match args.first().map(String::as_str) {
+        Some("bench") => Some(run_benchmark(args.get(1).map(String::as_str))),
+        Some("bench-compare") => {
+            let (Some(base), Some(new)) = (args.get(1), args.get(2)) else {
+                eprintln!("usage: bench-compare <base.json> <new.json> [threshold_pct]");
+                return Some(2);
+            };
+            let threshold = match args.get(3).map(|arg| arg.parse::<f64>()) {
+                None => DEFAULT_REGRESSION_PCT,
+                Some(Ok(threshold)) if threshold >= 0.0 => threshold,
+                Some(_) => {
+                    eprintln!("threshold_pct must be a non-negative number");
+                    return Some(2);
+                }
+            };
+            Some(run_compare(base, new, threshold))
+        }
+        _ => None,
+    }
+}
+
+fn run_benchmark(out_path: Option<&str>) -> i32 {
+    let generation_start = Instant::now();
+    let world = pregenerate_world();
+    eprintln!(
+        "pregenerated {0}x{0} terrain in {1:.1}s",
+        WORLD_SIZE,
+        generation_start.elapsed().as_secs_f32()
+    );
+
+    let mut runs = Vec::new();
+    for scale in BENCH_SCALES {
+        for draw_distance in BENCH_DRAW_DISTANCES {
+            let run = bench_configuration(&world, scale, draw_distance);
+            eprintln!(
+                "{:<12} mean {:>7.2} ms  p95 {:>7.2} ms  p99 {:>7.2} ms  {:>6.1} steps/ray",
+                run.name, run.mean_ms, run.p95_ms, run.p99_ms, run.avg_steps_per_ray
+            );
+            runs.push(run);
+        }
+    }
+
+    let json = runs_to_json(&runs);
+    match out_path {
+        Some(path) => {
+            if let Err(e) = fs::write(path, &json) {
+                eprintln!("failed to write {}: {}", path, e);
+                return 1;
+            }
+            eprintln!("wrote {}", path);
+        }
+        None => print!("{}", json),
+    }
+    0
+}
+
+pub(crate) fn pregenerate_world() -> Box<World> {
+    let mut world = build_demo_world(WORLD_SIZE);
+    world.gen_all_terrain_columns();
+    world
+}
+
+/// Slow loop around the world center with a gentle bob, looking ahead and down.
+fn camera_on_path(world: &World, frame: usize) -> Camera {
+    let phase = frame as f32 / BENCH_FRAMES as f32 * std::f32::consts::TAU;
+    let center = world.get_center();
+    let radius = world.dim as f32 * 0.3;
+    let height = world.get_floor_level() as f32 - PATH_HEIGHT_ABOVE_FLOOR;
+    let pos = Vec3::new(
+        center.x + radius * phase.cos(),
+        height + 6.0 * (phase * 2.0).sin(),
+        center.z + radius * phase.sin(),
+    );
+    let tangent = Vec3::new(-phase.sin(), 0.0, phase.cos());
+    let inward = Vec3::new(-phase.cos(), 0.0, -phase.sin());
+    let dir = tangent + inward * 0.35 + Vec3::new(0.0, 0.3, 0.0);
+    Camera::new(pos, dir, 3.0)
+}
+
+fn bench_configuration(world: &World, scale: ResolutionScale, draw_distance: f32) -> BenchRun {
+    let factor = scale.factor().expect("benchmark scales are fixed presets");
+    let width = scaled_dimension(DIMS.x, factor);
+    let height = scaled_dimension(DIMS.y, factor);
+    let aspect = DIMS.x as f32 / DIMS.y as f32;
+    let viewplane = Viewplane::new(Vec2::new(3.0 * aspect, 3.0), aspect);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+
+    let mut frame_ms = Vec::with_capacity(BENCH_FRAMES);
+    let mut total_steps = 0u64;
+    let mut total_rays = 0u64;
+    let mut total_skips = 0u64;
+    for frame in 0..WARMUP_FRAMES + BENCH_FRAMES {
+        let camera = camera_on_path(world, frame.saturating_sub(WARMUP_FRAMES));
+        let start = Instant::now();
+        let stats = raymarch::draw_voxels(
+            RaymarchInput {
+                world,
+                camera: &camera,
+                viewplane: &viewplane,
+                projection: Projection::Perspective,
+                draw_distance,
+                voxel_step_budget: VOXEL_STEP_BUDGET,
+                hidden_material: None,
+                debug_view: DebugView::Shaded,
+                jitter: Vec2::ZERO,
+                supersampling: Supersampling::Off,
+            },
+            &mut pixels,
+            width as i32,
+            height as i32,
+        );
+        let elapsed_ms = start.elapsed().as_secs_f64() * 1000.0;
+        if frame < WARMUP_FRAMES {
+            continue;
+        }
+        frame_ms.push(elapsed_ms);
+        total_steps += stats.voxel_steps;
+        total_rays += stats.rays_cast as u64;
+        total_skips += stats.empty_chunk_skips as u64;
+    }
+
+    frame_ms.sort_unstable_by(f64::total_cmp);
+    BenchRun {
+        name: format!("{}@{}", scale.label(), draw_distance),
+        render_width: width,
+        render_height: height,
+        draw_distance,
+        frames: BENCH_FRAMES,
+        mean_ms: frame_ms.iter().sum::<f64>() / frame_ms.len() as f64,
+        p95_ms: percentile(&frame_ms, 0.95),
+        p99_ms: percentile(&frame_ms, 0.99),
+        avg_steps_per_ray: total_steps as f64 / total_rays.max(1) as f64,
+        empty_chunk_skips_per_frame: total_skips as f64 / BENCH_FRAMES as f64,
+    }
+}
+
+fn runs_to_json(runs: &[BenchRun]) -> String {
+    let file = BenchFile {
+        version: BENCH_FORMAT_VERSION,
+        world_size: WORLD_SIZE,
+        runs: runs.to_vec(),
+    };
+    let mut json = serde_json::to_string_pretty(&file).expect("benchmark runs serialize");
+    json.push('\n');
+    json
+}
+
+fn runs_from_json(text: &str) -> Result<Vec<BenchRun>, String> {
+    let file: BenchFile = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    if file.version != BENCH_FORMAT_VERSION {
+        return Err(format!("unsupported benchmark version {}", file.version));
+    }
+    Ok(file.runs)
+}
+
+/// One line per matched run; returns how many runs regressed past `threshold_pct`.
+fn compare_runs(
+    base: &[BenchRun],
+    new: &[BenchRun],
+    threshold_pct: f64,
+    report: &mut String,
+) -> usize {
+    let mut regressions = 0;
+    for new_run in new {
+        let Some(base_run) = base.iter().find(|run| run.name == new_run.name) else {
+            let _ = writeln!(report, "{:<12} (no baseline)", new_run.name);
+            continue;
+        };
+
+        let metrics = [
+            ("mean", base_run.mean_ms, new_run.mean_ms),
+            ("p95", base_run.p95_ms, new_run.p95_ms),
+            ("p99", base_run.p99_ms, new_run.p99_ms),
+            (
+                "steps/ray",
+                base_run.avg_steps_per_ray,
+                new_run.avg_steps_per_ray,
+            ),
+        ];
+        let mut line = format!("{:<12}", new_run.name);
+        let mut regressed = false;
+        for (label, before, after) in metrics {
+            let change_pct = if before > 0.0 {
+                (after - before) / before * 100.0
+            } else {
+                0.0
+            };
+            let flag = if change_pct > threshold_pct {
+                regressed = true;
+                "!"
+            } else {
+                ""
+            };
+            let _ = write!(
+                line,
+                "  {} {:.2} -> {:.2} ({:+.1}%){}",
+                label, before, after, change_pct, flag
+            );
+        }
+        if regressed {
+            regressions += 1;
+            line.push_str("  REGRESSION");
+        }
+        report.push_str(&line);
+        report.push('\n');
+    }
+    for base_run in base {
+        if !new.iter().any(|run| run.name == base_run.name) {
+            let _ = writeln!(report, "{:<12} (missing from new run)", base_run.name);
+        }
+    }
+    regressions
+}
+
+fn run_compare(base_path: &str, new_path: &str, threshold_pct: f64) -> i32 {
+    let load = |path: &str| {
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| runs_from_json(&text))
+            .map_err(|e| format!("{}: {}", path, e))
+    };
+    let (base, new) = match (load(base_path), load(new_path)) {
+        (Ok(base), Ok(new)) => (base, new),
+        (Err(e), _) | (_, Err(e)) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+
+    let mut report = String::new();
+    let regressions = compare_runs(&base, &new, threshold_pct, &mut report);
+    print!("{}", report);
+    if regressions > 0 {
+        println!(
+            "{} run(s) regressed by more than {}%",
+            regressions, threshold_pct
+        );
+        1
+    } else {
+        println!("no regressions above {}%", threshold_pct);
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_run(name: &str, mean_ms: f64, steps: f64) -> BenchRun {
+        BenchRun {
+            name: name.to_string(),
+            render_width: 320,
+            render_height: 180,
+            draw_distance: 128.0,
+            frames: BENCH_FRAMES,
+            mean_ms,
+            p95_ms: mean_ms * 1.5,
+            p99_ms: mean_ms * 2.0,
+            avg_steps_per_ray: steps,
+            empty_chunk_skips_per_frame: 1234.5,
+        }
+    }
+
+    #[test]
+    fn json_round_trips_runs() {
+        let runs = vec![
+            sample_run("1x@64", 12.25, 40.5),
+            sample_run("1/4x@256", 1.5, 80.0),
+        ];
+        assert_eq!(runs_from_json(&runs_to_json(&runs)).unwrap(), runs);
+    }
+
+    #[test]
+    fn compare_flags_only_runs_past_threshold() {
+        let base = vec![sample_run("a", 10.0, 50.0), sample_run("b", 10.0, 50.0)];
+        let new = vec![sample_run("a", 10.5, 50.0), sample_run("b", 12.0, 50.0)];
+        let mut report = String::new();
+        assert_eq!(compare_runs(&base, &new, 10.0, &mut report), 1);
+        assert!(report.lines().nth(1).unwrap().ends_with("REGRESSION"));
+    }
+}
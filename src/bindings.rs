@@ -0,0 +1,733 @@
+//! Named input actions and the key chords bound to them.
+//!
+//! `ACTION_TABLE` holds each action's config name, default chords and README
+//! description. `bindings.txt` overrides defaults with lines such as
+//! `export_path_video = Shift+J` (several chords separated by spaces).
+//! `voxel-raymarcher-rust controls` prints the resulting list.
+
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use raylib::consts::KeyboardKey;
+use raylib::RaylibHandle;
+
+pub const BINDINGS_FILE: &str = "bindings.txt";
+
+const SHIFT: u8 = 1;
+const CTRL: u8 = 2;
+const ALT: u8 = 4;
+const MODIFIER_NAMES: [(u8, &str); 3] = [(SHIFT, "Shift"), (CTRL, "Ctrl"), (ALT, "Alt")];
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBack,
+    MoveLeft,
+    MoveRight,
+    MoveUp,
+    MoveDown,
+    Sprint,
+    YawLeft,
+    YawRight,
+    PitchDown,
+    PitchUp,
+    ViewplaneCloser,
+    ViewplaneFarther,
+    CycleMode,
+    ToggleMouseLook,
+    ResetCamera,
+    DrawDistanceDown,
+    DrawDistanceUp,
+    StepBudgetDown,
+    StepBudgetUp,
+    FovDown,
+    FovUp,
+    ResetRenderBudget,
+    RenderScale1x,
+    RenderScaleHalf,
+    RenderScaleQuarter,
+    RenderScaleEighth,
+    RenderScaleSixteenth,
+    RenderScaleThirtySecond,
+    RenderScaleAuto,
+    CycleUpscaler,
+    CycleSupersampling,
+    ChunkBudgetDown,
+    ChunkBudgetUp,
+    ToggleProjection,
+    IsometricPreset,
+    ToggleXray,
+    NextDebugView,
+    PreviousDebugView,
+    Screenshot,
+    Panorama,
+    Cubemap,
+    RecordKeyframe,
+    ClearPath,
+    TogglePathPlayback,
+    ExportPathFrames,
+    ExportPathVideo,
+    ToggleConsole,
+    Quit,
+}
+
+/// Config name, default chords and README description per action, in README order.
+const ACTION_TABLE: &[(Action, &str, &str, &str)] = &[
+    (Action::MoveForward, "move_forward", "W", "move forward"),
+    (Action::MoveBack, "move_back", "S", "move back"),
+    (Action::MoveLeft, "move_left", "A", "move left"),
+    (Action::MoveRight, "move_right", "D", "move right"),
+    (
+        Action::MoveUp,
+        "move_up",
+        "Space",
+        "up; jump (swim up in water) in walk mode; pause in orbit mode",
+    ),
+    (Action::MoveDown, "move_down", "LeftCtrl", "down; dive in walk mode"),
+    (
+        Action::Sprint,
+        "sprint",
+        "LeftShift",
+        "faster movement/rotation; sprint in walk mode",
+    ),
+    (Action::YawLeft, "yaw_left", "Q", "yaw left (roll left in 6-DOF mode)"),
+    (Action::YawRight, "yaw_right", "E", "yaw right (roll right in 6-DOF mode)"),
+    (Action::PitchDown, "pitch_down", "Y", "pitch down"),
+    (Action::PitchUp, "pitch_up", "H", "pitch up"),
+    (Action::ViewplaneCloser, "viewplane_closer", "T", "viewplane distance down"),
+    (Action::ViewplaneFarther, "viewplane_farther", "G", "viewplane distance up"),
+    (
+        Action::CycleMode,
+        "cycle_mode",
+        "M",
+        "cycle orbit, fly, 6-DOF and walk modes (6-DOF allows roll and looping past vertical)",
+    ),
+    (
+        Action::ToggleMouseLook,
+        "toggle_mouse_look",
+        "Tab",
+        "toggle mouse-look capture (fly, 6-DOF and walk modes)",
+    ),
+    (Action::ResetCamera, "reset_camera", "R", "reset camera"),
+    (Action::DrawDistanceDown, "draw_distance_down", "-", "draw distance down"),
+    (Action::DrawDistanceUp, "draw_distance_up", "=", "draw distance up"),
+    (Action::StepBudgetDown, "step_budget_down", ",", "DDA step budget down"),
+    (Action::StepBudgetUp, "step_budget_up", ".", "DDA step budget up"),
+    (Action::FovDown, "fov_down", "[", "FOV down"),
+    (Action::FovUp, "fov_up", "]", "FOV up"),
+    (
+        Action::ResetRenderBudget,
+        "reset_render_budget",
+        "Backspace",
+        "reset draw distance, step budget, and FOV",
+    ),
+    (Action::RenderScale1x, "render_scale_1x", "F1", "render scale `1x` (native)"),
+    (Action::RenderScaleHalf, "render_scale_half", "F2", "render scale `1/2x`"),
+    (Action::RenderScaleQuarter, "render_scale_quarter", "F3", "render scale `1/4x`"),
+    (Action::RenderScaleEighth, "render_scale_eighth", "F4", "render scale `1/8x`"),
+    (
+        Action::RenderScaleSixteenth,
+        "render_scale_sixteenth",
+        "F5",
+        "render scale `1/16x`",
+    ),
+    (
+        Action::RenderScaleThirtySecond,
+        "render_scale_thirty_second",
+        "F6",
+        "render scale `1/32x`",
+    ),
+    (
+        Action::RenderScaleAuto,
+        "render_scale_auto",
+        "F11",
+        "render scale `auto` (adjusts continuously to keep raymarching under `render_budget_ms`)",
+    ),
+    (
+        Action::CycleUpscaler,
+        "cycle_upscaler",
+        "U",
+        "cycle upscale filter: nearest, bilinear, edge (depth-aware bilinear), temporal (jittered samples accumulated over frames)",
+    ),
+    (
+        Action::CycleSupersampling,
+        "cycle_supersampling",
+        "Z",
+        "cycle supersampling: off, 2x2, rgss (4 rays per pixel on a rotated grid)",
+    ),
+    (Action::ChunkBudgetDown, "chunk_budget_down", "F7", "chunk generation budget down"),
+    (Action::ChunkBudgetUp, "chunk_budget_up", "F8", "chunk generation budget up"),
+    (
+        Action::ToggleProjection,
+        "toggle_projection",
+        "P",
+        "toggle perspective/orthographic projection",
+    ),
+    (
+        Action::IsometricPreset,
+        "isometric_preset",
+        "I",
+        "isometric preset (orthographic, 45°/35.264°, centered on the looked-at voxel)",
+    ),
+    (
+        Action::ToggleXray,
+        "toggle_xray",
+        "X",
+        "toggle x-ray view (hides stone to inspect ore veins)",
+    ),
+    (
+        Action::NextDebugView,
+        "next_debug_view",
+        "V",
+        "cycle debug views: voxel steps, chunk skips, depth, normals, materials, chunk grid",
+    ),
+    (
+        Action::PreviousDebugView,
+        "previous_debug_view",
+        "Shift+V",
+        "cycle debug views backwards",
+    ),
+    (Action::Screenshot, "screenshot", "F12", "export screenshot to `screenshots/`"),
+    (
+        Action::Panorama,
+        "panorama",
+        "F9",
+        "export a 2048x1024 equirectangular panorama from the camera position, centered on its heading, to `screenshots/`",
+    ),
+    (
+        Action::Cubemap,
+        "cubemap",
+        "F10",
+        "export six 512x512 cubemap faces (`pos_x` … `neg_z`; `neg_y` is the sky) to `screenshots/`",
+    ),
+    (
+        Action::RecordKeyframe,
+        "record_keyframe",
+        "K",
+        "record a camera keyframe (position, orientation, FOV) into `camera_path.txt`",
+    ),
+    (
+        Action::ClearPath,
+        "clear_path",
+        "L",
+        "clear the recorded camera path (`camera_path.txt` keeps it until the next `K`)",
+    ),
+    (
+        Action::TogglePathPlayback,
+        "toggle_path_playback",
+        "O",
+        "play/stop the camera path (takes over camera movement)",
+    ),
+    (
+        Action::ExportPathFrames,
+        "export_path_frames",
+        "J",
+        "export the camera path as numbered PNGs to `screenshots/` in the background (again: cancel)",
+    ),
+    (
+        Action::ExportPathVideo,
+        "export_path_video",
+        "Shift+J",
+        "export the camera path as a `.y4m` video to `screenshots/` in the background (again: cancel)",
+    ),
+    (
+        Action::ToggleConsole,
+        "toggle_console",
+        "`",
+        "open or close the command console",
+    ),
+    (Action::Quit, "quit", "Escape", "quit"),
+];
+
+/// Config-file names for the keys a chord can use.
+const KEY_NAMES: &[(&str, KeyboardKey)] = &[
+    ("A", KeyboardKey::KEY_A),
+    ("B", KeyboardKey::KEY_B),
+    ("C", KeyboardKey::KEY_C),
+    ("D", KeyboardKey::KEY_D),
+    ("E", KeyboardKey::KEY_E),
+    ("F", KeyboardKey::KEY_F),
+    ("G", KeyboardKey::KEY_G),
+    ("H", KeyboardKey::KEY_H),
+    ("I", KeyboardKey::KEY_I),
+    ("J", KeyboardKey::KEY_J),
+    ("K", KeyboardKey::KEY_K),
+    ("L", KeyboardKey::KEY_L),
+    ("M", KeyboardKey::KEY_M),
+    ("N", KeyboardKey::KEY_N),
+    ("O", KeyboardKey::KEY_O),
+    ("P", KeyboardKey::KEY_P),
+    ("Q", KeyboardKey::KEY_Q),
+    ("R", KeyboardKey::KEY_R),
+    ("S", KeyboardKey::KEY_S),
+    ("T", KeyboardKey::KEY_T),
+    ("U", KeyboardKey::KEY_U),
+    ("V", KeyboardKey::KEY_V),
+    ("W", KeyboardKey::KEY_W),
+    ("X", KeyboardKey::KEY_X),
+    ("Y", KeyboardKey::KEY_Y),
+    ("Z", KeyboardKey::KEY_Z),
+    ("0", KeyboardKey::KEY_ZERO),
+    ("1", KeyboardKey::KEY_ONE),
+    ("2", KeyboardKey::KEY_TWO),
+    ("3", KeyboardKey::KEY_THREE),
+    ("4", KeyboardKey::KEY_FOUR),
+    ("5", KeyboardKey::KEY_FIVE),
+    ("6", KeyboardKey::KEY_SIX),
+    ("7", KeyboardKey::KEY_SEVEN),
+    ("8", KeyboardKey::KEY_EIGHT),
+    ("9", KeyboardKey::KEY_NINE),
+    ("F1", KeyboardKey::KEY_F1),
+    ("F2", KeyboardKey::KEY_F2),
+    ("F3", KeyboardKey::KEY_F3),
+    ("F4", KeyboardKey::KEY_F4),
+    ("F5", KeyboardKey::KEY_F5),
+    ("F6", KeyboardKey::KEY_F6),
+    ("F7", KeyboardKey::KEY_F7),
+    ("F8", KeyboardKey::KEY_F8),
+    ("F9", KeyboardKey::KEY_F9),
+    ("F10", KeyboardKey::KEY_F10),
+    ("F11", KeyboardKey::KEY_F11),
+    ("F12", KeyboardKey::KEY_F12),
+    ("-", KeyboardKey::KEY_MINUS),
+    ("=", KeyboardKey::KEY_EQUAL),
+    (",", KeyboardKey::KEY_COMMA),
+    (".", KeyboardKey::KEY_PERIOD),
+    ("/", KeyboardKey::KEY_SLASH),
+    (";", KeyboardKey::KEY_SEMICOLON),
+    ("'", KeyboardKey::KEY_APOSTROPHE),
+    ("`", KeyboardKey::KEY_GRAVE),
+    ("[", KeyboardKey::KEY_LEFT_BRACKET),
+    ("]", KeyboardKey::KEY_RIGHT_BRACKET),
+    ("\\", KeyboardKey::KEY_BACKSLASH),
+    ("Space", KeyboardKey::KEY_SPACE),
+    ("Tab", KeyboardKey::KEY_TAB),
+    ("Enter", KeyboardKey::KEY_ENTER),
+    ("Escape", KeyboardKey::KEY_ESCAPE),
+    ("Backspace", KeyboardKey::KEY_BACKSPACE),
+    ("Insert", KeyboardKey::KEY_INSERT),
+    ("Delete", KeyboardKey::KEY_DELETE),
+    ("Home", KeyboardKey::KEY_HOME),
+    ("End", KeyboardKey::KEY_END),
+    ("PageUp", KeyboardKey::KEY_PAGE_UP),
+    ("PageDown", KeyboardKey::KEY_PAGE_DOWN),
+    ("Up", KeyboardKey::KEY_UP),
+    ("Down", KeyboardKey::KEY_DOWN),
+    ("Left", KeyboardKey::KEY_LEFT),
+    ("Right", KeyboardKey::KEY_RIGHT),
+    ("LeftShift", KeyboardKey::KEY_LEFT_SHIFT),
+    ("RightShift", KeyboardKey::KEY_RIGHT_SHIFT),
+    ("LeftCtrl", KeyboardKey::KEY_LEFT_CONTROL),
+    ("RightCtrl", KeyboardKey::KEY_RIGHT_CONTROL),
+    ("LeftAlt", KeyboardKey::KEY_LEFT_ALT),
+    ("RightAlt", KeyboardKey::KEY_RIGHT_ALT),
+    ("Kp0", KeyboardKey::KEY_KP_0),
+    ("Kp1", KeyboardKey::KEY_KP_1),
+    ("Kp2", KeyboardKey::KEY_KP_2),
+    ("Kp3", KeyboardKey::KEY_KP_3),
+    ("Kp4", KeyboardKey::KEY_KP_4),
+    ("Kp5", KeyboardKey::KEY_KP_5),
+    ("Kp6", KeyboardKey::KEY_KP_6),
+    ("Kp7", KeyboardKey::KEY_KP_7),
+    ("Kp8", KeyboardKey::KEY_KP_8),
+    ("Kp9", KeyboardKey::KEY_KP_9),
+    ("KpAdd", KeyboardKey::KEY_KP_ADD),
+    ("KpSubtract", KeyboardKey::KEY_KP_SUBTRACT),
+    ("KpMultiply", KeyboardKey::KEY_KP_MULTIPLY),
+    ("KpDivide", KeyboardKey::KEY_KP_DIVIDE),
+    ("KpEnter", KeyboardKey::KEY_KP_ENTER),
+];
+
+/// Short overlay labels for the most used actions, in hint order; each
+/// action shows its first bound chord.
+const HINT_GROUPS: &[(&str, &[Action])] = &[
+    ("Mouse Look", &[Action::ToggleMouseLook]),
+    ("Dist", &[Action::DrawDistanceDown, Action::DrawDistanceUp]),
+    (
+        "StepBudget",
+        &[Action::StepBudgetDown, Action::StepBudgetUp],
+    ),
+    ("FOV", &[Action::FovDown, Action::FovUp]),
+    (
+        "Scale",
+        &[
+            Action::RenderScale1x,
+            Action::RenderScaleHalf,
+            Action::RenderScaleQuarter,
+            Action::RenderScaleEighth,
+            Action::RenderScaleSixteenth,
+            Action::RenderScaleThirtySecond,
+            Action::RenderScaleAuto,
+        ],
+    ),
+    ("Upscale", &[Action::CycleUpscaler]),
+    ("SSAA", &[Action::CycleSupersampling]),
+    ("Gen", &[Action::ChunkBudgetDown, Action::ChunkBudgetUp]),
+    (
+        "Proj/Iso",
+        &[Action::ToggleProjection, Action::IsometricPreset],
+    ),
+    ("X-Ray", &[Action::ToggleXray]),
+    ("Debug View", &[Action::NextDebugView]),
+    ("Pano/Cube", &[Action::Panorama, Action::Cubemap]),
+    (
+        "Path",
+        &[
+            Action::RecordKeyframe,
+            Action::ClearPath,
+            Action::TogglePathPlayback,
+            Action::ExportPathFrames,
+        ],
+    ),
+    ("Shot", &[Action::Screenshot]),
+    ("Console", &[Action::ToggleConsole]),
+    ("Reset", &[Action::ResetRenderBudget]),
+];
+
+impl Action {
+    fn entry(self) -> &'static (Action, &'static str, &'static str, &'static str) {
+        ACTION_TABLE
+            .iter()
+            .find(|entry| entry.0 == self)
+            .expect("every action has an ACTION_TABLE entry")
+    }
+
+    pub fn name(self) -> &'static str {
+        self.entry().1
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        ACTION_TABLE
+            .iter()
+            .find(|entry| entry.1 == name)
+            .map(|entry| entry.0)
+    }
+}
+
+/// A key plus the modifiers (`SHIFT`/`CTRL`/`ALT` bits) that must be held with it.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KeyChord {
+    pub key: KeyboardKey,
+    modifiers: u8,
+}
+
+impl KeyChord {
+    /// Parses `Key` or `Mod+...+Key`, e.g. `Shift+J` or `Ctrl+Alt+F5`.
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = text.split('+').collect();
+        let key_name = parts.pop().unwrap_or_default();
+        let mut modifiers = 0;
+        for part in parts {
+            let Some(&(bit, _)) = MODIFIER_NAMES
+                .iter()
+                .find(|(_, name)| name.eq_ignore_ascii_case(part))
+            else {
+                return Err(format!("unknown modifier `{}` in `{}`", part, text));
+            };
+            modifiers |= bit;
+        }
+        let Some(&(_, key)) = KEY_NAMES
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(key_name))
+        else {
+            return Err(format!("unknown key `{}`", key_name));
+        };
+        Ok(Self { key, modifiers })
+    }
+
+    fn label(&self) -> String {
+        let mut label = String::new();
+        for (bit, name) in MODIFIER_NAMES {
+            if self.modifiers & bit != 0 {
+                label.push_str(name);
+                label.push('+');
+            }
+        }
+        let key_name = KEY_NAMES
+            .iter()
+            .find(|(_, key)| *key == self.key)
+            .map_or("?", |(name, _)| name);
+        label.push_str(key_name);
+        label
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    chords: Vec<(Action, KeyChord)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let chords = ACTION_TABLE
+            .iter()
+            .flat_map(|&(action, _, defaults, _)| {
+                defaults.split_whitespace().map(move |text| {
+                    let chord = KeyChord::parse(text).expect("default bindings parse");
+                    (action, chord)
+                })
+            })
+            .collect();
+        Self { chords }
+    }
+}
+
+impl Bindings {
+    /// Defaults overridden by `path` if it exists, plus every problem found
+    /// (unreadable file, unknown actions or keys, chords bound twice).
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Self::default(), Vec::new()),
+            Err(e) => (
+                Self::default(),
+                vec![format!("cannot read {}: {}", path.display(), e)],
+            ),
+        }
+    }
+
+    fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut bindings = Self::default();
+        let mut problems = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_number + 1;
+            let Some((name, chords)) = line.split_once('=') else {
+                problems.push(format!("line {}: expected `action = Key`", line_number));
+                continue;
+            };
+            let Some(action) = Action::from_name(name.trim()) else {
+                problems.push(format!(
+                    "line {}: unknown action `{}`",
+                    line_number,
+                    name.trim()
+                ));
+                continue;
+            };
+
+            let mut parsed = Vec::new();
+            for text in chords.split_whitespace() {
+                match KeyChord::parse(text) {
+                    Ok(chord) => parsed.push((action, chord)),
+                    Err(e) => problems.push(format!("line {}: {}", line_number, e)),
+                }
+            }
+            bindings.chords.retain(|(bound, _)| *bound != action);
+            bindings.chords.extend(parsed);
+        }
+        problems.extend(bindings.conflicts());
+        (bindings, problems)
+    }
+
+    fn conflicts(&self) -> Vec<String> {
+        let mut conflicts = Vec::new();
+        for (index, (action, chord)) in self.chords.iter().enumerate() {
+            for (other_action, other_chord) in &self.chords[index + 1..] {
+                if chord == other_chord && action != other_action {
+                    conflicts.push(format!(
+                        "`{}` is bound to both {} and {}",
+                        chord.label(),
+                        action.name(),
+                        other_action.name()
+                    ));
+                }
+            }
+        }
+        conflicts
+    }
+
+    fn held_modifiers(rl: &RaylibHandle) -> u8 {
+        let held = |left, right| rl.is_key_down(left) || rl.is_key_down(right);
+        let mut modifiers = 0;
+        if held(KeyboardKey::KEY_LEFT_SHIFT, KeyboardKey::KEY_RIGHT_SHIFT) {
+            modifiers |= SHIFT;
+        }
+        if held(
+            KeyboardKey::KEY_LEFT_CONTROL,
+            KeyboardKey::KEY_RIGHT_CONTROL,
+        ) {
+            modifiers |= CTRL;
+        }
+        if held(KeyboardKey::KEY_LEFT_ALT, KeyboardKey::KEY_RIGHT_ALT) {
+            modifiers |= ALT;
+        }
+        modifiers
+    }
+
+    /// Whether `chord` applies with `held` modifiers: its own modifiers are
+    /// down and no chord on the same key needing more of the held ones exists.
+    /// Extra modifiers are otherwise ignored, so `W` still moves while
+    /// sprinting, but `Shift+J` wins over `J`.
+    fn chord_applies(&self, chord: &KeyChord, held: u8) -> bool {
+        let satisfied = |modifiers: u8| modifiers & !held == 0;
+        satisfied(chord.modifiers)
+            && !self.chords.iter().any(|(_, other)| {
+                other.key == chord.key
+                    && satisfied(other.modifiers)
+                    && other.modifiers.count_ones() > chord.modifiers.count_ones()
+            })
+    }
+
+    pub fn pressed(&self, rl: &RaylibHandle, action: Action) -> bool {
+        let held = Self::held_modifiers(rl);
+        self.chords.iter().any(|(bound, chord)| {
+            *bound == action && rl.is_key_pressed(chord.key) && self.chord_applies(chord, held)
+        })
+    }
+
+    pub fn down(&self, rl: &RaylibHandle, action: Action) -> bool {
+        let held = Self::held_modifiers(rl);
+        self.chords.iter().any(|(bound, chord)| {
+            *bound == action && rl.is_key_down(chord.key) && self.chord_applies(chord, held)
+        })
+    }
+
+    /// One-line overlay hint with the keys currently bound to common actions.
+    pub fn key_hint(&self) -> String {
+        let groups: Vec<String> = HINT_GROUPS
+            .iter()
+            .filter_map(|&(label, actions)| {
+                let keys: Vec<String> = actions
+                    .iter()
+                    .filter_map(|&action| {
+                        self.chords
+                            .iter()
+                            .find(|(bound, _)| *bound == action)
+                            .map(|(_, chord)| chord.label())
+                    })
+                    .collect();
+                (!keys.is_empty()).then(|| format!("{} {}", keys.join("/"), label))
+            })
+            .collect();
+        format!("Keys: {}", groups.join(", "))
+    }
+
+    /// Markdown list of every bound action, as embedded in the README.
+    pub fn markdown_list(&self) -> String {
+        let mut list = String::new();
+        for &(action, _, _, description) in ACTION_TABLE {
+            let labels: Vec<String> = self
+                .chords
+                .iter()
+                .filter(|(bound, _)| *bound == action)
+                .map(|(_, chord)| code_span(&chord.label()))
+                .collect();
+            if labels.is_empty() {
+                continue;
+            }
+            list.push_str(&format!("- {}: {}\n", labels.join(" / "), description));
+        }
+        list
+    }
+}
+
+/// Markdown inline code, padded with double backticks when `text` holds one.
+fn code_span(text: &str) -> String {
+    if text.contains('`') {
+        format!("`` {} ``", text)
+    } else {
+        format!("`{}`", text)
+    }
+}
+
+/// Handles the `controls` subcommand, printing the effective bindings as the
+/// README's markdown list; `None` when `args` is something else.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    if args.first().map(String::as_str) != Some("controls") {
+        return None;
+    }
+    let (bindings, problems) = Bindings::load(Path::new(BINDINGS_FILE));
+    print!("{}", bindings.markdown_list());
+    for problem in &problems {
+        eprintln!("{}: {}", BINDINGS_FILE, problem);
+    }
+    Some(if problems.is_empty() { 0 } else { 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const README_BEGIN: &str = "<!-- controls:begin -->\n";
+    const README_END: &str = "<!-- controls:end -->";
+
+    #[test]
+    fn default_table_has_every_action_once_and_no_conflicts() {
+        let bindings = Bindings::default();
+        assert!(
+            bindings.conflicts().is_empty(),
+            "{:?}",
+            bindings.conflicts()
+        );
+        for (index, entry) in ACTION_TABLE.iter().enumerate() {
+            assert!(
+                ACTION_TABLE[index + 1..]
+                    .iter()
+                    .all(|other| other.0 != entry.0 && other.1 != entry.1),
+                "{} listed twice",
+                entry.1
+            );
+        }
+    }
+
+    #[test]
+    fn config_overrides_and_reports_problems() {
+        let (bindings, problems) = Bindings::parse(
+            "# comment\n\
+             move_forward = Up ctrl+W\n\
+             fly_faster = F11\n\
+             screenshot = Hyper+P\n\
+             quit = Tab\n",
+        );
+        let forward: Vec<String> = bindings
+            .chords
+            .iter()
+            .filter(|(action, _)| *action == Action::MoveForward)
+            .map(|(_, chord)| chord.label())
+            .collect();
+        assert_eq!(forward, ["Up", "Ctrl+W"]);
+        assert_eq!(problems.len(), 3, "{problems:?}");
+        assert!(problems[0].contains("unknown action `fly_faster`"));
+        assert!(problems[1].contains("unknown modifier `Hyper`"));
+        assert!(problems[2].contains("`Tab` is bound to both toggle_mouse_look and quit"));
+    }
+
+    #[test]
+    fn more_specific_chord_wins_on_shared_key() {
+        let bindings = Bindings::default();
+        let plain_j = KeyChord::parse("J").unwrap();
+        let shift_j = KeyChord::parse("Shift+J").unwrap();
+        let plain_w = KeyChord::parse("W").unwrap();
+        assert!(bindings.chord_applies(&plain_j, 0));
+        assert!(!bindings.chord_applies(&plain_j, SHIFT));
+        assert!(bindings.chord_applies(&shift_j, SHIFT));
+        assert!(!bindings.chord_applies(&shift_j, 0));
+        assert!(bindings.chord_applies(&plain_w, SHIFT | CTRL));
+    }
+
+    #[test]
+    fn key_hint_follows_rebinding() {
+        assert!(Bindings::default().key_hint().contains(", X X-Ray,"));
+        let (bindings, problems) = Bindings::parse("toggle_xray = B\n");
+        assert!(problems.is_empty(), "{problems:?}");
+        let hint = bindings.key_hint();
+        assert!(hint.contains(", B X-Ray,"), "{hint}");
+        assert!(!hint.contains("X X-Ray"), "{hint}");
+    }
+
+    #[test]
+    fn readme_controls_list_matches_default_table() {
+        let readme = include_str!("../README.md");
+        let start = readme.find(README_BEGIN).expect("controls:begin marker") + README_BEGIN.len();
+        let end = readme.find(README_END).expect("controls:end marker");
+        let expected = Bindings::default().markdown_list();
+        assert!(
+            readme[start..end] == expected,
+            "README controls list is out of date; replace it with:\n{expected}"
+        );
+    }
+}
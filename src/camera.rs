@@ -1,48 +1,141 @@
-use glam::{Quat, Vec3};
+use glam::{Mat3, Quat, Vec3};
 
+/// Position plus a full orientation. The local basis is +Z forward, +X right
+/// and +Y down, matching the world's y-down convention at identity.
 #[derive(Debug, Clone)]
 pub struct Camera {
     pub pos: Vec3,
-    pub dir: Vec3,
+    pub orientation: Quat,
     pub original_pos: Vec3,
-    pub original_dir: Vec3,
+    pub original_orientation: Quat,
     pub viewplane_distance: f32,
 }
 
 const UP: Vec3 = Vec3::new(0.0, -1.0, 0.0);
+pub const ISOMETRIC_YAW_DEG: f32 = 45.0;
+/// `atan(1 / sqrt(2))`: all three axes foreshorten equally.
+pub const ISOMETRIC_PITCH_DEG: f32 = 35.264;
+
+/// View direction for the isometric preset, pitched down (towards +y).
+pub fn isometric_dir() -> Vec3 {
+    let yaw = ISOMETRIC_YAW_DEG.to_radians();
+    let pitch = ISOMETRIC_PITCH_DEG.to_radians();
+    Vec3::new(
+        pitch.cos() * yaw.sin(),
+        pitch.sin(),
+        pitch.cos() * yaw.cos(),
+    )
+}
+
+/// Roll-free orientation looking along `dir`. Straight up or down keeps the
+/// image's top towards +z so the result is still well defined.
+pub fn upright_orientation(dir: Vec3) -> Quat {
+    let forward = dir.normalize();
+    let right = forward.cross(UP);
+    let right = if right.length_squared() > 1e-8 {
+        right.normalize()
+    } else {
+        forward.cross(Vec3::Z).normalize()
+    };
+    let down = forward.cross(right);
+    Quat::from_mat3(&Mat3::from_cols(right, down, forward)).normalize()
+}
 
 impl Camera {
     pub fn new(pos: Vec3, dir: Vec3, viewplane_distance: f32) -> Self {
-        let normalized_dir = dir.normalize();
+        let orientation = upright_orientation(dir);
         Self {
             pos,
-            dir: normalized_dir,
+            orientation,
             original_pos: pos,
-            original_dir: normalized_dir,
+            original_orientation: orientation,
             viewplane_distance,
         }
     }
 
     pub fn reset(&mut self) {
         self.pos = self.original_pos;
-        self.dir = self.original_dir;
+        self.orientation = self.original_orientation;
+    }
+
+    pub fn dir(&self) -> Vec3 {
+        self.orientation * Vec3::Z
+    }
+
+    /// Points the camera along `dir` with no roll.
+    pub fn set_dir(&mut self, dir: Vec3) {
+        self.orientation = upright_orientation(dir);
+    }
+
+    /// Removes any roll while keeping the view direction.
+    pub fn level(&mut self) {
+        self.set_dir(self.dir());
     }
 
     pub fn get_right(&self) -> Vec3 {
-        self.dir.cross(UP).normalize()
+        self.orientation * Vec3::X
     }
 
     pub fn get_down(&self) -> Vec3 {
-        let right = self.get_right();
-        right.cross(self.dir).normalize() * -1.0
+        self.orientation * Vec3::Y
     }
 
     pub fn get_up(&self) -> Vec3 {
         self.get_down() * -1.0
     }
 
+    /// Rotates about a world-space axis.
     pub fn rotate(&mut self, axis: Vec3, angle: f32) {
         let rotation = Quat::from_axis_angle(axis.normalize(), angle);
-        self.dir = rotation * self.dir;
+        self.orientation = (rotation * self.orientation).normalize();
+    }
+
+    /// Rotates about the camera's own axes: `pitch` around right, `yaw`
+    /// around down and `roll` around forward.
+    pub fn rotate_local(&mut self, pitch: f32, yaw: f32, roll: f32) {
+        let rotation =
+            Quat::from_rotation_x(pitch) * Quat::from_rotation_y(yaw) * Quat::from_rotation_z(roll);
+        self.orientation = (self.orientation * rotation).normalize();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: Vec3, b: Vec3) {
+        assert!((a - b).length() < 1e-5, "{a} != {b}");
+    }
+
+    #[test]
+    fn upright_basis_matches_world_up() {
+        let dir = Vec3::new(0.3, 0.4, -1.0).normalize();
+        let camera = Camera::new(Vec3::ZERO, dir, 3.0);
+        let right = dir.cross(UP).normalize();
+        assert_close(camera.dir(), dir);
+        assert_close(camera.get_right(), right);
+        assert_close(camera.get_down(), -right.cross(dir).normalize());
+    }
+
+    #[test]
+    fn pitching_past_vertical_flips_the_view_over() {
+        let mut camera = Camera::new(Vec3::ZERO, Vec3::Z, 3.0);
+        let up_before = camera.get_up();
+        camera.rotate_local(std::f32::consts::PI, 0.0, 0.0);
+        assert_close(camera.dir(), -Vec3::Z);
+        assert_close(camera.get_up(), -up_before);
+
+        camera.level();
+        assert_close(camera.dir(), -Vec3::Z);
+        assert_close(camera.get_up(), UP);
+    }
+
+    #[test]
+    fn roll_keeps_direction() {
+        let mut camera = Camera::new(Vec3::ZERO, Vec3::new(1.0, 0.0, 1.0), 3.0);
+        let dir = camera.dir();
+        camera.rotate_local(0.0, 0.0, 0.7);
+        assert_close(camera.dir(), dir);
+        assert!(camera.get_up().dot(UP) < 0.8);
     }
 }
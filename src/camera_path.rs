@@ -0,0 +1,456 @@
+//! Recorded camera keyframes, Catmull-Rom playback and offline frame export.
+//!
+//! `voxel-raymarcher-rust render-path <path.txt> <out_dir | out.y4m> [width height]`
+//! renders a saved path against the fully generated demo world.
+
+use std::fs;
+use std::io;
+use std::ops::{Add, Mul, Sub};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Instant;
+
+use glam::{Quat, Vec2, Vec3, Vec4};
+
+use crate::camera::Camera;
+use crate::image_io::{self, Y4mWriter};
+use crate::raymarch::{self, DebugView, RaymarchInput, Supersampling};
+use crate::state::DEFAULT_DRAW_DISTANCE;
+use crate::viewplane::{size_for_fov_y, Projection, Viewplane};
+use crate::world::{MaterialId, World};
+use crate::{DIMS, VOXEL_STEP_BUDGET};
+
+/// Where the viewer keeps the path it records and plays back.
+pub const CAMERA_PATH_FILE: &str = "camera_path.txt";
+pub const SECONDS_PER_KEYFRAME: f32 = 2.0;
+pub const EXPORT_FPS: u32 = 30;
+const EXPORT_VIEWPLANE_DISTANCE: f32 = 3.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Keyframe {
+    pub pos: Vec3,
+    pub orientation: Quat,
+    pub fov_y_deg: f32,
+}
+
+/// Keyframes spaced `SECONDS_PER_KEYFRAME` apart.
+#[derive(Debug, Clone, Default)]
+pub struct CameraPath {
+    pub keyframes: Vec<Keyframe>,
+}
+
+fn catmull_rom<T>(p0: T, p1: T, p2: T, p3: T, t: f32) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<f32, Output = T>,
+{
+    let t2 = t * t;
+    let t3 = t2 * t;
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.len().saturating_sub(1) as f32 * SECONDS_PER_KEYFRAME
+    }
+
+    /// Camera state `time` seconds into the path, clamped to its ends.
+    pub fn sample(&self, time: f32) -> Option<Keyframe> {
+        let last = self.keyframes.len().checked_sub(1)?;
+        let position = (time / SECONDS_PER_KEYFRAME).clamp(0.0, last as f32);
+        let segment = (position.floor() as usize).min(last.saturating_sub(1));
+        let t = position - segment as f32;
+        let key = |offset: isize| {
+            let index = (segment as isize + offset).clamp(0, last as isize) as usize;
+            self.keyframes[index]
+        };
+        let (k0, k1, k2, k3) = (key(-1), key(0), key(1), key(2));
+
+        // Keep neighbouring quaternions in one hemisphere so the spline takes
+        // the short way round.
+        let q1 = Vec4::from(k1.orientation);
+        let align = |q: Quat| {
+            let q = Vec4::from(q);
+            if q.dot(q1) < 0.0 {
+                -q
+            } else {
+                q
+            }
+        };
+        let orientation = catmull_rom(
+            align(k0.orientation),
+            q1,
+            align(k2.orientation),
+            align(k3.orientation),
+            t,
+        );
+
+        Some(Keyframe {
+            pos: catmull_rom(k0.pos, k1.pos, k2.pos, k3.pos, t),
+            orientation: Quat::from_vec4(orientation).normalize(),
+            fov_y_deg: catmull_rom(k0.fov_y_deg, k1.fov_y_deg, k2.fov_y_deg, k3.fov_y_deg, t),
+        })
+    }
+
+    /// One keyframe per line: `x y z qx qy qz qw fov_y_deg`.
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        let mut text = String::from("# x y z qx qy qz qw fov_y_deg\n");
+        for key in &self.keyframes {
+            let q = key.orientation;
+            text.push_str(&format!(
+                "{} {} {} {} {} {} {} {}\n",
+                key.pos.x, key.pos.y, key.pos.z, q.x, q.y, q.z, q.w, key.fov_y_deg
+            ));
+        }
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        let text = fs::read_to_string(path)?;
+        let mut keyframes = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let values: Vec<f32> = line
+                .split_whitespace()
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .ok()
+                .filter(|values: &Vec<f32>| values.len() == 8)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        format!("{}:{}: expected 8 numbers", path.display(), line_number + 1),
+                    )
+                })?;
+            keyframes.push(Keyframe {
+                pos: Vec3::new(values[0], values[1], values[2]),
+                orientation: Quat::from_xyzw(values[3], values[4], values[5], values[6])
+                    .normalize(),
+                fov_y_deg: values[7],
+            });
+        }
+        Ok(Self { keyframes })
+    }
+}
+
+/// Render settings shared by every exported frame.
+#[derive(Debug, Copy, Clone)]
+pub struct PathExport {
+    pub projection: Projection,
+    pub draw_distance: f32,
+    pub voxel_step_budget: f32,
+    pub hidden_material: Option<MaterialId>,
+    pub supersampling: Supersampling,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub enum ExportTarget {
+    /// `frame_00000.png`, `frame_00001.png`, ... inside this directory.
+    PngSequence(PathBuf),
+    Y4m(PathBuf),
+}
+
+enum FrameSink<'a> {
+    Png(&'a Path),
+    Video(Y4mWriter),
+}
+
+/// How far an export got; `cancelled` asks it to stop before the next frame.
+#[derive(Debug, Default)]
+pub struct ExportProgress {
+    pub frames_done: AtomicUsize,
+    /// Zero until terrain generation is done and rendering starts.
+    pub frame_count: AtomicUsize,
+    pub cancelled: AtomicBool,
+}
+
+/// A path export running on its own thread, so the viewer stays responsive.
+pub struct PathExportJob {
+    /// Where the frames go, for messages.
+    pub label: String,
+    pub progress: Arc<ExportProgress>,
+    handle: JoinHandle<io::Result<usize>>,
+}
+
+impl PathExportJob {
+    /// Generates the rest of `world`'s terrain, then renders `path` into `target`.
+    pub fn spawn(
+        path: CameraPath,
+        mut world: Box<World>,
+        export: PathExport,
+        target: ExportTarget,
+        label: String,
+    ) -> Self {
+        let progress = Arc::new(ExportProgress::default());
+        let thread_progress = Arc::clone(&progress);
+        let handle = std::thread::spawn(move || {
+            world.gen_all_terrain_columns();
+            export_frames(&path, &world, &export, &target, &thread_progress)
+        });
+        Self {
+            label,
+            progress,
+            handle,
+        }
+    }
+
+    pub fn cancel(&self) {
+        self.progress.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// The export's result once its thread is done; otherwise the job back.
+    pub fn try_finish(self) -> Result<io::Result<usize>, Self> {
+        if !self.handle.is_finished() {
+            return Err(self);
+        }
+        Ok(self
+            .handle
+            .join()
+            .unwrap_or_else(|_| Err(io::Error::other("path export thread panicked"))))
+    }
+}
+
+/// Renders the path at `EXPORT_FPS` through `draw_voxels`, returning the frame count.
+pub fn export_frames(
+    path: &CameraPath,
+    world: &World,
+    export: &PathExport,
+    target: &ExportTarget,
+    progress: &ExportProgress,
+) -> io::Result<usize> {
+    let frame_count = (path.duration() * EXPORT_FPS as f32).floor() as usize + 1;
+    progress.frame_count.store(frame_count, Ordering::Relaxed);
+    let aspect = export.width as f32 / export.height as f32;
+    let mut pixels = vec![0u8; export.width as usize * export.height as usize * 4];
+    let mut sink = match target {
+        ExportTarget::Y4m(file) => FrameSink::Video(Y4mWriter::create(
+            file,
+            export.width,
+            export.height,
+            EXPORT_FPS,
+        )?),
+        ExportTarget::PngSequence(dir) => {
+            fs::create_dir_all(dir)?;
+            FrameSink::Png(dir)
+        }
+    };
+
+    for frame in 0..frame_count {
+        if progress.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(
+                io::ErrorKind::Interrupted,
+                format!("cancelled after {} frames", frame),
+            ));
+        }
+        let Some(key) = path.sample(frame as f32 / EXPORT_FPS as f32) else {
+            break;
+        };
+        let mut camera = Camera::new(key.pos, Vec3::Z, EXPORT_VIEWPLANE_DISTANCE);
+        camera.orientation = key.orientation;
+        let viewplane = Viewplane::new(
+            size_for_fov_y(key.fov_y_deg, EXPORT_VIEWPLANE_DISTANCE, aspect),
+            aspect,
+        );
+        raymarch::draw_voxels(
+            RaymarchInput {
+                world,
+                camera: &camera,
+                viewplane: &viewplane,
+                projection: export.projection,
+                draw_distance: export.draw_distance,
+                voxel_step_budget: export.voxel_step_budget,
+                hidden_material: export.hidden_material,
+                debug_view: DebugView::Shaded,
+                jitter: Vec2::ZERO,
+                supersampling: export.supersampling,
+            },
+            &mut pixels,
+            export.width as i32,
+            export.height as i32,
+        );
+
+        match &mut sink {
+            FrameSink::Video(video) => video.write_frame(&pixels)?,
+            FrameSink::Png(dir) => image_io::write_png(
+                &dir.join(format!("frame_{frame:05}.png")),
+                export.width,
+                export.height,
+                &pixels,
+            )?,
+        }
+        progress.frames_done.store(frame + 1, Ordering::Relaxed);
+    }
+
+    if let FrameSink::Video(video) = sink {
+        video.finish()?;
+    }
+    Ok(frame_count)
+}
+
+/// `.y4m` files become a video; anything else is a directory of PNGs.
+fn export_target_for(out: &str) -> ExportTarget {
+    if out.ends_with(".y4m") {
+        ExportTarget::Y4m(PathBuf::from(out))
+    } else {
+        ExportTarget::PngSequence(PathBuf::from(out))
+    }
+}
+
+/// Handles the `render-path` subcommand; `None` when `args` is something else.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    if args.first().map(String::as_str) != Some("render-path") {
+        return None;
+    }
+    let (Some(path_file), Some(out)) = (args.get(1), args.get(2)) else {
+        eprintln!("usage: render-path <path.txt> <out_dir | out.y4m> [width height]");
+        return Some(2);
+    };
+    let (width, height) = match (args.get(3), args.get(4)) {
+        (None, None) => (DIMS.x, DIMS.y),
+        (Some(width), Some(height)) => match (width.parse::<u32>(), height.parse::<u32>()) {
+            (Ok(width), Ok(height)) if width > 0 && height > 0 => (width, height),
+            _ => {
+                eprintln!("width and height must be positive integers");
+                return Some(2);
+            }
+        },
+        _ => {
+            eprintln!("give both width and height, or neither");
+            return Some(2);
+        }
+    };
+
+    let path = match CameraPath::load(Path::new(path_file)) {
+        Ok(path) if path.keyframes.len() >= 2 => path,
+        Ok(_) => {
+            eprintln!("{} needs at least two keyframes", path_file);
+            return Some(1);
+        }
+        Err(e) => {
+            eprintln!("failed to read {}: {}", path_file, e);
+            return Some(1);
+        }
+    };
+
+    let world = crate::benchmark::pregenerate_world();
+    let start = Instant::now();
+    let export = PathExport {
+        projection: Projection::Perspective,
+        draw_distance: DEFAULT_DRAW_DISTANCE,
+        voxel_step_budget: VOXEL_STEP_BUDGET,
+        hidden_material: None,
+        supersampling: Supersampling::Off,
+        width,
+        height,
+    };
+    let target = export_target_for(out);
+    match export_frames(&path, &world, &export, &target, &ExportProgress::default()) {
+        Ok(frames) => {
+            eprintln!(
+                "rendered {} frames to {} in {:.1}s",
+                frames,
+                out,
+                start.elapsed().as_secs_f32()
+            );
+            Some(0)
+        }
+        Err(e) => {
+            eprintln!("failed to export {}: {}", out, e);
+            Some(1)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::camera::upright_orientation;
+
+    fn keyframe(x: f32, yaw_dir: Vec3, fov_y_deg: f32) -> Keyframe {
+        Keyframe {
+            pos: Vec3::new(x, -2.0 * x, 0.5 * x * x),
+            orientation: upright_orientation(yaw_dir),
+            fov_y_deg,
+        }
+    }
+
+    fn demo_path() -> CameraPath {
+        CameraPath {
+            keyframes: vec![
+                keyframe(0.0, Vec3::Z, 60.0),
+                keyframe(3.0, Vec3::X, 70.0),
+                keyframe(5.0, -Vec3::Z, 45.0),
+                keyframe(9.0, Vec3::new(1.0, 0.5, 1.0), 90.0),
+            ],
+        }
+    }
+
+    #[test]
+    fn spline_passes_through_keyframes_and_clamps() {
+        let path = demo_path();
+        assert_eq!(path.duration(), 3.0 * SECONDS_PER_KEYFRAME);
+        for (index, key) in path.keyframes.iter().enumerate() {
+            let sample = path.sample(index as f32 * SECONDS_PER_KEYFRAME).unwrap();
+            assert!((sample.pos - key.pos).length() < 1e-4);
+            assert!(sample.orientation.angle_between(key.orientation) < 1e-3);
+            assert!((sample.fov_y_deg - key.fov_y_deg).abs() < 1e-4);
+        }
+        assert_eq!(path.sample(-1.0), path.sample(0.0));
+        assert_eq!(path.sample(100.0), path.sample(path.duration()));
+        assert!(CameraPath::default().sample(0.0).is_none());
+    }
+
+    #[test]
+    fn cancelled_export_stops_before_rendering() {
+        let world = World::new(32);
+        let export = PathExport {
+            projection: Projection::Perspective,
+            draw_distance: DEFAULT_DRAW_DISTANCE,
+            voxel_step_budget: VOXEL_STEP_BUDGET,
+            hidden_material: None,
+            supersampling: Supersampling::Off,
+            width: 8,
+            height: 6,
+        };
+        let dir = std::env::temp_dir().join(format!("camera_path_frames_{}", std::process::id()));
+        let progress = ExportProgress::default();
+        progress.cancelled.store(true, Ordering::Relaxed);
+        let result = export_frames(
+            &demo_path(),
+            &world,
+            &export,
+            &ExportTarget::PngSequence(dir.clone()),
+            &progress,
+        );
+        let written = fs::read_dir(&dir).unwrap().count();
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::Interrupted);
+        assert_eq!(written, 0);
+        assert_eq!(progress.frames_done.load(Ordering::Relaxed), 0);
+        assert!(progress.frame_count.load(Ordering::Relaxed) > 0);
+    }
+
+    #[test]
+    fn save_and_load_round_trip() {
+        let path = demo_path();
+        let file = std::env::temp_dir().join(format!("camera_path_{}.txt", std::process::id()));
+        path.save(&file).unwrap();
+        let loaded = CameraPath::load(&file).unwrap();
+        fs::remove_file(&file).unwrap();
+        assert_eq!(loaded.keyframes.len(), path.keyframes.len());
+        for (loaded, saved) in loaded.keyframes.iter().zip(&path.keyframes) {
+            assert_eq!(loaded.pos, saved.pos);
+            assert_eq!(loaded.fov_y_deg, saved.fov_y_deg);
+            assert!(loaded.orientation.angle_between(saved.orientation) < 1e-5);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+//! Drop-down command console and the small command language it runs.
+//!
+//! Each line is a command name followed by whitespace-separated arguments;
+//! scripts hold one command per line with `#` comments. `startup.txt` runs
+//! when the viewer starts, and `save` writes a script that restores the
+//! current session.
+
+use std::fs;
+use std::path::Path;
+
+use glam::{IVec3, Vec3};
+
+use crate::image_io;
+use crate::player::Player;
+use crate::raymarch;
+use crate::settings::{Settings, RUNTIME_SETTINGS};
+use crate::state::{Mode, State};
+use crate::world_generation::MATERIAL_NAMES;
+use crate::UP;
+
+pub const STARTUP_SCRIPT: &str = "startup.txt";
+
+const MAX_LOG_LINES: usize = 200;
+const MAX_HISTORY: usize = 100;
+/// Scripts may `exec` other scripts, but not endlessly.
+const MAX_SCRIPT_DEPTH: usize = 8;
+const MAX_PITCH_DEG: f32 = 89.0;
+
+/// Name, usage and description of every command, in `help` order.
+const COMMANDS: &[(&str, &str, &str)] = &[
+    ("help", "help", "list commands"),
+    ("tp", "tp x y z", "move the camera"),
+    (
+        "look",
+        "look yaw pitch",
+        "face a direction in degrees; yaw 0 looks along +z",
+    ),
+    (
+        "set",
+        "set [key value]",
+        "change a runtime setting, or list them",
+    ),
+    (
+        "fill",
+        "fill x0 y0 z0 x1 y1 z1 material",
+        "fill a box with a named or #rrggbb[aa] material",
+    ),
+    ("seed", "seed n", "regenerate the world with another seed"),
+    (
+        "screenshot",
+        "screenshot name",
+        "render the view to screenshots/<name>.png",
+    ),
+    (
+        "save",
+        "save path",
+        "write a script restoring the seed, settings, fills and camera",
+    ),
+    ("exec", "exec path", "run a script"),
+    ("clear", "clear", "clear the console"),
+];
+
+#[derive(Debug, Default)]
+pub struct Console {
+    pub open: bool,
+    pub input: String,
+    pub log: Vec<String>,
+    history: Vec<String>,
+    /// Entry of `history` shown in `input` while browsing with Up/Down.
+    history_cursor: Option<usize>,
+    /// `fill` commands since the world was last regenerated, replayed by `save`.
+    edits: Vec<String>,
+}
+
+impl Console {
+    pub fn print(&mut self, line: impl Into<String>) {
+        self.log.push(line.into());
+        if self.log.len() > MAX_LOG_LINES {
+            self.log.drain(..self.log.len() - MAX_LOG_LINES);
+        }
+    }
+
+    pub fn history_prev(&mut self) {
+        if self.history.is_empty() {
+            return;
+        }
+        let cursor = self
+            .history_cursor
+            .map_or(self.history.len() - 1, |cursor| cursor.saturating_sub(1));
+        self.history_cursor = Some(cursor);
+        self.input = self.history[cursor].clone();
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_cursor {
+            Some(cursor) if cursor + 1 < self.history.len() => {
+                self.history_cursor = Some(cursor + 1);
+                self.input = self.history[cursor + 1].clone();
+            }
+            Some(_) => {
+                self.history_cursor = None;
+                self.input.clear();
+            }
+            None => {}
+        }
+    }
+
+    fn remember(&mut self, line: &str) {
+        self.history_cursor = None;
+        if line.is_empty() || self.history.last().map(String::as_str) == Some(line) {
+            return;
+        }
+        self.history.push(line.to_string());
+        if self.history.len() > MAX_HISTORY {
+            self.history.remove(0);
+        }
+    }
+
+    /// Completes the word being typed: command names first, then setting
+    /// keys after `set` and material names as `fill`'s last argument.
+    /// Several matches extend to their common prefix and are listed.
+    pub fn complete(&mut self) {
+        let input = self.input.clone();
+        let words: Vec<&str> = input.split_whitespace().collect();
+        let starts_new_word = input.is_empty() || input.ends_with(' ');
+        let (index, prefix) = if starts_new_word {
+            (words.len(), "")
+        } else {
+            (words.len() - 1, words[words.len() - 1])
+        };
+        let candidates: Vec<&str> = match (index, words.first().copied()) {
+            (0, _) => COMMANDS.iter().map(|(name, _, _)| *name).collect(),
+            (1, Some("set")) => RUNTIME_SETTINGS.to_vec(),
+            (7, Some("fill")) => MATERIAL_NAMES.to_vec(),
+            _ => Vec::new(),
+        };
+        let matches: Vec<&str> = candidates
+            .into_iter()
+            .filter(|candidate| candidate.starts_with(prefix))
+            .collect();
+        let Some(first) = matches.first() else {
+            return;
+        };
+
+        let completion = if matches.len() == 1 {
+            format!("{} ", first)
+        } else {
+            let common = matches.iter().fold(first.len(), |len, candidate| {
+                first
+                    .bytes()
+                    .zip(candidate.bytes())
+                    .take(len)
+                    .take_while(|(a, b)| a == b)
+                    .count()
+            });
+            let listing = matches.join("  ");
+            self.print(listing);
+            first[..common].to_string()
+        };
+        self.input.truncate(self.input.len() - prefix.len());
+        self.input.push_str(&completion);
+    }
+}
+
+/// Runs the typed line, echoing it and its result into the log.
+pub fn submit(state: &mut State) {
+    let line = std::mem::take(&mut state.console.input).trim().to_string();
+    state.console.remember(&line);
+    state.console.print(format!("> {}", line));
+    let result = execute(state, &line, 0);
+    report(state, result);
+}
+
+/// Runs `startup.txt` if it exists.
+pub fn run_startup_script(state: &mut State) {
+    let path = Path::new(STARTUP_SCRIPT);
+    if path.exists() {
+        let result = run_script(state, path, 0);
+        if let Err(e) = &result {
+            eprintln!("{}", e);
+        }
+        report(state, result);
+    }
+}
+
+fn report(state: &mut State, result: Result<String, String>) {
+    match result {
+        Ok(output) => {
+            for line in output.lines() {
+                state.console.print(line);
+            }
+        }
+        Err(e) => state.console.print(format!("error: {}", e)),
+    }
+}
+
+fn run_script(state: &mut State, path: &Path, depth: usize) -> Result<String, String> {
+    if depth >= MAX_SCRIPT_DEPTH {
+        return Err(format!("scripts nested deeper than {}", MAX_SCRIPT_DEPTH));
+    }
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let mut output = Vec::new();
+    for (line_number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let result = execute(state, line, depth + 1)
+            .map_err(|e| format!("{}:{}: {}", path.display(), line_number + 1, e))?;
+        if !result.is_empty() {
+            output.push(result);
+        }
+    }
+    output.push(format!("ran {}", path.display()));
+    Ok(output.join("\n"))
+}
+
+fn usage(name: &str) -> String {
+    let usage = COMMANDS
+        .iter()
+        .find(|(command, _, _)| *command == name)
+        .map_or(name, |(_, usage, _)| usage);
+    format!("usage: {}", usage)
+}
+
+fn parse_args<const N: usize>(name: &str, args: &[&str]) -> Result<[f32; N], String> {
+    if args.len() != N {
+        return Err(usage(name));
+    }
+    let mut values = [0.0; N];
+    for (value, arg) in values.iter_mut().zip(args) {
+        *value = arg
+            .parse()
+            .map_err(|_| format!("`{}` is not a number", arg))?;
+    }
+    Ok(values)
+}
+
+fn execute(state: &mut State, line: &str, depth: usize) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let Some((&name, args)) = words.split_first() else {
+        return Ok(String::new());
+    };
+    match name {
+        "help" => Ok(COMMANDS
+            .iter()
+            .map(|(_, usage, description)| format!("{:<32} {}", usage, description))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        "tp" => {
+            let [x, y, z] = parse_args(name, args)?;
+            state.camera.pos = Vec3::new(x, y, z);
+            camera_moved(state);
+            if state.mode == Mode::Orbit {
+                state.orbit_target(state.orbit.target);
+            }
+            Ok(String::new())
+        }
+        "look" => {
+            let [yaw, pitch] = parse_args(name, args)?;
+            let (yaw, pitch) = (
+                yaw.to_radians(),
+                pitch.clamp(-MAX_PITCH_DEG, MAX_PITCH_DEG).to_radians(),
+            );
+            let horizontal = Vec3::new(yaw.sin(), 0.0, yaw.cos()) * pitch.cos();
+            state.camera.set_dir(horizontal + UP * pitch.sin());
+            camera_moved(state);
+            if state.mode == Mode::Orbit {
+                state.orbit_look_at_point();
+            }
+            Ok(String::new())
+        }
+        "set" => set(state, args),
+        "fill" => {
+            let [x0, y0, z0, x1, y1, z1, material] = args else {
+                return Err(usage(name));
+            };
+            let [x0, y0, z0, x1, y1, z1] = parse_args(name, &[*x0, *y0, *z0, *x1, *y1, *z1])?;
+            let material_id = state
+                .world
+                .material_by_name(material)
+                .ok_or_else(|| format!("unknown material `{}`", material))?;
+            let min = Vec3::new(x0, y0, z0).floor().as_ivec3();
+            let max = Vec3::new(x1, y1, z1).floor().as_ivec3();
+            let Some((lo, hi)) = state.world.fill_box(min, max, material_id) else {
+                return Ok("filled 0 voxels".to_string());
+            };
+            state.console.edits.push(format!(
+                "fill {} {} {} {} {} {} {}",
+                lo.x, lo.y, lo.z, hi.x, hi.y, hi.z, material
+            ));
+            let count: u64 = (hi - lo + IVec3::ONE)
+                .to_array()
+                .iter()
+                .map(|&side| side as u64)
+                .product();
+            Ok(format!("filled {} voxels", count))
+        }
+        "seed" => {
+            let [seed] = args else {
+                return Err(usage(name));
+            };
+            let seed = seed
+                .parse()
+                .map_err(|_| format!("`{}` is not a seed (0 to {})", seed, u32::MAX))?;
+            state.regenerate_world(seed);
+            state.console.edits.clear();
+            Ok(format!("regenerated the world with seed {}", seed))
+        }
+        "screenshot" => {
+            let [stem] = args else {
+                return Err(usage(name));
+            };
+            screenshot(state, stem)
+        }
+        "save" => {
+            let [path] = args else {
+                return Err(usage(name));
+            };
+            fs::write(path, session_script(state))
+                .map_err(|e| format!("cannot write {}: {}", path, e))?;
+            Ok(format!("saved {}", path))
+        }
+        "exec" => {
+            let [path] = args else {
+                return Err(usage(name));
+            };
+            run_script(state, Path::new(path), depth)
+        }
+        "clear" => {
+            state.console.log.clear();
+            Ok(String::new())
+        }
+        _ => Err(format!("unknown command `{}`; try `help`", name)),
+    }
+}
+
+/// Keeps walk and playback consistent with a camera the console moved.
+fn camera_moved(state: &mut State) {
+    state.path_playback_time = None;
+    if state.mode == Mode::Walk {
+        state.player = Player::spawn_at_eye(&state.world, state.camera.pos);
+    }
+}
+
+fn set(state: &mut State, args: &[&str]) -> Result<String, String> {
+    let mut settings = Settings::default();
+    settings.capture(state);
+    match args {
+        [] => Ok(RUNTIME_SETTINGS
+            .iter()
+            .map(|key| format!("{} = {}", key, settings.value(key)))
+            .collect::<Vec<_>>()
+            .join("\n")),
+        [key, value] => {
+            if !RUNTIME_SETTINGS.contains(key) {
+                return Err(format!(
+                    "`{}` cannot be changed at runtime; one of: {}",
+                    key,
+                    RUNTIME_SETTINGS.join(", ")
+                ));
+            }
+            settings.set(key, value)?;
+            settings.apply_runtime(state);
+            Ok(String::new())
+        }
+        _ => Err(usage("set")),
+    }
+}
+
+fn screenshot(state: &State, stem: &str) -> Result<String, String> {
+    fs::create_dir_all("screenshots")
+        .map_err(|e| format!("cannot create screenshots directory: {}", e))?;
+    let (width, height) = (state.window_dims.x, state.window_dims.y);
+    let mut pixels = vec![0u8; width as usize * height as usize * 4];
+    raymarch::draw_voxels(
+        state.raymarch_input(),
+        &mut pixels,
+        width as i32,
+        height as i32,
+    );
+    let filename = format!("screenshots/{}.png", stem);
+    image_io::write_png(Path::new(&filename), width, height, &pixels)
+        .map_err(|e| format!("cannot write {}: {}", filename, e))?;
+    Ok(format!("saved {}", filename))
+}
+
+/// Script that brings a fresh viewer back to the current session.
+fn session_script(state: &State) -> String {
+    let mut settings = Settings::default();
+    settings.capture(state);
+    let mut script = format!(
+        "# saved from the console; run with `exec`, or rename to {}\nseed {}\n",
+        STARTUP_SCRIPT, state.world.seed
+    );
+    for key in RUNTIME_SETTINGS {
+        script += &format!("set {} {}\n", key, settings.value(key));
+    }
+    for edit in &state.console.edits {
+        script += &format!("{}\n", edit);
+    }
+    let pos = state.camera.pos;
+    let dir = state.camera.dir();
+    script += &format!("tp {} {} {}\n", pos.x, pos.y, pos.z);
+    script += &format!(
+        "look {} {}\n",
+        dir.x.atan2(dir.z).to_degrees(),
+        dir.dot(UP).clamp(-1.0, 1.0).asin().to_degrees()
+    );
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn completes_commands_settings_and_materials() {
+        let mut console = Console {
+            input: "scr".to_string(),
+            ..Console::default()
+        };
+        console.complete();
+        assert_eq!(console.input, "screenshot ");
+
+        console.input = "s".to_string();
+        console.complete();
+        assert_eq!(console.input, "s");
+        assert_eq!(console.log.last().unwrap(), "set  seed  screenshot  save");
+
+        console.input = "set draw".to_string();
+        console.complete();
+        assert_eq!(console.input, "set draw_distance ");
+
+        console.input = "fill 0 0 0 1 1 1 gl".to_string();
+        console.complete();
+        assert_eq!(console.input, "fill 0 0 0 1 1 1 gl");
+        console.input.push('o');
+        console.complete();
+        assert_eq!(console.input, "fill 0 0 0 1 1 1 glow ");
+    }
+
+    #[test]
+    fn commands_change_state_and_save_a_script_that_restores_it() {
+        let settings = Settings {
+            world_size: 64,
+            ..Settings::default()
+        };
+        let mut state = State::new(&settings);
+        for line in [
+            "seed 5",
+            "set draw_distance 300",
+            "fill 2 3 4 3 3 4 #ff0000",
+            "tp 10 20 30",
+            "look 90 0",
+        ] {
+            execute(&mut state, line, 0).unwrap();
+        }
+        assert_eq!(state.world.seed, 5);
+        assert_eq!(state.draw_distance, 300.0);
+        assert_ne!(state.world.get_voxel_material_unchecked_i32(3, 3, 4), 0);
+        assert!((state.camera.dir() - Vec3::X).length() < 1e-4);
+        assert!(execute(&mut state, "set world_size 128", 0).is_err());
+        assert!(execute(&mut state, "fill 0 0 0 1 1 1 lava", 0).is_err());
+        assert!(execute(&mut state, "tp 1 2", 0).is_err());
+
+        let script = session_script(&state);
+        let mut restored = State::new(&settings);
+        for line in script.lines().filter(|line| !line.starts_with('#')) {
+            execute(&mut restored, line, 0).unwrap();
+        }
+        assert_eq!(restored.world.seed, 5);
+        assert_eq!(restored.draw_distance, 300.0);
+        assert_eq!(
+            restored.world.get_voxel_material_unchecked_i32(3, 3, 4),
+            state.world.get_voxel_material_unchecked_i32(3, 3, 4)
+        );
+        assert_eq!(restored.camera.pos, Vec3::new(10.0, 20.0, 30.0));
+        assert!((restored.camera.dir() - Vec3::X).length() < 1e-4);
+    }
+
+    #[test]
+    fn fill_counts_and_records_only_the_part_inside_the_world() {
+        let settings = Settings {
+            world_size: 64,
+            ..Settings::default()
+        };
+        let mut state = State::new(&settings);
+        assert_eq!(
+            execute(&mut state, "fill -3e9 60 60 2000 2000 2000 stone", 0),
+            Ok("filled 1024 voxels".to_string())
+        );
+        assert_eq!(state.console.edits, ["fill 0 60 60 63 63 63 stone"]);
+        assert_eq!(
+            execute(&mut state, "fill 100 100 100 200 200 200 stone", 0),
+            Ok("filled 0 voxels".to_string())
+        );
+        assert_eq!(state.console.edits.len(), 1);
+    }
+
+    #[test]
+    fn history_browses_back_and_forward() {
+        let mut console = Console::default();
+        for line in ["tp 1 2 3", "seed 4", "seed 4"] {
+            console.remember(line);
+        }
+        console.history_prev();
+        assert_eq!(console.input, "seed 4");
+        console.history_prev();
+        assert_eq!(console.input, "tp 1 2 3");
+        console.history_prev();
+        assert_eq!(console.input, "tp 1 2 3");
+        console.history_next();
+        assert_eq!(console.input, "seed 4");
+        console.history_next();
+        assert_eq!(console.input, "");
+    }
+}
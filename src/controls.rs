@@ -1,170 +1,224 @@
-use glam::Vec2;
+use glam::{Vec2, Vec3};
 use raylib::prelude::*;
 use std::fs;
+use std::path::Path;
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::state::{Mode, ResolutionScale, State, DEFAULT_DRAW_DISTANCE};
+use crate::bindings::{Action, Bindings};
+use crate::camera::{upright_orientation, Camera};
+use crate::camera_path::{self, ExportTarget, Keyframe, PathExport, PathExportJob};
+use crate::console;
+use crate::image_io;
+use crate::player::{Player, WalkInput};
+use crate::raymarch::{self, CubeFace, PanoramaInput};
+use crate::state::{
+    Mode, ResolutionScale, State, DEFAULT_DRAW_DISTANCE, DEFAULT_FOV_Y_DEG, MAX_CHUNK_GEN_BUDGET,
+};
 use crate::ui_overlay;
+use crate::viewplane::{Projection, MAX_ORTHO_ZOOM, MIN_ORTHO_ZOOM};
 use crate::{UP, VOXEL_STEP_BUDGET};
 
 const DISTANCE_FACTOR: f32 = 1.1;
 const STEP_FACTOR: f32 = 1.1;
 const FOV_FACTOR: f32 = 1.05;
-const MOUSE_LOOK_SENSITIVITY: f32 = 0.0015;
 const MAX_VIEW_ALIGNMENT_WITH_UP: f32 = 0.995;
 const HIGH_SPEED_MULTIPLIER: f32 = 4.0;
+const ORTHO_ZOOM_FACTOR: f32 = 1.15;
+const ORBIT_ZOOM_FACTOR: f32 = 1.1;
+const ORBIT_ELEVATION_PER_PIXEL: f32 = 0.005;
+/// Radians per second gained per pixel of horizontal right-drag.
+const ORBIT_SPEED_PER_PIXEL: f32 = 0.005;
+const PANORAMA_WIDTH: i32 = 2048;
+const PANORAMA_HEIGHT: i32 = 1024;
+const CUBEMAP_FACE_SIZE: i32 = 512;
 
 pub fn process_events_and_input(rl: &mut RaylibHandle, thread: &RaylibThread, state: &mut State) {
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_ESCAPE) {
+    poll_path_export(state);
+    if state.bindings.pressed(rl, Action::ToggleConsole) {
+        state.console.open = !state.console.open;
+        // Drop the toggle key's own character.
+        while rl.get_char_pressed().is_some() {}
+    }
+    if state.console.open {
+        state.walk_input = WalkInput::default();
+        console_input(rl, state);
+        return;
+    }
+
+    if state.bindings.pressed(rl, Action::Quit) {
         state.running = false;
     }
 
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_M) {
+    if state.bindings.pressed(rl, Action::CycleMode) {
         state.mode = match state.mode {
             Mode::Orbit => Mode::Fly,
-            Mode::Fly => Mode::Orbit,
+            Mode::Fly => Mode::SixDof,
+            Mode::SixDof => Mode::Walk,
+            Mode::Walk => Mode::Orbit,
         };
+        if state.mode == Mode::Walk {
+            state.player = Player::spawn_at_eye(&state.world, state.camera.pos);
+        }
         match state.mode {
-            Mode::Fly => {
+            Mode::Fly | Mode::SixDof | Mode::Walk => {
+                state.camera.level();
                 state.mouse_look_locked = true;
                 rl.disable_cursor();
             }
             Mode::Orbit => {
+                state.orbit_look_at_point();
                 state.mouse_look_locked = false;
                 rl.enable_cursor();
             }
         }
     }
 
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_R) {
+    if state.bindings.pressed(rl, Action::ResetCamera) {
         state.camera.reset();
+        if state.mode == Mode::Walk {
+            state.player = Player::spawn_at_eye(&state.world, state.camera.pos);
+        }
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F12) {
-        export_screenshot(rl, thread);
+    if state.bindings.pressed(rl, Action::ToggleProjection) {
+        state.projection = match state.projection {
+            Projection::Perspective => Projection::Orthographic { zoom: 1.0 },
+            Projection::Orthographic { .. } => Projection::Perspective,
+        };
     }
-
-    if state.mode == Mode::Fly && rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_TAB) {
-        state.mouse_look_locked = !state.mouse_look_locked;
-        if state.mouse_look_locked {
-            rl.disable_cursor();
-        } else {
-            rl.enable_cursor();
+    if state.bindings.pressed(rl, Action::IsometricPreset) {
+        state.apply_isometric_preset();
+        if state.mode == Mode::Orbit {
+            state.orbit_look_at_point();
         }
     }
-
-    let mut cam_speed = 0.1;
-    let mut rotation_speed = 0.02;
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_SHIFT) {
-        cam_speed *= HIGH_SPEED_MULTIPLIER;
-        rotation_speed *= HIGH_SPEED_MULTIPLIER;
-    }
-
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_W) {
-        state.camera.pos += state.camera.dir * cam_speed;
+    let wheel = rl.get_mouse_wheel_move();
+    if wheel != 0.0 {
+        if let Projection::Orthographic { zoom } = &mut state.projection {
+            *zoom = (*zoom * ORTHO_ZOOM_FACTOR.powf(wheel)).clamp(MIN_ORTHO_ZOOM, MAX_ORTHO_ZOOM);
+        } else if state.mode == Mode::Orbit {
+            let radius = state.orbit.radius * ORBIT_ZOOM_FACTOR.powf(-wheel);
+            state.orbit.set_radius(radius);
+        }
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_S) {
-        state.camera.pos -= state.camera.dir * cam_speed;
+    if state.bindings.pressed(rl, Action::ToggleXray) {
+        state.xray = !state.xray;
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_A) {
-        state.camera.pos -= state.camera.get_right() * cam_speed;
+    if state.bindings.pressed(rl, Action::NextDebugView) {
+        state.debug_view = state.debug_view.cycled(false);
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_D) {
-        state.camera.pos += state.camera.get_right() * cam_speed;
+    if state.bindings.pressed(rl, Action::PreviousDebugView) {
+        state.debug_view = state.debug_view.cycled(true);
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_SPACE) {
-        state.camera.pos += state.camera.get_up() * cam_speed;
+    if state.bindings.pressed(rl, Action::Screenshot) {
+        export_screenshot(rl, thread);
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_LEFT_CONTROL) {
-        state.camera.pos -= state.camera.get_up() * cam_speed;
+    if state.bindings.pressed(rl, Action::Panorama) {
+        export_panorama(state);
+    }
+    if state.bindings.pressed(rl, Action::Cubemap) {
+        export_cubemap(state);
+    }
+    if state.bindings.pressed(rl, Action::RecordKeyframe) {
+        state.camera_path.keyframes.push(Keyframe {
+            pos: state.camera.pos,
+            orientation: state.camera.orientation,
+            fov_y_deg: state.fov_y_deg,
+        });
+        save_camera_path(state);
+    }
+    if state.bindings.pressed(rl, Action::ClearPath) {
+        // The file keeps the old path until the next keyframe is recorded, so
+        // a stray press can be undone by restarting.
+        state.camera_path.keyframes.clear();
+        state.path_playback_time = None;
+        println!(
+            "cleared the camera path; {} keeps it until the next keyframe",
+            camera_path::CAMERA_PATH_FILE
+        );
+    }
+    if state.bindings.pressed(rl, Action::TogglePathPlayback) {
+        state.path_playback_time = match state.path_playback_time {
+            None if state.camera_path.keyframes.len() >= 2 => Some(0.0),
+            _ => None,
+        };
     }
-
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_Q) {
-        state.camera.rotate(UP, rotation_speed);
+    if state.bindings.pressed(rl, Action::ExportPathFrames) {
+        export_camera_path(state, false);
     }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_E) {
-        state.camera.rotate(UP, -rotation_speed);
+    if state.bindings.pressed(rl, Action::ExportPathVideo) {
+        export_camera_path(state, true);
     }
 
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_Y) {
-        state.camera.dir.y += rotation_speed;
-    }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_H) {
-        state.camera.dir.y -= rotation_speed;
+    let free_look = matches!(state.mode, Mode::Fly | Mode::SixDof | Mode::Walk);
+    if free_look && state.bindings.pressed(rl, Action::ToggleMouseLook) {
+        state.mouse_look_locked = !state.mouse_look_locked;
+        if state.mouse_look_locked {
+            rl.disable_cursor();
+        } else {
+            rl.enable_cursor();
+        }
     }
 
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_T) {
-        state.camera.viewplane_distance -= cam_speed;
-    }
-    if rl.is_key_down(raylib::consts::KeyboardKey::KEY_G) {
-        state.camera.viewplane_distance += cam_speed;
+    if state.path_playback_time.is_none() {
+        move_camera(rl, state);
     }
 
-    state.sync_fov_y_from_viewplane();
-
-    if state.mode == Mode::Fly && state.mouse_look_locked {
-        let mouse_delta = rl.get_mouse_delta();
-        let yaw = -mouse_delta.x * MOUSE_LOOK_SENSITIVITY;
-        let pitch = -mouse_delta.y * MOUSE_LOOK_SENSITIVITY;
-
-        if yaw != 0.0 {
-            state.camera.rotate(UP, yaw);
-        }
-        if pitch != 0.0 {
-            let old_dir = state.camera.dir;
-            state.camera.rotate(state.camera.get_right(), pitch);
-            if state.camera.dir.dot(UP).abs() > MAX_VIEW_ALIGNMENT_WITH_UP {
-                state.camera.dir = old_dir;
-            }
-        }
-        state.camera.dir = state.camera.dir.normalize();
-    }
-
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_MINUS) {
+    if state.bindings.pressed(rl, Action::DrawDistanceDown) {
         state.draw_distance /= DISTANCE_FACTOR;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_EQUAL) {
+    if state.bindings.pressed(rl, Action::DrawDistanceUp) {
         state.draw_distance *= DISTANCE_FACTOR;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_COMMA) {
+    if state.bindings.pressed(rl, Action::StepBudgetDown) {
         state.voxel_step_budget /= STEP_FACTOR;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_PERIOD) {
+    if state.bindings.pressed(rl, Action::StepBudgetUp) {
         state.voxel_step_budget *= STEP_FACTOR;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_BACKSPACE) {
+    if state.bindings.pressed(rl, Action::ResetRenderBudget) {
         state.draw_distance = DEFAULT_DRAW_DISTANCE;
         state.voxel_step_budget = VOXEL_STEP_BUDGET;
-        state.apply_fov_y_deg(53.130104);
+        state.apply_fov_y_deg(DEFAULT_FOV_Y_DEG);
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_LEFT_BRACKET) {
+    if state.bindings.pressed(rl, Action::FovDown) {
         state.apply_fov_y_deg(state.fov_y_deg / FOV_FACTOR);
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_RIGHT_BRACKET) {
+    if state.bindings.pressed(rl, Action::FovUp) {
         state.apply_fov_y_deg(state.fov_y_deg * FOV_FACTOR);
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F1) {
+    if state.bindings.pressed(rl, Action::RenderScale1x) {
         state.resolution_scale = ResolutionScale::X1;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F2) {
+    if state.bindings.pressed(rl, Action::RenderScaleHalf) {
         state.resolution_scale = ResolutionScale::XHalf;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F3) {
+    if state.bindings.pressed(rl, Action::RenderScaleQuarter) {
         state.resolution_scale = ResolutionScale::XQuarter;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F4) {
+    if state.bindings.pressed(rl, Action::RenderScaleEighth) {
         state.resolution_scale = ResolutionScale::XEighth;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F5) {
+    if state.bindings.pressed(rl, Action::RenderScaleSixteenth) {
         state.resolution_scale = ResolutionScale::XSixteenth;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F6) {
+    if state.bindings.pressed(rl, Action::RenderScaleThirtySecond) {
         state.resolution_scale = ResolutionScale::XThirtySecond;
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F7) {
+    if state.bindings.pressed(rl, Action::RenderScaleAuto) {
+        state.resolution_scale = ResolutionScale::Auto;
+    }
+    if state.bindings.pressed(rl, Action::CycleUpscaler) {
+        state.upscaler = state.upscaler.next();
+    }
+    if state.bindings.pressed(rl, Action::CycleSupersampling) {
+        state.supersampling = state.supersampling.next();
+    }
+    if state.bindings.pressed(rl, Action::ChunkBudgetDown) {
         state.chunk_gen_budget_per_step = state.chunk_gen_budget_per_step.saturating_sub(1).max(1);
     }
-    if rl.is_key_pressed(raylib::consts::KeyboardKey::KEY_F8) {
-        state.chunk_gen_budget_per_step = (state.chunk_gen_budget_per_step + 1).min(32);
+    if state.bindings.pressed(rl, Action::ChunkBudgetUp) {
+        state.chunk_gen_budget_per_step =
+            (state.chunk_gen_budget_per_step + 1).min(MAX_CHUNK_GEN_BUDGET);
     }
     state.clamp_render_budget();
 
@@ -195,18 +249,346 @@ pub fn process_events_and_input(rl: &mut RaylibHandle, thread: &RaylibThread, st
         }
         state.clamp_render_budget();
     }
+
+    if state.mode == Mode::Orbit {
+        orbit_mouse_input(rl, state, ui_mouse, screen_width, screen_height);
+    }
 }
 
-fn export_screenshot(rl: &mut RaylibHandle, thread: &RaylibThread) {
+/// Text editing while the console is open; fixed keys, not bindings.
+fn console_input(rl: &mut RaylibHandle, state: &mut State) {
+    while let Some(c) = rl.get_char_pressed() {
+        state.console.input.push(c);
+    }
+    let pressed = |key| rl.is_key_pressed(key) || rl.is_key_pressed_repeat(key);
+    if pressed(KeyboardKey::KEY_BACKSPACE) {
+        state.console.input.pop();
+    }
+    if pressed(KeyboardKey::KEY_UP) {
+        state.console.history_prev();
+    }
+    if pressed(KeyboardKey::KEY_DOWN) {
+        state.console.history_next();
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_TAB) {
+        state.console.complete();
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_ENTER) || rl.is_key_pressed(KeyboardKey::KEY_KP_ENTER) {
+        console::submit(state);
+    }
+    if rl.is_key_pressed(KeyboardKey::KEY_ESCAPE) {
+        state.console.open = false;
+    }
+}
+
+/// Right-drag adjusts elevation (vertical) and angular speed (horizontal);
+/// a left click outside the overlay panel orbits the voxel under the cursor.
+fn orbit_mouse_input(
+    rl: &RaylibHandle,
+    state: &mut State,
+    mouse: Vec2,
+    screen_width: i32,
+    screen_height: i32,
+) {
+    if state.bindings.pressed(rl, Action::MoveUp) {
+        state.orbit.paused = !state.orbit.paused;
+    }
+
+    if rl.is_mouse_button_down(MouseButton::MOUSE_BUTTON_RIGHT) {
+        let delta = rl.get_mouse_delta();
+        let elevation = state.orbit.elevation - delta.y * ORBIT_ELEVATION_PER_PIXEL;
+        state.orbit.set_elevation(elevation);
+        let angular_speed = state.orbit.angular_speed + delta.x * ORBIT_SPEED_PER_PIXEL;
+        state.orbit.set_angular_speed(angular_speed);
+    }
+
+    if rl.is_mouse_button_pressed(MouseButton::MOUSE_BUTTON_LEFT) {
+        let layout = ui_overlay::ui_layout(screen_width, screen_height);
+        if ui_overlay::point_in_rect(mouse, layout.panel) {
+            return;
+        }
+        let screen_uv = mouse / Vec2::new(screen_width.max(1) as f32, screen_height.max(1) as f32);
+        let (origin, dir) = state
+            .viewplane
+            .ray_through(&state.camera, state.projection, screen_uv);
+        if let Some((voxel, _)) =
+            raymarch::raycast_first_hit(&state.world, origin, dir, state.draw_distance)
+        {
+            state.orbit_target(voxel.as_vec3() + Vec3::splat(0.5));
+        }
+    }
+}
+
+/// Keyboard and mouse-look camera movement; skipped while a recorded path plays back.
+fn move_camera(rl: &mut RaylibHandle, state: &mut State) {
+    let mut cam_speed = 0.1;
+    let mut rotation_speed = 0.02;
+    if state.bindings.down(rl, Action::Sprint) {
+        cam_speed *= HIGH_SPEED_MULTIPLIER;
+        rotation_speed *= HIGH_SPEED_MULTIPLIER;
+    }
+
+    if state.mode == Mode::Walk {
+        state.walk_input = read_walk_input(rl, &state.bindings, &state.camera);
+    } else {
+        if state.bindings.down(rl, Action::MoveForward) {
+            state.camera.pos += state.camera.dir() * cam_speed;
+        }
+        if state.bindings.down(rl, Action::MoveBack) {
+            state.camera.pos -= state.camera.dir() * cam_speed;
+        }
+        if state.bindings.down(rl, Action::MoveLeft) {
+            state.camera.pos -= state.camera.get_right() * cam_speed;
+        }
+        if state.bindings.down(rl, Action::MoveRight) {
+            state.camera.pos += state.camera.get_right() * cam_speed;
+        }
+        if state.bindings.down(rl, Action::MoveUp) {
+            state.camera.pos += state.camera.get_up() * cam_speed;
+        }
+        if state.bindings.down(rl, Action::MoveDown) {
+            state.camera.pos -= state.camera.get_up() * cam_speed;
+        }
+    }
+
+    let six_dof = state.mode == Mode::SixDof;
+    if state.bindings.down(rl, Action::YawLeft) {
+        if six_dof {
+            state.camera.rotate_local(0.0, 0.0, -rotation_speed);
+        } else {
+            state.camera.rotate(UP, rotation_speed);
+        }
+    }
+    if state.bindings.down(rl, Action::YawRight) {
+        if six_dof {
+            state.camera.rotate_local(0.0, 0.0, rotation_speed);
+        } else {
+            state.camera.rotate(UP, -rotation_speed);
+        }
+    }
+
+    if state.bindings.down(rl, Action::PitchDown) {
+        pitch_camera(state, -rotation_speed);
+    }
+    if state.bindings.down(rl, Action::PitchUp) {
+        pitch_camera(state, rotation_speed);
+    }
+
+    if state.bindings.down(rl, Action::ViewplaneCloser) {
+        state.camera.viewplane_distance -= cam_speed;
+    }
+    if state.bindings.down(rl, Action::ViewplaneFarther) {
+        state.camera.viewplane_distance += cam_speed;
+    }
+
+    state.sync_fov_y_from_viewplane();
+
+    let free_look = matches!(state.mode, Mode::Fly | Mode::SixDof | Mode::Walk);
+    if free_look && state.mouse_look_locked {
+        let mouse_delta = rl.get_mouse_delta();
+        let yaw = -mouse_delta.x * state.mouse_sensitivity;
+        let pitch = -mouse_delta.y * state.mouse_sensitivity;
+
+        if six_dof {
+            state.camera.rotate_local(pitch, -yaw, 0.0);
+        } else {
+            if yaw != 0.0 {
+                state.camera.rotate(UP, yaw);
+            }
+            if pitch != 0.0 {
+                pitch_camera(state, pitch);
+            }
+        }
+    }
+}
+
+/// WASD relative to the camera's heading, flattened onto the ground plane.
+fn read_walk_input(rl: &RaylibHandle, bindings: &Bindings, camera: &Camera) -> WalkInput {
+    let flatten = |v: Vec3| Vec3::new(v.x, 0.0, v.z).normalize_or_zero();
+    let forward = flatten(camera.dir());
+    let right = flatten(camera.get_right());
+    let mut wish_dir = Vec3::ZERO;
+    if bindings.down(rl, Action::MoveForward) {
+        wish_dir += forward;
+    }
+    if bindings.down(rl, Action::MoveBack) {
+        wish_dir -= forward;
+    }
+    if bindings.down(rl, Action::MoveLeft) {
+        wish_dir -= right;
+    }
+    if bindings.down(rl, Action::MoveRight) {
+        wish_dir += right;
+    }
+    WalkInput {
+        wish_dir: wish_dir.normalize_or_zero(),
+        jump: bindings.down(rl, Action::MoveUp),
+        descend: bindings.down(rl, Action::MoveDown),
+        sprint: bindings.down(rl, Action::Sprint),
+    }
+}
+
+/// Pitches around the camera's right axis. Outside 6-DOF mode the view
+/// refuses to pass vertical so the horizon stays level.
+fn pitch_camera(state: &mut State, angle: f32) {
+    if state.mode == Mode::SixDof {
+        state.camera.rotate_local(angle, 0.0, 0.0);
+        return;
+    }
+    let old_orientation = state.camera.orientation;
+    state.camera.rotate(state.camera.get_right(), angle);
+    if state.camera.dir().dot(UP).abs() > MAX_VIEW_ALIGNMENT_WITH_UP {
+        state.camera.orientation = old_orientation;
+    }
+}
+
+/// `screenshots/<prefix>_<unix_ms>`, creating the directory if needed.
+fn screenshot_stem(prefix: &str) -> Option<String> {
     if let Err(e) = fs::create_dir_all("screenshots") {
         eprintln!("failed to create screenshots directory: {}", e);
-        return;
+        return None;
     }
 
     let unix_ms = SystemTime::now()
         .duration_since(UNIX_EPOCH)
         .map_or(0, |d| d.as_millis());
-    let filename = format!("screenshots/screenshot_{}.png", unix_ms);
+    Some(format!("screenshots/{}_{}", prefix, unix_ms))
+}
+
+fn export_screenshot(rl: &mut RaylibHandle, thread: &RaylibThread) {
+    let Some(stem) = screenshot_stem("screenshot") else {
+        return;
+    };
+    let filename = format!("{}.png", stem);
     rl.take_screenshot(thread, &filename);
     println!("saved screenshot: {}", filename);
 }
+
+/// Panoramas face the camera's heading but stay level, so the horizon is the
+/// image's middle row even when the camera is pitched or rolled.
+fn panorama_input(state: &State) -> PanoramaInput<'_> {
+    let forward = state.camera.dir();
+    let heading = if forward.x.abs() + forward.z.abs() > 1e-3 {
+        forward
+    } else {
+        // Looking straight up or down: the top of the view points the way.
+        state.camera.get_up()
+    };
+    PanoramaInput {
+        world: &state.world,
+        origin: state.camera.pos,
+        orientation: upright_orientation(Vec3::new(heading.x, 0.0, heading.z)),
+        draw_distance: state.draw_distance,
+        voxel_step_budget: state.voxel_step_budget,
+        hidden_material: state.hidden_material(),
+    }
+}
+
+fn save_png(filename: &str, width: i32, height: i32, pixels: &[u8]) {
+    match image_io::write_png(Path::new(filename), width as u32, height as u32, pixels) {
+        Ok(()) => println!("saved {}", filename),
+        Err(e) => eprintln!("failed to write {}: {}", filename, e),
+    }
+}
+
+fn export_panorama(state: &State) {
+    let Some(stem) = screenshot_stem("panorama") else {
+        return;
+    };
+    let mut pixels = vec![0u8; PANORAMA_WIDTH as usize * PANORAMA_HEIGHT as usize * 4];
+    raymarch::draw_equirectangular(
+        &panorama_input(state),
+        &mut pixels,
+        PANORAMA_WIDTH,
+        PANORAMA_HEIGHT,
+    );
+    save_png(
+        &format!("{}.png", stem),
+        PANORAMA_WIDTH,
+        PANORAMA_HEIGHT,
+        &pixels,
+    );
+}
+
+fn export_cubemap(state: &State) {
+    let Some(stem) = screenshot_stem("cubemap") else {
+        return;
+    };
+    let input = panorama_input(state);
+    let mut pixels = vec![0u8; CUBEMAP_FACE_SIZE as usize * CUBEMAP_FACE_SIZE as usize * 4];
+    for face in CubeFace::ALL {
+        raymarch::draw_cube_face(&input, face, &mut pixels, CUBEMAP_FACE_SIZE);
+        save_png(
+            &format!("{}_{}.png", stem, face.label()),
+            CUBEMAP_FACE_SIZE,
+            CUBEMAP_FACE_SIZE,
+            &pixels,
+        );
+    }
+}
+
+fn save_camera_path(state: &State) {
+    let file = Path::new(camera_path::CAMERA_PATH_FILE);
+    match state.camera_path.save(file) {
+        Ok(()) => println!(
+            "saved {} keyframes to {}",
+            state.camera_path.keyframes.len(),
+            file.display()
+        ),
+        Err(e) => eprintln!("failed to write {}: {}", file.display(), e),
+    }
+}
+
+/// Starts rendering the recorded path at the current render resolution on a
+/// background thread, against a copy of the world with all terrain generated
+/// so the output does not depend on streaming. Pressed again, cancels it.
+fn export_camera_path(state: &mut State, as_video: bool) {
+    if let Some(job) = &state.path_export {
+        job.cancel();
+        println!("cancelling the path export to {}", job.label);
+        return;
+    }
+    if state.camera_path.keyframes.len() < 2 {
+        eprintln!("record at least two keyframes with K before exporting");
+        return;
+    }
+    let Some(stem) = screenshot_stem("path") else {
+        return;
+    };
+    let target = if as_video {
+        ExportTarget::Y4m(format!("{}.y4m", stem).into())
+    } else {
+        ExportTarget::PngSequence(stem.clone().into())
+    };
+
+    let export = PathExport {
+        projection: state.projection,
+        draw_distance: state.draw_distance,
+        voxel_step_budget: state.voxel_step_budget,
+        hidden_material: state.hidden_material(),
+        supersampling: state.supersampling,
+        width: state.render_width,
+        height: state.render_height,
+    };
+    println!("exporting the camera path to {}", stem);
+    state.path_export = Some(PathExportJob::spawn(
+        state.camera_path.clone(),
+        Box::new((*state.world).clone()),
+        export,
+        target,
+        stem,
+    ));
+}
+
+/// Reports a finished path export.
+fn poll_path_export(state: &mut State) {
+    let Some(job) = state.path_export.take() else {
+        return;
+    };
+    let label = job.label.clone();
+    match job.try_finish() {
+        Err(job) => state.path_export = Some(job),
+        Ok(Ok(frames)) => println!("exported {} path frames to {}", frames, label),
+        Ok(Err(e)) => eprintln!("path export to {} stopped: {}", label, e),
+    }
+}
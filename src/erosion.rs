@@ -0,0 +1,196 @@
+//! Droplet-based hydraulic erosion over a rectangular elevation grid.
+//!
+//! Elevations are "up is positive" here, unlike world `y`, which grows downward.
+
+#[derive(Copy, Clone, Debug)]
+pub struct ErosionSettings {
+    pub iterations: u32,
+    pub max_droplet_lifetime: u32,
+    pub inertia: f32,
+    pub sediment_capacity: f32,
+    pub min_sediment_capacity: f32,
+    pub erosion_rate: f32,
+    pub deposition_rate: f32,
+    pub evaporation_rate: f32,
+    pub gravity: f32,
+    pub erosion_radius: i32,
+}
+
+impl Default for ErosionSettings {
+    fn default() -> Self {
+        Self {
+            iterations: 40_000,
+            max_droplet_lifetime: 30,
+            inertia: 0.05,
+            sediment_capacity: 4.0,
+            min_sediment_capacity: 0.01,
+            erosion_rate: 0.3,
+            deposition_rate: 0.3,
+            evaporation_rate: 0.01,
+            gravity: 4.0,
+            erosion_radius: 3,
+        }
+    }
+}
+
+/// Small deterministic generator so erosion never depends on `rand` internals.
+pub(crate) struct SplitMix64(pub(crate) u64);
+
+impl SplitMix64 {
+    #[inline]
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        z ^ (z >> 31)
+    }
+
+    #[inline]
+    pub(crate) fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[derive(Copy, Clone)]
+struct BrushCell {
+    offset_x: i32,
+    offset_z: i32,
+    weight: f32,
+}
+
+fn build_brush(radius: i32) -> Vec<BrushCell> {
+    let radius = radius.max(1);
+    let mut cells = Vec::new();
+    let mut weight_sum = 0.0;
+    for offset_z in -radius..=radius {
+        for offset_x in -radius..=radius {
+            let distance = ((offset_x * offset_x + offset_z * offset_z) as f32).sqrt();
+            if distance < radius as f32 {
+                let weight = 1.0 - distance / radius as f32;
+                weight_sum += weight;
+                cells.push(BrushCell {
+                    offset_x,
+                    offset_z,
+                    weight,
+                });
+            }
+        }
+    }
+    for cell in &mut cells {
+        cell.weight /= weight_sum;
+    }
+    cells
+}
+
+/// Returns `(height, gradient_x, gradient_z)` at a fractional grid position.
+#[inline]
+fn sample_height_and_gradient(heights: &[f32], width: usize, x: f32, z: f32) -> (f32, f32, f32) {
+    let cell_x = x as usize;
+    let cell_z = z as usize;
+    let u = x - cell_x as f32;
+    let v = z - cell_z as f32;
+
+    let idx = cell_x + cell_z * width;
+    let h00 = heights[idx];
+    let h10 = heights[idx + 1];
+    let h01 = heights[idx + width];
+    let h11 = heights[idx + width + 1];
+
+    let gradient_x = (h10 - h00) * (1.0 - v) + (h11 - h01) * v;
+    let gradient_z = (h01 - h00) * (1.0 - u) + (h11 - h10) * u;
+    let height =
+        h00 * (1.0 - u) * (1.0 - v) + h10 * u * (1.0 - v) + h01 * (1.0 - u) * v + h11 * u * v;
+    (height, gradient_x, gradient_z)
+}
+
+/// Simulates `settings.iterations` droplets over `heights` (row-major, `width * depth`).
+/// The same `seed` always yields the same result.
+pub fn erode_heightfield(
+    heights: &mut [f32],
+    width: usize,
+    depth: usize,
+    settings: &ErosionSettings,
+    seed: u64,
+) {
+    debug_assert_eq!(heights.len(), width * depth);
+    if width < 3 || depth < 3 {
+        return;
+    }
+
+    let brush = build_brush(settings.erosion_radius);
+    let mut rng = SplitMix64(seed);
+    let max_x = (width - 2) as f32;
+    let max_z = (depth - 2) as f32;
+
+    for _ in 0..settings.iterations {
+        let mut x = rng.next_f32() * max_x;
+        let mut z = rng.next_f32() * max_z;
+        let mut dir_x = 0.0;
+        let mut dir_z = 0.0;
+        let mut speed = 1.0f32;
+        let mut water = 1.0f32;
+        let mut sediment = 0.0f32;
+
+        for _ in 0..settings.max_droplet_lifetime {
+            let cell_x = x as usize;
+            let cell_z = z as usize;
+            let u = x - cell_x as f32;
+            let v = z - cell_z as f32;
+            let (height, gradient_x, gradient_z) = sample_height_and_gradient(heights, width, x, z);
+
+            dir_x = dir_x * settings.inertia - gradient_x * (1.0 - settings.inertia);
+            dir_z = dir_z * settings.inertia - gradient_z * (1.0 - settings.inertia);
+            let len = (dir_x * dir_x + dir_z * dir_z).sqrt();
+            if len <= f32::EPSILON {
+                break;
+            }
+            dir_x /= len;
+            dir_z /= len;
+            x += dir_x;
+            z += dir_z;
+            if x < 0.0 || z < 0.0 || x >= max_x || z >= max_z {
+                break;
+            }
+
+            let (new_height, _, _) = sample_height_and_gradient(heights, width, x, z);
+            let delta_height = new_height - height;
+            let capacity = (-delta_height * speed * water * settings.sediment_capacity)
+                .max(settings.min_sediment_capacity);
+
+            if sediment > capacity || delta_height > 0.0 {
+                // Uphill: fill the pit behind us. Otherwise drop the excess sediment.
+                let amount = if delta_height > 0.0 {
+                    delta_height.min(sediment)
+                } else {
+                    (sediment - capacity) * settings.deposition_rate
+                };
+                sediment -= amount;
+
+                let idx = cell_x + cell_z * width;
+                heights[idx] += amount * (1.0 - u) * (1.0 - v);
+                heights[idx + 1] += amount * u * (1.0 - v);
+                heights[idx + width] += amount * (1.0 - u) * v;
+                heights[idx + width + 1] += amount * u * v;
+            } else {
+                let amount = ((capacity - sediment) * settings.erosion_rate).min(-delta_height);
+                for cell in &brush {
+                    let bx = cell_x as i32 + cell.offset_x;
+                    let bz = cell_z as i32 + cell.offset_z;
+                    if bx < 0 || bz < 0 || bx >= width as i32 || bz >= depth as i32 {
+                        continue;
+                    }
+                    let idx = bx as usize + bz as usize * width;
+                    let removed = amount * cell.weight;
+                    heights[idx] -= removed;
+                    sediment += removed;
+                }
+            }
+
+            speed = (speed * speed + delta_height * settings.gravity)
+                .max(0.0)
+                .sqrt();
+            water *= 1.0 - settings.evaporation_rate;
+        }
+    }
+}
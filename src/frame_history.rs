@@ -0,0 +1,121 @@
+//! Ring buffer of recent `FrameTimings` for the overlay's frame-time graph.
+
+use crate::state::FrameTimings;
+use crate::stats::percentile;
+
+pub const FRAME_HISTORY_LEN: usize = 240;
+
+/// One slice of a frame's time, stacked bottom to top in the graph.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Stage {
+    Simulation,
+    Raymarch,
+    Upload,
+    /// Frame time not covered by the other stages: overlay drawing, swap, waiting.
+    Other,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 4] = [
+        Stage::Simulation,
+        Stage::Raymarch,
+        Stage::Upload,
+        Stage::Other,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Simulation => "Sim",
+            Self::Raymarch => "Ray",
+            Self::Upload => "Up",
+            Self::Other => "Other",
+        }
+    }
+
+    pub fn ms(self, timings: &FrameTimings) -> f32 {
+        match self {
+            Self::Simulation => timings.simulation_ms,
+            Self::Raymarch => timings.raymarch_ms,
+            Self::Upload => timings.upload_ms,
+            Self::Other => {
+                (timings.frame_ms - timings.simulation_ms - timings.raymarch_ms - timings.upload_ms)
+                    .max(0.0)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct StageStats {
+    pub min_ms: f32,
+    pub avg_ms: f32,
+    pub p99_ms: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct FrameHistory {
+    frames: Vec<FrameTimings>,
+    /// Slot the next frame overwrites once `frames` is full.
+    next: usize,
+}
+
+impl FrameHistory {
+    pub fn push(&mut self, timings: FrameTimings) {
+        if self.frames.len() < FRAME_HISTORY_LEN {
+            self.frames.push(timings);
+        } else {
+            self.frames[self.next] = timings;
+        }
+        self.next = (self.next + 1) % FRAME_HISTORY_LEN;
+    }
+
+    /// Frames from oldest to newest.
+    pub fn iter(&self) -> impl Iterator<Item = &FrameTimings> {
+        let (newer, older) = self.frames.split_at(self.next.min(self.frames.len()));
+        older.iter().chain(newer)
+    }
+
+    pub fn stats(&self, ms: impl Fn(&FrameTimings) -> f32) -> StageStats {
+        if self.frames.is_empty() {
+            return StageStats::default();
+        }
+        let mut sorted: Vec<f64> = self.frames.iter().map(|f| ms(f) as f64).collect();
+        sorted.sort_by(f64::total_cmp);
+        StageStats {
+            min_ms: sorted[0] as f32,
+            avg_ms: (sorted.iter().sum::<f64>() / sorted.len() as f64) as f32,
+            p99_ms: percentile(&sorted, 0.99) as f32,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(frame_ms: f32) -> FrameTimings {
+        FrameTimings {
+            frame_ms,
+            ..FrameTimings::default()
+        }
+    }
+
+    #[test]
+    fn keeps_the_newest_frames_in_order() {
+        let mut history = FrameHistory::default();
+        for i in 0..FRAME_HISTORY_LEN + 10 {
+            history.push(frame(i as f32));
+        }
+        let frames: Vec<f32> = history.iter().map(|f| f.frame_ms).collect();
+        assert_eq!(frames.len(), FRAME_HISTORY_LEN);
+        assert_eq!(frames[0], 10.0);
+        assert_eq!(
+            frames[FRAME_HISTORY_LEN - 1],
+            (FRAME_HISTORY_LEN + 9) as f32
+        );
+
+        let stats = history.stats(|f| f.frame_ms);
+        assert_eq!(stats.min_ms, 10.0);
+        assert_eq!(stats.p99_ms, (FRAME_HISTORY_LEN + 7) as f32);
+    }
+}
@@ -0,0 +1,148 @@
+//! Renders small fixed scenes through `draw_voxels` and compares them with the
+//! reference images in `tests/golden/`. Run with `UPDATE_GOLDEN=1` to accept
+//! intended rendering changes.
+
+use std::path::{Path, PathBuf};
+
+use glam::{Vec2, Vec3};
+
+use crate::camera::{isometric_dir, Camera};
+use crate::image_io::{read_ppm, write_ppm};
+use crate::raymarch::{draw_voxels, DebugView, RaymarchInput, Supersampling};
+use crate::state::{build_demo_world, demo_camera, DEFAULT_DRAW_DISTANCE};
+use crate::viewplane::{Projection, Viewplane};
+use crate::world::World;
+use crate::VOXEL_STEP_BUDGET;
+
+const WIDTH: u32 = 96;
+const HEIGHT: u32 = 72;
+const DEMO_WORLD_SIZE: usize = 64;
+/// Largest per-channel difference still treated as a match.
+const CHANNEL_TOLERANCE: u8 = 3;
+/// Pixels allowed past the tolerance, for float drift on silhouette edges.
+const MAX_MISMATCHED_PIXELS: usize = 4;
+
+fn golden_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{name}.ppm"))
+}
+
+fn failure_path(name: &str, suffix: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("target/golden-failures")
+        .join(format!("{name}.{suffix}.ppm"))
+}
+
+fn render(world: &World, camera: &Camera, projection: Projection) -> Vec<u8> {
+    let viewplane = Viewplane::new(Vec2::new(4.0, 3.0), WIDTH as f32 / HEIGHT as f32);
+    let mut pixels = vec![0u8; WIDTH as usize * HEIGHT as usize * 4];
+    draw_voxels(
+        RaymarchInput {
+            world,
+            camera,
+            viewplane: &viewplane,
+            projection,
+            draw_distance: DEFAULT_DRAW_DISTANCE,
+            voxel_step_budget: VOXEL_STEP_BUDGET,
+            hidden_material: None,
+            debug_view: DebugView::Shaded,
+            jitter: Vec2::ZERO,
+            supersampling: Supersampling::Off,
+        },
+        &mut pixels,
+        WIDTH as i32,
+        HEIGHT as i32,
+    );
+    pixels
+}
+
+/// Red where a pixel is out of tolerance, a dimmed copy of the reference elsewhere.
+fn diff_image(expected: &[u8], actual: &[u8]) -> (Vec<u8>, usize) {
+    let mut diff = Vec::with_capacity(expected.len());
+    let mut mismatched = 0;
+    for (want, got) in expected.chunks_exact(4).zip(actual.chunks_exact(4)) {
+        let out_of_tolerance = want[..3]
+            .iter()
+            .zip(&got[..3])
+            .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE);
+        if out_of_tolerance {
+            mismatched += 1;
+            diff.extend_from_slice(&[255, 0, 0, 255]);
+        } else {
+            diff.extend_from_slice(&[want[0] / 4, want[1] / 4, want[2] / 4, 255]);
+        }
+    }
+    (diff, mismatched)
+}
+
+fn assert_matches_golden(name: &str, actual: &[u8]) {
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_ppm(&path, WIDTH, HEIGHT, actual).expect("write golden image");
+        return;
+    }
+
+    let (width, height, expected) = read_ppm(&path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read {}: {e}; run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        (width, height),
+        (WIDTH, HEIGHT),
+        "{name}: golden image size changed"
+    );
+
+    let (diff, mismatched) = diff_image(&expected, actual);
+    if mismatched > MAX_MISMATCHED_PIXELS {
+        let actual_path = failure_path(name, "actual");
+        let diff_path = failure_path(name, "diff");
+        write_ppm(&actual_path, WIDTH, HEIGHT, actual).expect("write actual image");
+        write_ppm(&diff_path, WIDTH, HEIGHT, &diff).expect("write diff image");
+        panic!(
+            "{name}: {mismatched} pixels differ from {} by more than {CHANNEL_TOLERANCE}; \
+             see {} and {}",
+            path.display(),
+            actual_path.display(),
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn demo_scene_from_start_camera() {
+    let world = build_demo_world(DEMO_WORLD_SIZE);
+    let camera = demo_camera(&world);
+    assert_matches_golden(
+        "demo_start_camera",
+        &render(&world, &camera, Projection::Perspective),
+    );
+}
+
+#[test]
+fn demo_scene_through_glass_slab() {
+    let world = build_demo_world(DEMO_WORLD_SIZE);
+    let floor = world.get_floor_level() as f32;
+    let camera = Camera::new(
+        Vec3::new(20.0, floor - 6.0, 14.0),
+        Vec3::new(-1.0, 0.25, -0.45),
+        3.0,
+    );
+    assert_matches_golden(
+        "demo_glass_slab",
+        &render(&world, &camera, Projection::Perspective),
+    );
+}
+
+#[test]
+fn demo_scene_isometric() {
+    let world = build_demo_world(DEMO_WORLD_SIZE);
+    let focus = Vec3::new(8.0, world.get_floor_level() as f32 - 4.0, 12.0);
+    let camera = Camera::new(focus - isometric_dir() * 96.0, isometric_dir(), 3.0);
+    assert_matches_golden(
+        "demo_isometric",
+        &render(&world, &camera, Projection::Orthographic { zoom: 2.0 }),
+    );
+}
@@ -0,0 +1,270 @@
+//! Flow-accumulation rivers and spill-height lakes over an elevation grid.
+//!
+//! Elevations are "up is positive", matching `erosion`.
+
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+
+#[derive(Copy, Clone, Debug)]
+pub struct HydrologySettings {
+    /// Upstream cell count at which a flow path becomes a river.
+    pub river_threshold: f32,
+    pub max_river_depth: f32,
+    /// Basins shallower or smaller than this drain instead of forming lakes.
+    pub min_lake_depth: f32,
+    pub min_lake_area: usize,
+}
+
+impl Default for HydrologySettings {
+    fn default() -> Self {
+        Self {
+            river_threshold: 400.0,
+            max_river_depth: 4.0,
+            min_lake_depth: 3.0,
+            min_lake_area: 24,
+        }
+    }
+}
+
+/// Water surface elevation per cell; `f32::NEG_INFINITY` where dry.
+pub struct WaterMap {
+    pub water: Vec<f32>,
+}
+
+// Keeps priority-flood outflow strictly downhill across filled flats.
+const FLOOD_EPSILON: f32 = 1e-3;
+const LAKE_MEMBER_DEPTH: f32 = 0.5;
+const NEIGHBORS: [(i32, i32); 8] = [
+    (-1, -1),
+    (0, -1),
+    (1, -1),
+    (-1, 0),
+    (1, 0),
+    (-1, 1),
+    (0, 1),
+    (1, 1),
+];
+
+#[derive(Copy, Clone)]
+struct FloodCell {
+    elevation: f32,
+    index: usize,
+}
+
+impl PartialEq for FloodCell {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for FloodCell {}
+
+impl PartialOrd for FloodCell {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for FloodCell {
+    // Reversed so `BinaryHeap` pops the lowest cell first; index breaks ties
+    // to keep the fill deterministic.
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .elevation
+            .total_cmp(&self.elevation)
+            .then_with(|| other.index.cmp(&self.index))
+    }
+}
+
+/// Priority-flood depression filling. Grid borders and cells at or below
+/// `sea_elevation` act as outlets.
+fn fill_depressions(heights: &[f32], width: usize, depth: usize, sea_elevation: f32) -> Vec<f32> {
+    let mut filled = heights.to_vec();
+    let mut closed = vec![false; heights.len()];
+    let mut open = BinaryHeap::new();
+
+    for z in 0..depth {
+        for x in 0..width {
+            let index = x + z * width;
+            let on_border = x == 0 || z == 0 || x == width - 1 || z == depth - 1;
+            if on_border || heights[index] <= sea_elevation {
+                closed[index] = true;
+                open.push(FloodCell {
+                    elevation: heights[index],
+                    index,
+                });
+            }
+        }
+    }
+
+    while let Some(cell) = open.pop() {
+        let x = (cell.index % width) as i32;
+        let z = (cell.index / width) as i32;
+        for (dx, dz) in NEIGHBORS {
+            let nx = x + dx;
+            let nz = z + dz;
+            if nx < 0 || nz < 0 || nx >= width as i32 || nz >= depth as i32 {
+                continue;
+            }
+            let neighbor = nx as usize + nz as usize * width;
+            if closed[neighbor] {
+                continue;
+            }
+            closed[neighbor] = true;
+            filled[neighbor] = filled[neighbor].max(cell.elevation + FLOOD_EPSILON);
+            open.push(FloodCell {
+                elevation: filled[neighbor],
+                index: neighbor,
+            });
+        }
+    }
+    filled
+}
+
+/// D8 steepest-descent receivers over the filled surface, then upstream cell
+/// counts accumulated from the highest cell down.
+fn accumulate_flow(filled: &[f32], width: usize, depth: usize) -> Vec<f32> {
+    let mut receiver = vec![usize::MAX; filled.len()];
+    for z in 0..depth as i32 {
+        for x in 0..width as i32 {
+            let index = x as usize + z as usize * width;
+            let mut best_slope = 0.0;
+            for (dx, dz) in NEIGHBORS {
+                let nx = x + dx;
+                let nz = z + dz;
+                if nx < 0 || nz < 0 || nx >= width as i32 || nz >= depth as i32 {
+                    continue;
+                }
+                let neighbor = nx as usize + nz as usize * width;
+                let distance = if dx != 0 && dz != 0 {
+                    std::f32::consts::SQRT_2
+                } else {
+                    1.0
+                };
+                let slope = (filled[index] - filled[neighbor]) / distance;
+                if slope > best_slope {
+                    best_slope = slope;
+                    receiver[index] = neighbor;
+                }
+            }
+        }
+    }
+
+    let mut order: Vec<usize> = (0..filled.len()).collect();
+    order.sort_unstable_by(|&a, &b| filled[b].total_cmp(&filled[a]).then(a.cmp(&b)));
+    let mut accumulation = vec![1.0f32; filled.len()];
+    for index in order {
+        let downstream = receiver[index];
+        if downstream != usize::MAX {
+            accumulation[downstream] += accumulation[index];
+        }
+    }
+    accumulation
+}
+
+/// Marks connected basins that are deep and wide enough as lakes at their
+/// spill height. Returns the lake level per cell (`NEG_INFINITY` elsewhere).
+fn find_lakes(
+    heights: &[f32],
+    filled: &[f32],
+    width: usize,
+    depth: usize,
+    settings: &HydrologySettings,
+) -> Vec<f32> {
+    let mut lake_level = vec![f32::NEG_INFINITY; heights.len()];
+    let mut visited = vec![false; heights.len()];
+    let mut stack = Vec::new();
+    let mut component = Vec::new();
+
+    for start in 0..heights.len() {
+        if visited[start] || filled[start] - heights[start] < LAKE_MEMBER_DEPTH {
+            continue;
+        }
+        visited[start] = true;
+        stack.push(start);
+        component.clear();
+        let mut max_depth = 0.0f32;
+        let mut level = f32::NEG_INFINITY;
+
+        while let Some(index) = stack.pop() {
+            component.push(index);
+            max_depth = max_depth.max(filled[index] - heights[index]);
+            level = level.max(filled[index]);
+            let x = (index % width) as i32;
+            let z = (index / width) as i32;
+            for (dx, dz) in [(-1, 0), (1, 0), (0, -1), (0, 1)] {
+                let nx = x + dx;
+                let nz = z + dz;
+                if nx < 0 || nz < 0 || nx >= width as i32 || nz >= depth as i32 {
+                    continue;
+                }
+                let neighbor = nx as usize + nz as usize * width;
+                if visited[neighbor] || filled[neighbor] - heights[neighbor] < LAKE_MEMBER_DEPTH {
+                    continue;
+                }
+                visited[neighbor] = true;
+                stack.push(neighbor);
+            }
+        }
+
+        if max_depth >= settings.min_lake_depth && component.len() >= settings.min_lake_area {
+            for &index in &component {
+                lake_level[index] = level;
+            }
+        }
+    }
+    lake_level
+}
+
+/// Carves river channels into `heights` along high-accumulation paths and
+/// returns the water surface for rivers and lakes. Sea water below
+/// `sea_elevation` is left to the caller.
+pub fn carve_rivers_and_lakes(
+    heights: &mut [f32],
+    width: usize,
+    depth: usize,
+    sea_elevation: f32,
+    settings: &HydrologySettings,
+) -> WaterMap {
+    debug_assert_eq!(heights.len(), width * depth);
+    let original = heights.to_vec();
+    let filled = fill_depressions(&original, width, depth, sea_elevation);
+    let accumulation = accumulate_flow(&filled, width, depth);
+    let mut water = find_lakes(&original, &filled, width, depth, settings);
+
+    let threshold = settings.river_threshold.max(1.0);
+    for z in 0..depth as i32 {
+        for x in 0..width as i32 {
+            let index = x as usize + z as usize * width;
+            if accumulation[index] < threshold
+                || water[index].is_finite()
+                || original[index] <= sea_elevation
+            {
+                continue;
+            }
+
+            // Bigger rivers cut deeper and spill onto the neighboring cells.
+            let strength = (accumulation[index] / threshold).ln();
+            let channel_depth = (2.0 + strength).min(settings.max_river_depth.max(2.0));
+            let radius = if strength > 2.0 { 1 } else { 0 };
+            let water_level = original[index] - 1.0;
+            for dz in -radius..=radius {
+                for dx in -radius..=radius {
+                    let nx = x + dx;
+                    let nz = z + dz;
+                    if nx < 0 || nz < 0 || nx >= width as i32 || nz >= depth as i32 {
+                        continue;
+                    }
+                    let neighbor = nx as usize + nz as usize * width;
+                    let bank_offset = if dx == 0 && dz == 0 { 0.0 } else { 1.0 };
+                    let bed = original[index] - channel_depth + bank_offset;
+                    heights[neighbor] = heights[neighbor].min(bed);
+                    let neighbor_water = water_level.min(original[neighbor] - 1.0);
+                    water[neighbor] = water[neighbor].max(neighbor_water);
+                }
+            }
+        }
+    }
+
+    WaterMap { water }
+}
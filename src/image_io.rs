@@ -0,0 +1,157 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Writes tightly packed RGBA pixels as a binary PPM (alpha is dropped).
+#[cfg(test)]
+pub fn write_ppm(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    debug_assert_eq!(rgba.len(), width as usize * height as usize * 4);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut bytes = format!("P6\n{} {}\n255\n", width, height).into_bytes();
+    bytes.reserve(width as usize * height as usize * 3);
+    for pixel in rgba.chunks_exact(4) {
+        bytes.extend_from_slice(&pixel[..3]);
+    }
+    fs::write(path, bytes)
+}
+
+/// Reads a binary PPM written by `write_ppm` back into opaque RGBA pixels.
+#[cfg(test)]
+pub fn read_ppm(path: &Path) -> io::Result<(u32, u32, Vec<u8>)> {
+    let bytes = fs::read(path)?;
+    let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+    // Header: magic, width, height, max value, each whitespace separated.
+    let mut fields = Vec::with_capacity(4);
+    let mut cursor = 0;
+    while fields.len() < 4 {
+        while cursor < bytes.len() && bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+        let start = cursor;
+        while cursor < bytes.len() && !bytes[cursor].is_ascii_whitespace() {
+            cursor += 1;
+        }
+        if start == cursor {
+            return Err(invalid("truncated PPM header"));
+        }
+        fields.push(String::from_utf8_lossy(&bytes[start..cursor]).into_owned());
+    }
+    cursor += 1;
+
+    if fields[0] != "P6" || fields[3] != "255" {
+        return Err(invalid("only 8-bit binary PPM (P6) is supported"));
+    }
+    let width: u32 = fields[1].parse().map_err(|_| invalid("bad PPM width"))?;
+    let height: u32 = fields[2].parse().map_err(|_| invalid("bad PPM height"))?;
+    let pixel_count = width as usize * height as usize;
+    let Some(rgb) = bytes.get(cursor..cursor + pixel_count * 3) else {
+        return Err(invalid("truncated PPM pixel data"));
+    };
+
+    let mut rgba = Vec::with_capacity(pixel_count * 4);
+    for pixel in rgb.chunks_exact(3) {
+        rgba.extend_from_slice(pixel);
+        rgba.push(255);
+    }
+    Ok((width, height, rgba))
+}
+
+/// Writes tightly packed RGBA pixels as an 8-bit RGBA PNG.
+pub fn write_png(path: &Path, width: u32, height: u32, rgba: &[u8]) -> io::Result<()> {
+    debug_assert_eq!(rgba.len(), width as usize * height as usize * 4);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut encoder = png::Encoder::new(BufWriter::new(File::create(path)?), width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(rgba).map_err(io::Error::other)?;
+    writer.finish().map_err(io::Error::other)
+}
+
+/// Streams RGBA frames into an uncompressed YUV4MPEG2 (`.y4m`) video with
+/// 4:4:4 chroma, which ffmpeg and most players read directly.
+pub struct Y4mWriter {
+    out: BufWriter<File>,
+    width: u32,
+    height: u32,
+    planes: Vec<u8>,
+}
+
+impl Y4mWriter {
+    pub fn create(path: &Path, width: u32, height: u32, fps: u32) -> io::Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "YUV4MPEG2 W{width} H{height} F{fps}:1 Ip A1:1 C444")?;
+        Ok(Self {
+            out,
+            width,
+            height,
+            planes: vec![0; width as usize * height as usize * 3],
+        })
+    }
+
+    pub fn write_frame(&mut self, rgba: &[u8]) -> io::Result<()> {
+        let pixel_count = self.width as usize * self.height as usize;
+        debug_assert_eq!(rgba.len(), pixel_count * 4);
+        let (luma, chroma) = self.planes.split_at_mut(pixel_count);
+        let (cb, cr) = chroma.split_at_mut(pixel_count);
+        for (i, pixel) in rgba.chunks_exact(4).enumerate() {
+            [luma[i], cb[i], cr[i]] = rgb_to_ycbcr(pixel[0], pixel[1], pixel[2]);
+        }
+        self.out.write_all(b"FRAME\n")?;
+        self.out.write_all(&self.planes)
+    }
+
+    pub fn finish(mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
+
+/// BT.601 studio-range conversion, the colorspace Y4M readers assume by default.
+fn rgb_to_ycbcr(r: u8, g: u8, b: u8) -> [u8; 3] {
+    let (r, g, b) = (r as f32, g as f32, b as f32);
+    let y = 16.0 + 0.256_788 * r + 0.504_129 * g + 0.097_906 * b;
+    let cb = 128.0 - 0.148_223 * r - 0.290_993 * g + 0.439_216 * b;
+    let cr = 128.0 + 0.439_216 * r - 0.367_788 * g - 0.071_427 * b;
+    [y, cb, cr].map(|v| v.round().clamp(0.0, 255.0) as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn png_round_trips_and_compresses() {
+        let (width, height) = (64, 32);
+        let rgba: Vec<u8> = (0..width * height)
+            .flat_map(|i| [(i / width * 8) as u8, 90, 200, 255])
+            .collect();
+        let path = std::env::temp_dir().join("voxel-raymarcher-png-round-trip.png");
+        write_png(&path, width, height, &rgba).unwrap();
+
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(bytes.len() < rgba.len() / 4, "{} bytes", bytes.len());
+        let mut reader = png::Decoder::new(bytes.as_slice()).read_info().unwrap();
+        let mut decoded = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut decoded).unwrap();
+        assert_eq!((info.width, info.height), (width, height));
+        assert_eq!(decoded, rgba);
+    }
+
+    #[test]
+    fn ycbcr_conversion_uses_studio_range() {
+        assert_eq!(rgb_to_ycbcr(0, 0, 0), [16, 128, 128]);
+        assert_eq!(rgb_to_ycbcr(255, 255, 255), [235, 128, 128]);
+        assert_eq!(rgb_to_ycbcr(255, 0, 0), [81, 90, 240]);
+    }
+}
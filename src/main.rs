@@ -1,16 +1,34 @@
 use glam::{UVec2, Vec3};
 use raylib::prelude::*;
 use raylib::{ffi::SetTraceLogLevel, prelude::TraceLogLevel};
+use std::path::Path;
 use std::time::Instant;
 
+mod adaptive_resolution;
+mod benchmark;
+mod bindings;
 mod camera;
+mod camera_path;
+mod console;
 mod controls;
+mod erosion;
+mod frame_history;
+#[cfg(test)]
+mod golden_image_tests;
+mod hydrology;
+mod image_io;
+mod ores;
+mod player;
 mod raymarch;
 mod rendering;
+mod settings;
 mod simulation;
 mod state;
+mod stats;
+mod temporal_upscale;
 mod terrain_worker;
 mod ui_overlay;
+mod vegetation;
 mod viewplane;
 mod world;
 mod world_generation;
@@ -22,14 +40,40 @@ const UP: Vec3 = Vec3::new(0.0, -1.0, 0.0);
 const WORLD_SIZE: usize = 256;
 
 fn main() {
-    let mut state = state::State::new();
-    let (mut rl, rlt) = raylib::init().title("Voxels").build();
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(exit_code) = benchmark::run_cli(&args)
+        .or_else(|| camera_path::run_cli(&args))
+        .or_else(|| bindings::run_cli(&args))
+        .or_else(|| settings::run_cli(&args))
+    {
+        std::process::exit(exit_code);
+    }
+
+    let settings_path = Path::new(settings::SETTINGS_FILE);
+    let (mut settings, problems) = settings::Settings::load(settings_path);
+    for problem in problems {
+        eprintln!("{}: {}", settings::SETTINGS_FILE, problem);
+    }
+    if let Err(e) = settings.apply_args(&args) {
+        eprintln!("{}\n\n{}", e, settings::Settings::usage());
+        std::process::exit(2);
+    }
+
+    let mut state = state::State::new(&settings);
+    console::run_startup_script(&mut state);
+    let (mut rl, rlt) = raylib::init().title("Voxels").resizable().build();
     unsafe {
         SetTraceLogLevel(TraceLogLevel::LOG_WARNING as i32);
     }
+    // Quitting goes through the `quit` binding instead of raylib's fixed Escape.
+    rl.set_exit_key(None);
+    rl.set_window_min_size(
+        settings::MIN_WINDOW_DIMS.x as i32,
+        settings::MIN_WINDOW_DIMS.y as i32,
+    );
 
-    let window_dims = DIMS;
-    let fullscreen = false;
+    let window_dims = settings.window_dims;
+    let fullscreen = settings.fullscreen;
     rl.set_window_size(window_dims.x as i32, window_dims.y as i32);
     if fullscreen {
         rl.toggle_fullscreen();
@@ -37,17 +81,27 @@ fn main() {
     }
 
     center_window(&mut rl, window_dims);
+    rl.set_target_fps(settings.target_fps);
     if state.mouse_look_locked {
         rl.disable_cursor();
     } else {
         rl.enable_cursor();
     }
-    let mut renderer = rendering::Renderer::new(&mut rl, &rlt, DIMS);
+    let mut renderer = rendering::Renderer::new(&mut rl, &rlt, window_dims);
 
     while state.running && !rl.window_should_close() {
         let frame_start = Instant::now();
         state.fps = rl.get_fps() as i32;
 
+        if rl.is_window_resized() {
+            let dims = UVec2::new(
+                rl.get_screen_width().max(1) as u32,
+                rl.get_screen_height().max(1) as u32,
+            );
+            state.resize_window(dims);
+            renderer.resize(&mut rl, &rlt, dims);
+        }
+
         controls::process_events_and_input(&mut rl, &rlt, &mut state);
 
         let simulation_start = Instant::now();
@@ -56,7 +110,7 @@ fn main() {
         while state.time_since_last_update > TIMESTEP {
             state.time_since_last_update -= TIMESTEP;
 
-            simulation::step(&mut rl, &mut state);
+            simulation::step(&mut state);
         }
         state.last_frame_timings.simulation_ms = simulation_start.elapsed().as_secs_f32() * 1000.0;
 
@@ -64,11 +118,18 @@ fn main() {
 
         let mut draw_handle = rl.begin_drawing(&rlt);
         draw_handle.clear_background(Color::BLACK);
-        renderer.draw_to_window(&mut draw_handle, fullscreen, window_dims);
+        renderer.draw_to_window(&mut draw_handle, fullscreen, state.window_dims);
         rendering::draw_ui_overlay(&state, &mut draw_handle);
         drop(draw_handle);
 
         state.last_frame_timings.frame_ms = frame_start.elapsed().as_secs_f32() * 1000.0;
+        state.frame_history.push(state.last_frame_timings);
+        state.last_frame_timings.chunk_columns_applied = 0;
+    }
+
+    settings.capture(&state);
+    if let Err(e) = settings.save(settings_path) {
+        eprintln!("cannot save {}: {}", settings::SETTINGS_FILE, e);
     }
 }
 
@@ -92,5 +153,4 @@ pub fn center_window(rl: &mut raylib::RaylibHandle, window_dims: UVec2) {
     let x = monitor_pos.x as i32 + (monitor_width - window_dims.x as i32) / 2;
     let y = monitor_pos.y as i32 + (monitor_height - window_dims.y as i32) / 2;
     rl.set_window_position(x, y);
-    rl.set_target_fps(144);
 }
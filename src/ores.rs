@@ -0,0 +1,163 @@
+use raylib::color::Color;
+
+use crate::world::{MaterialId, World, CHUNK_SIZE};
+
+const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
+const ORE_SALT: u32 = 0x0AE5;
+
+/// One ore type. Depths are measured in voxels below the local surface.
+#[derive(Copy, Clone, Debug)]
+pub struct OreSettings {
+    pub color: Color,
+    pub min_depth: i32,
+    pub max_depth: i32,
+    /// Voxels visited by each vein's random walk.
+    pub vein_size: u32,
+    /// Expected veins per terrain column; the fractional part is a probability.
+    pub veins_per_column: f32,
+}
+
+pub fn default_ore_table() -> Vec<OreSettings> {
+    vec![
+        // Coal
+        OreSettings {
+            color: Color::new(38, 38, 42, 255),
+            min_depth: 3,
+            max_depth: 24,
+            vein_size: 12,
+            veins_per_column: 6.0,
+        },
+        // Iron
+        OreSettings {
+            color: Color::new(196, 148, 118, 255),
+            min_depth: 5,
+            max_depth: 28,
+            vein_size: 9,
+            veins_per_column: 3.5,
+        },
+        // Copper
+        OreSettings {
+            color: Color::new(204, 112, 58, 255),
+            min_depth: 3,
+            max_depth: 18,
+            vein_size: 8,
+            veins_per_column: 2.5,
+        },
+        // Gold
+        OreSettings {
+            color: Color::new(242, 200, 58, 255),
+            min_depth: 12,
+            max_depth: 34,
+            vein_size: 6,
+            veins_per_column: 1.0,
+        },
+        // Diamond
+        OreSettings {
+            color: Color::new(118, 232, 228, 255),
+            min_depth: 18,
+            max_depth: 40,
+            vein_size: 4,
+            veins_per_column: 0.4,
+        },
+    ]
+}
+
+/// Xorshift stream seeded per vein, so veins never depend on generation order.
+struct VeinRng(u32);
+
+impl VeinRng {
+    fn new(seed: u32, chunk_x: u32, chunk_z: u32, ore_index: usize, vein_index: u32) -> Self {
+        let mut h = seed ^ ORE_SALT.wrapping_mul(0x27D4EB2F);
+        h = h.wrapping_add(chunk_x.wrapping_mul(0x9E3779B1));
+        h = h.rotate_left(13) ^ chunk_z.wrapping_mul(0x85EBCA77);
+        h = h.wrapping_add((ore_index as u32).wrapping_mul(0xC2B2AE3D));
+        h = h.rotate_left(7) ^ vein_index.wrapping_mul(0x165667B1);
+        Self(h | 1)
+    }
+
+    #[inline]
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    #[inline]
+    fn below(&mut self, n: u32) -> u32 {
+        self.next() % n.max(1)
+    }
+}
+
+impl World {
+    fn ore_material_ids(&mut self) -> Vec<MaterialId> {
+        if let Some(ids) = &self.ore_materials {
+            return ids.clone();
+        }
+        let colors: Vec<Color> = self.ores.iter().map(|ore| ore.color).collect();
+        let ids: Vec<MaterialId> = colors
+            .into_iter()
+            .map(|color| self.intern_material(color))
+            .collect();
+        self.ore_materials = Some(ids.clone());
+        ids
+    }
+
+    /// Random-walk veins that only replace stone and never leave the column.
+    pub(crate) fn stamp_ore_veins_for_column(
+        &mut self,
+        chunk_x: u32,
+        chunk_z: u32,
+        surface_y: &[i32; CHUNK_AREA],
+        stone: MaterialId,
+    ) {
+        let ore_ids = self.ore_material_ids();
+        let lower_void = self.get_lower_void() as i32;
+        let col_min_x = chunk_x as i32 * CHUNK_SIZE as i32;
+        let col_min_z = chunk_z as i32 * CHUNK_SIZE as i32;
+        let col_max_x = col_min_x + CHUNK_SIZE as i32 - 1;
+        let col_max_z = col_min_z + CHUNK_SIZE as i32 - 1;
+
+        let ores = self.ores.clone();
+        for (ore_index, (ore, &material)) in ores.iter().zip(&ore_ids).enumerate() {
+            let depth_span = (ore.max_depth - ore.min_depth).max(0) as u32 + 1;
+
+            let mut count_rng = VeinRng::new(self.seed, chunk_x, chunk_z, ore_index, u32::MAX);
+            let whole = ore.veins_per_column.max(0.0).floor();
+            let fraction = ore.veins_per_column.max(0.0) - whole;
+            let mut vein_count = whole as u32;
+            if (count_rng.below(1000) as f32) < fraction * 1000.0 {
+                vein_count += 1;
+            }
+
+            for vein_index in 0..vein_count {
+                let mut rng = VeinRng::new(self.seed, chunk_x, chunk_z, ore_index, vein_index);
+                let local_x = rng.below(CHUNK_SIZE as u32) as usize;
+                let local_z = rng.below(CHUNK_SIZE as u32) as usize;
+                let surface = surface_y[local_x + local_z * CHUNK_SIZE];
+                let mut x = col_min_x + local_x as i32;
+                let mut z = col_min_z + local_z as i32;
+                let mut y = surface + ore.min_depth + rng.below(depth_span) as i32;
+
+                for _ in 0..ore.vein_size {
+                    if y >= 0
+                        && y < lower_void
+                        && self.get_voxel_material_unchecked_i32(x, y, z) == stone
+                    {
+                        self.set_voxel_material_i32(x, y, z, material);
+                    }
+                    match rng.below(6) {
+                        0 => x += 1,
+                        1 => x -= 1,
+                        2 => y += 1,
+                        3 => y -= 1,
+                        4 => z += 1,
+                        _ => z -= 1,
+                    }
+                    x = x.clamp(col_min_x, col_max_x);
+                    z = z.clamp(col_min_z, col_max_z);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! Walk-mode player: an axis-aligned box with gravity, jumping, 1-voxel
+//! step-up and swimming, collided against world voxels at the fixed timestep.
+
+use glam::Vec3;
+
+use crate::world::World;
+use crate::UP;
+
+const HALF_WIDTH: f32 = 0.3;
+const HEIGHT: f32 = 1.8;
+pub const EYE_HEIGHT: f32 = 1.6;
+const WALK_SPEED: f32 = 4.5;
+const SPRINT_SPEED: f32 = 8.0;
+const SWIM_SPEED: f32 = 3.0;
+/// Voxels per second squared, pulling towards +y (down).
+const GRAVITY: f32 = 28.0;
+const TERMINAL_SPEED: f32 = 50.0;
+const JUMP_SPEED: f32 = 8.5;
+const WATER_GRAVITY: f32 = 4.0;
+const WATER_TERMINAL_SPEED: f32 = 2.0;
+const SWIM_VERTICAL_SPEED: f32 = 3.5;
+const STEP_HEIGHT: f32 = 1.0;
+/// Gap kept between the box and the voxel it touches so it never starts a move overlapping.
+const SKIN: f32 = 1e-3;
+/// Longest move tested at once; below one voxel so thin walls cannot be skipped.
+const MAX_SUBSTEP: f32 = 0.45;
+
+/// What the controls ask of the player this step.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct WalkInput {
+    /// Horizontal move direction in world space, length 0 to 1.
+    pub wish_dir: Vec3,
+    pub jump: bool,
+    pub descend: bool,
+    pub sprint: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Player {
+    /// Center of the box's bottom face (the feet); the box extends towards -y.
+    pub pos: Vec3,
+    pub velocity: Vec3,
+    pub on_ground: bool,
+    pub in_water: bool,
+}
+
+/// Whether the player collides with the voxel. The world's sides and bottom
+/// act as walls; above the world is open sky.
+fn is_solid_for_player(world: &World, x: i32, y: i32, z: i32) -> bool {
+    match world.voxel_at(x, y, z) {
+        Some(material) => material != 0 && Some(material) != world.water_material(),
+        None => y >= 0,
+    }
+}
+
+fn box_is_blocked(world: &World, min: Vec3, max: Vec3) -> bool {
+    let lo = min.floor().as_ivec3();
+    let hi = (max.ceil() - Vec3::ONE).as_ivec3();
+    for x in lo.x..=hi.x {
+        for y in lo.y..=hi.y {
+            for z in lo.z..=hi.z {
+                if is_solid_for_player(world, x, y, z) {
+                    return true;
+                }
+            }
+        }
+    }
+    false
+}
+
+impl Player {
+    /// Places the feet `EYE_HEIGHT` below `eye`, lifted out of any voxel it starts inside.
+    pub fn spawn_at_eye(world: &World, eye: Vec3) -> Self {
+        let mut player = Self {
+            pos: eye - UP * EYE_HEIGHT,
+            velocity: Vec3::ZERO,
+            on_ground: false,
+            in_water: false,
+        };
+        while player.pos.y > HEIGHT && player.is_blocked_at(world, player.pos) {
+            player.pos.y = player.pos.y.floor() - SKIN;
+        }
+        player
+    }
+
+    pub fn eye_pos(&self) -> Vec3 {
+        self.pos + UP * EYE_HEIGHT
+    }
+
+    fn bounds_at(pos: Vec3) -> (Vec3, Vec3) {
+        (
+            Vec3::new(pos.x - HALF_WIDTH, pos.y - HEIGHT, pos.z - HALF_WIDTH),
+            Vec3::new(pos.x + HALF_WIDTH, pos.y, pos.z + HALF_WIDTH),
+        )
+    }
+
+    fn is_blocked_at(&self, world: &World, pos: Vec3) -> bool {
+        let (min, max) = Self::bounds_at(pos);
+        box_is_blocked(world, min, max)
+    }
+
+    pub fn step(&mut self, world: &World, input: &WalkInput, dt: f32) {
+        self.in_water = world.is_water_at(self.pos + UP * (HEIGHT * 0.5));
+
+        let speed = if self.in_water {
+            SWIM_SPEED
+        } else if input.sprint {
+            SPRINT_SPEED
+        } else {
+            WALK_SPEED
+        };
+        let wish = input.wish_dir.clamp_length_max(1.0) * speed;
+        self.velocity.x = wish.x;
+        self.velocity.z = wish.z;
+
+        if self.in_water {
+            self.velocity.y = (self.velocity.y + WATER_GRAVITY * dt).min(WATER_TERMINAL_SPEED);
+            if input.jump {
+                self.velocity.y = -SWIM_VERTICAL_SPEED;
+            } else if input.descend {
+                self.velocity.y = SWIM_VERTICAL_SPEED;
+            }
+        } else {
+            self.velocity.y = (self.velocity.y + GRAVITY * dt).min(TERMINAL_SPEED);
+            if input.jump && self.on_ground {
+                self.velocity.y = -JUMP_SPEED;
+            }
+        }
+
+        let was_on_ground = self.on_ground;
+        let horizontal = Vec3::new(self.velocity.x, 0.0, self.velocity.z) * dt;
+        if horizontal != Vec3::ZERO {
+            self.move_horizontal(world, horizontal, was_on_ground || self.in_water);
+        }
+
+        let vertical = self.velocity.y * dt;
+        self.on_ground = false;
+        if self.move_axis(world, 1, vertical) {
+            self.on_ground = vertical > 0.0;
+            self.velocity.y = 0.0;
+        }
+    }
+
+    /// Moves sideways; a blocked move is retried one voxel higher so the
+    /// player walks up ledges instead of stopping at them.
+    fn move_horizontal(&mut self, world: &World, delta: Vec3, can_step_up: bool) {
+        let start = self.pos;
+        let blocked_x = self.move_axis(world, 0, delta.x);
+        let blocked_z = self.move_axis(world, 2, delta.z);
+        if !(blocked_x || blocked_z) || !can_step_up {
+            return;
+        }
+
+        let stepped = start + UP * STEP_HEIGHT;
+        if self.is_blocked_at(world, stepped) || self.is_blocked_at(world, stepped + delta) {
+            return;
+        }
+        let blocked_pos = self.pos;
+        self.pos = stepped + delta;
+        // Settle back down onto the ledge.
+        self.move_axis(world, 1, STEP_HEIGHT);
+        if self.pos.y >= blocked_pos.y {
+            self.pos = blocked_pos;
+        }
+    }
+
+    /// Moves along one axis, stopping flush against the first solid voxel.
+    /// Returns whether the move was cut short.
+    fn move_axis(&mut self, world: &World, axis: usize, delta: f32) -> bool {
+        let mut remaining = delta;
+        while remaining != 0.0 {
+            let step = remaining.clamp(-MAX_SUBSTEP, MAX_SUBSTEP);
+            let mut moved = self.pos;
+            moved[axis] += step;
+            if !self.is_blocked_at(world, moved) {
+                self.pos = moved;
+                remaining -= step;
+                continue;
+            }
+
+            let (min, max) = Self::bounds_at(self.pos);
+            let gap = if step > 0.0 {
+                ((max[axis] + step).floor() - max[axis] - SKIN).max(0.0)
+            } else {
+                ((min[axis] + step).floor() + 1.0 - min[axis] + SKIN).min(0.0)
+            };
+            self.pos[axis] += gap;
+            return true;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::Block;
+
+    const DIM: usize = 32;
+    const DT: f32 = 1.0 / 60.0;
+
+    fn flat_world() -> World {
+        let mut world = World::new(DIM);
+        world.gen_floor(Block::new(255, 255, 255, 255));
+        world
+    }
+
+    fn run(player: &mut Player, world: &World, input: WalkInput, steps: usize) {
+        for _ in 0..steps {
+            player.step(world, &input, DT);
+        }
+    }
+
+    fn floor_top(world: &World) -> f32 {
+        world.get_floor_level() as f32
+    }
+
+    #[test]
+    fn falls_and_lands_on_the_floor() {
+        let world = flat_world();
+        let mut player = Player::spawn_at_eye(&world, Vec3::new(16.5, 10.0, 16.5));
+        run(&mut player, &world, WalkInput::default(), 120);
+        assert!(player.on_ground);
+        assert!(
+            (player.pos.y - floor_top(&world)).abs() < 0.01,
+            "{}",
+            player.pos
+        );
+        assert!(!player.is_blocked_at(&world, player.pos));
+
+        let jump = WalkInput {
+            jump: true,
+            ..WalkInput::default()
+        };
+        run(&mut player, &world, jump, 10);
+        assert!(player.pos.y < floor_top(&world) - 0.5);
+    }
+
+    #[test]
+    fn steps_onto_one_voxel_ledges_but_not_walls() {
+        let mut world = flat_world();
+        let ledge = world.get_above_floor_level() as f32;
+        world.gen_cube(
+            Vec3::new(10.0, ledge, 0.0),
+            Vec3::new(4.0, 1.0, DIM as f32),
+            Block::new(200, 0, 0, 255),
+        );
+        world.gen_cube(
+            Vec3::new(20.0, ledge - 1.0, 0.0),
+            Vec3::new(1.0, 2.0, DIM as f32),
+            Block::new(0, 200, 0, 255),
+        );
+
+        let mut player = Player::spawn_at_eye(&world, Vec3::new(7.5, ledge - 2.0, 16.5));
+        run(&mut player, &world, WalkInput::default(), 60);
+        let walk = WalkInput {
+            wish_dir: Vec3::X,
+            ..WalkInput::default()
+        };
+        run(&mut player, &world, walk, 40);
+        assert!(player.pos.x > 10.0 && player.pos.x < 14.0, "{}", player.pos);
+        assert!((player.pos.y - ledge).abs() < 0.01, "{}", player.pos);
+
+        run(&mut player, &world, walk, 200);
+        assert!(
+            (player.pos.x - (20.0 - HALF_WIDTH)).abs() < 0.01,
+            "{}",
+            player.pos
+        );
+        assert!((player.pos.y - floor_top(&world)).abs() < 0.01);
+    }
+
+    #[test]
+    fn swims_in_water_instead_of_falling() {
+        let mut world = flat_world();
+        let water = world.terrain_material_ids().water;
+        let floor = world.get_floor_level() as i32;
+        for x in 0..DIM as i32 {
+            for y in floor - 6..floor {
+                for z in 0..DIM as i32 {
+                    world.set_voxel_material_i32(x, y, z, water);
+                }
+            }
+        }
+
+        let mut player = Player::spawn_at_eye(&world, Vec3::new(16.5, floor as f32 - 4.0, 16.5));
+        run(&mut player, &world, WalkInput::default(), 1);
+        assert!(player.in_water);
+        run(&mut player, &world, WalkInput::default(), 10);
+        assert!(player.velocity.y <= WATER_TERMINAL_SPEED);
+
+        let start_y = player.pos.y;
+        let swim_up = WalkInput {
+            jump: true,
+            ..WalkInput::default()
+        };
+        run(&mut player, &world, swim_up, 20);
+        assert!(player.pos.y < start_y - 1.0);
+    }
+}
@@ -1,9 +1,9 @@
-use glam::Vec3;
+use glam::{IVec3, Quat, Vec2, Vec3};
 use raylib::prelude::*;
 use rayon::prelude::*;
 
 use crate::camera::Camera;
-use crate::viewplane::Viewplane;
+use crate::viewplane::{Projection, Viewplane};
 use crate::world::{MaterialId, World, CHUNK_SIZE};
 
 pub const MIN_STEP_BUDGET: f32 = 0.02;
@@ -12,6 +12,18 @@ pub const MAX_RAY_STEPS: i32 = 4096;
 
 const DDA_EPSILON: f32 = 0.0001;
 const AIR_MATERIAL_ID: MaterialId = 0;
+const AIR_IOR: f32 = 1.0;
+/// Interfaces a ray may split at before its descendants just pass straight
+/// through media.
+const MAX_SECONDARY_DEPTH: u32 = 2;
+/// How far secondary rays start off the interface, against self-intersection.
+const SECONDARY_RAY_OFFSET: f32 = 0.001;
+/// Voxel steps (or chunk skips) per ray that saturate the debug heatmaps.
+const HEATMAP_MAX_STEPS: f32 = 256.0;
+const HEATMAP_MAX_SKIPS: f32 = 16.0;
+/// Width of the chunk-boundary lines, in voxels.
+const CHUNK_GRID_LINE_WIDTH: f32 = 0.08;
+const CHUNK_GRID_COLOR: Color = Color::new(255, 0, 255, 255);
 
 #[derive(Debug, Copy, Clone, Default)]
 pub struct RenderStats {
@@ -19,14 +31,139 @@ pub struct RenderStats {
     pub rays_hit: u32,
     pub voxel_steps: u64,
     pub empty_chunk_skips: u32,
+    /// Reflected and refracted rays spawned at water and glass surfaces.
+    pub secondary_rays: u32,
+    pub secondary_voxel_steps: u64,
 }
 
 pub struct RaymarchInput<'a> {
     pub world: &'a World,
     pub camera: &'a Camera,
     pub viewplane: &'a Viewplane,
+    pub projection: Projection,
     pub draw_distance: f32,
     pub voxel_step_budget: f32,
+    /// Treated as air while tracing (x-ray view).
+    pub hidden_material: Option<MaterialId>,
+    pub debug_view: DebugView,
+    /// Offset of every ray from its pixel center, in pixels (within ±0.5).
+    pub jitter: Vec2,
+    pub supersampling: Supersampling,
+}
+
+/// Rays traced per pixel and averaged by `draw_voxels`.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum Supersampling {
+    #[default]
+    Off,
+    /// Four rays on an ordered 2x2 grid.
+    Grid2x2,
+    /// Four rays on a rotated grid, which resolves near-horizontal and
+    /// near-vertical edges better than `Grid2x2`.
+    RotatedGrid,
+}
+
+impl Supersampling {
+    pub const ALL: [Supersampling; 3] = [
+        Supersampling::Off,
+        Supersampling::Grid2x2,
+        Supersampling::RotatedGrid,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Grid2x2 => "2x2",
+            Self::RotatedGrid => "rgss",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|samples| samples.label() == label)
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&samples| samples == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    /// Ray offsets from the pixel center, in pixels.
+    fn offsets(self) -> &'static [Vec2] {
+        const CENTER: [Vec2; 1] = [Vec2::ZERO];
+        const GRID_2X2: [Vec2; 4] = [
+            Vec2::new(-0.25, -0.25),
+            Vec2::new(0.25, -0.25),
+            Vec2::new(-0.25, 0.25),
+            Vec2::new(0.25, 0.25),
+        ];
+        const ROTATED_GRID: [Vec2; 4] = [
+            Vec2::new(0.125, -0.375),
+            Vec2::new(0.375, 0.125),
+            Vec2::new(-0.125, 0.375),
+            Vec2::new(-0.375, -0.125),
+        ];
+        match self {
+            Self::Off => &CENTER,
+            Self::Grid2x2 => &GRID_2X2,
+            Self::RotatedGrid => &ROTATED_GRID,
+        }
+    }
+}
+
+/// What `draw_voxels` writes per pixel in place of the shaded color.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+pub enum DebugView {
+    #[default]
+    Shaded,
+    /// Voxel steps taken by the ray, cold to hot.
+    StepHeatmap,
+    /// Empty chunks the ray skipped over, cold to hot.
+    ChunkSkips,
+    /// Distance to the first hit, near white to far black.
+    HitDepth,
+    /// Face the ray entered the first hit through, as RGB = XYZ.
+    Normals,
+    /// Stable false color per material of the first hit.
+    MaterialIds,
+    /// Shaded view with lines where hits lie on a chunk boundary.
+    ChunkGrid,
+}
+
+impl DebugView {
+    pub const ALL: [DebugView; 7] = [
+        DebugView::Shaded,
+        DebugView::StepHeatmap,
+        DebugView::ChunkSkips,
+        DebugView::HitDepth,
+        DebugView::Normals,
+        DebugView::MaterialIds,
+        DebugView::ChunkGrid,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Shaded => "Shaded",
+            Self::StepHeatmap => "Steps",
+            Self::ChunkSkips => "Chunk Skips",
+            Self::HitDepth => "Depth",
+            Self::Normals => "Normals",
+            Self::MaterialIds => "Materials",
+            Self::ChunkGrid => "Chunk Grid",
+        }
+    }
+
+    /// The next view in `ALL`, wrapping around; `back` walks the other way.
+    pub fn cycled(self, back: bool) -> Self {
+        let index = Self::ALL.iter().position(|&view| view == self).unwrap_or(0);
+        let len = Self::ALL.len();
+        let next = if back { index + len - 1 } else { index + 1 };
+        Self::ALL[next % len]
+    }
 }
 
 #[derive(Copy, Clone)]
@@ -232,188 +369,1220 @@ fn chunk_exit_t(
     t
 }
 
+/// Per-frame ray limits shared by every pixel.
+#[derive(Copy, Clone)]
+struct TraceLimits {
+    world_min: Vec3,
+    world_max: Vec3,
+    world_dim: i32,
+    draw_distance: f32,
+    num_ray_steps: i32,
+    hidden_material: MaterialId,
+}
+
+impl TraceLimits {
+    fn new(
+        world: &World,
+        draw_distance: f32,
+        voxel_step_budget: f32,
+        hidden_material: Option<MaterialId>,
+    ) -> Self {
+        let step_budget = voxel_step_budget.max(MIN_STEP_BUDGET).min(MAX_STEP_BUDGET);
+        let mut num_ray_steps = (draw_distance / step_budget).ceil() as i32;
+        num_ray_steps = num_ray_steps.max(1).min(MAX_RAY_STEPS);
+
+        Self {
+            world_min: Vec3::ZERO,
+            world_max: Vec3::splat(world.dim as f32 - DDA_EPSILON),
+            world_dim: world.dim as i32,
+            draw_distance: num_ray_steps as f32 * step_budget,
+            num_ray_steps,
+            hidden_material: hidden_material.unwrap_or(AIR_MATERIAL_ID),
+        }
+    }
+}
+
+#[derive(Copy, Clone)]
+struct RayTrace {
+    /// First non-air voxel the traversal entered.
+    first_hit: Option<IVec3>,
+    first_material: MaterialId,
+    hit_distance: f32,
+    accumulated: Vec3,
+    /// Light that still reaches the ray origin from beyond `interface`.
+    transmittance: Vec3,
+    /// Where the traversal stopped at a change of refractive index.
+    interface: Option<Interface>,
+}
+
+/// Boundary between two media with different refractive indices, e.g. air
+/// and water.
+#[derive(Copy, Clone)]
+struct Interface {
+    t: f32,
+    /// Voxel on the far side of the boundary.
+    voxel: IVec3,
+    from_ior: f32,
+    to_ior: f32,
+}
+
+/// Chunk-skipping DDA walk of one ray, blending transparent voxels until an
+/// opaque one (or the step budget) ends it.
+#[inline]
+fn trace_ray(
+    world: &World,
+    limits: &TraceLimits,
+    origin: Vec3,
+    ray: Vec3,
+    stats: &mut RenderStats,
+) -> RayTrace {
+    trace_ray_in(world, limits, origin, ray, AIR_IOR, false, stats)
+}
+
+/// `trace_ray` for a ray starting inside a medium of refractive index
+/// `medium_ior`. With `stop_at_interfaces`, it ends where the index changes
+/// between air and transparent voxels so the caller can split the ray.
+#[inline]
+fn trace_ray_in(
+    world: &World,
+    limits: &TraceLimits,
+    origin: Vec3,
+    ray: Vec3,
+    medium_ior: f32,
+    stop_at_interfaces: bool,
+    stats: &mut RenderStats,
+) -> RayTrace {
+    let mut current_ior = medium_ior;
+    let mut interface = None;
+    let mut first_hit = None;
+    let mut first_material = AIR_MATERIAL_ID;
+    let mut hit_distance = limits.draw_distance;
+
+    let mut accumulated = Vec3::ZERO;
+    let mut transmittance = Vec3::ONE;
+
+    if let Some((mut t_enter, mut t_exit)) =
+        ray_aabb_intersection(origin, ray, limits.world_min, limits.world_max)
+    {
+        t_enter = t_enter.max(0.0);
+        t_exit = t_exit.min(limits.draw_distance);
+
+        if t_enter <= t_exit {
+            let world_dim = limits.world_dim;
+            let mut t = t_enter;
+            let mut dda = init_dda(origin, ray, t);
+            let mut remaining_steps = limits.num_ray_steps;
+            let mut last_chunk_x = i32::MIN;
+            let mut last_chunk_y = i32::MIN;
+            let mut last_chunk_z = i32::MIN;
+            let mut current_chunk_empty = false;
+            let mut current_chunk_has_transparency = false;
+
+            while t <= t_exit && remaining_steps > 0 {
+                if dda.voxel_x < 0
+                    || dda.voxel_y < 0
+                    || dda.voxel_z < 0
+                    || dda.voxel_x >= world_dim
+                    || dda.voxel_y >= world_dim
+                    || dda.voxel_z >= world_dim
+                {
+                    break;
+                }
+
+                let chunk_x = dda.voxel_x.div_euclid(CHUNK_SIZE as i32);
+                let chunk_y = dda.voxel_y.div_euclid(CHUNK_SIZE as i32);
+                let chunk_z = dda.voxel_z.div_euclid(CHUNK_SIZE as i32);
+                if chunk_x != last_chunk_x || chunk_y != last_chunk_y || chunk_z != last_chunk_z {
+                    let Some(chunk_meta) = world.chunk_meta(chunk_x, chunk_y, chunk_z) else {
+                        break;
+                    };
+                    current_chunk_empty = chunk_meta.is_empty();
+                    current_chunk_has_transparency = chunk_meta.has_transparency;
+                    last_chunk_x = chunk_x;
+                    last_chunk_y = chunk_y;
+                    last_chunk_z = chunk_z;
+                }
+
+                if current_chunk_empty {
+                    if stop_at_interfaces && current_ior != AIR_IOR {
+                        interface = Some(Interface {
+                            t,
+                            voxel: IVec3::new(dda.voxel_x, dda.voxel_y, dda.voxel_z),
+                            from_ior: current_ior,
+                            to_ior: AIR_IOR,
+                        });
+                        break;
+                    }
+                    current_ior = AIR_IOR;
+                    stats.empty_chunk_skips += 1;
+                    t = chunk_exit_t(origin, ray, chunk_x, chunk_y, chunk_z, t) + DDA_EPSILON;
+                    if t > t_exit {
+                        break;
+                    }
+                    dda = init_dda(origin, ray, t);
+                    continue;
+                }
+
+                stats.voxel_steps += 1;
+                remaining_steps -= 1;
+
+                let material_id =
+                    world.get_voxel_material_unchecked_i32(dda.voxel_x, dda.voxel_y, dda.voxel_z);
+                let is_air =
+                    material_id == AIR_MATERIAL_ID || material_id == limits.hidden_material;
+                if is_air && current_ior != AIR_IOR {
+                    if stop_at_interfaces {
+                        interface = Some(Interface {
+                            t,
+                            voxel: IVec3::new(dda.voxel_x, dda.voxel_y, dda.voxel_z),
+                            from_ior: current_ior,
+                            to_ior: AIR_IOR,
+                        });
+                        break;
+                    }
+                    current_ior = AIR_IOR;
+                }
+                if !is_air {
+                    if first_hit.is_none() {
+                        first_hit = Some(IVec3::new(dda.voxel_x, dda.voxel_y, dda.voxel_z));
+                        first_material = material_id;
+                        hit_distance = t.max(0.0);
+                    }
+
+                    let material = world.get_material(material_id);
+                    if material.is_transparent && material.ior != current_ior {
+                        if stop_at_interfaces {
+                            interface = Some(Interface {
+                                t,
+                                voxel: IVec3::new(dda.voxel_x, dda.voxel_y, dda.voxel_z),
+                                from_ior: current_ior,
+                                to_ior: material.ior,
+                            });
+                            break;
+                        }
+                        current_ior = material.ior;
+                    }
+                    if !current_chunk_has_transparency || !material.is_transparent {
+                        let color = material.color;
+                        accumulated += Vec3::new(color.r as f32, color.g as f32, color.b as f32)
+                            * transmittance;
+                        break;
+                    }
+
+                    // Beer–Lambert over the ray's actual length inside this voxel.
+                    let t_voxel_exit = dda.t_max_x.min(dda.t_max_y).min(dda.t_max_z).min(t_exit);
+                    let length = (t_voxel_exit - t).max(0.0);
+                    let segment_transmittance = (-material.absorption * length).exp();
+                    accumulated +=
+                        material.scatter * (Vec3::ONE - segment_transmittance) * transmittance;
+                    transmittance *= segment_transmittance;
+                    if transmittance.max_element() <= 0.01 {
+                        break;
+                    }
+                }
+
+                t = step_dda(&mut dda);
+            }
+        }
+    }
+
+    RayTrace {
+        first_hit,
+        first_material,
+        hit_distance,
+        accumulated,
+        transmittance,
+        interface,
+    }
+}
+
+/// Distance fog and sky constants for turning a `RayTrace` into a pixel.
+#[derive(Copy, Clone)]
+struct Shading {
+    draw_distance: f32,
+    inv_draw_distance: f32,
+    sky_limit: f32,
+}
+
+impl Shading {
+    fn new(world: &World, limits: &TraceLimits) -> Self {
+        Self {
+            draw_distance: limits.draw_distance,
+            inv_draw_distance: 1.0 / limits.draw_distance.max(0.0001),
+            sky_limit: world.get_above_floor_level() as f32,
+        }
+    }
+}
+
+#[inline]
+fn shade_trace(
+    trace: &RayTrace,
+    origin: Vec3,
+    ray: Vec3,
+    shading: &Shading,
+    stats: &mut RenderStats,
+) -> Vec3 {
+    let mut rgb = Vec3::ZERO;
+    if trace.first_hit.is_some() {
+        stats.rays_hit += 1;
+        let mut brightness = 1.0 - trace.hit_distance * shading.inv_draw_distance;
+        brightness = brightness.max(0.0).min(1.0);
+        let lit_scale = 0.25 + brightness * 0.75;
+        rgb = trace.accumulated * lit_scale;
+    } else {
+        let sky_probe_y = origin.y + ray.y * shading.draw_distance;
+        if sky_probe_y < shading.sky_limit {
+            const BLUE: Vec3 = Vec3::new(0.0, 0.0, 255.0);
+            rgb = BLUE * 0.1;
+        }
+    }
+    rgb
+}
+
+fn rgb_color(rgb: Vec3) -> Color {
+    let rgb = rgb.clamp(Vec3::ZERO, Vec3::splat(255.0));
+    Color::new(rgb.x as u8, rgb.y as u8, rgb.z as u8, 255)
+}
+
+/// Cold-to-hot ramp (black, blue, green, yellow, red, white) for `value` in 0..=1.
+fn heat_color(value: f32) -> Color {
+    const STOPS: [Vec3; 6] = [
+        Vec3::new(0.0, 0.0, 0.0),
+        Vec3::new(0.0, 0.0, 255.0),
+        Vec3::new(0.0, 255.0, 0.0),
+        Vec3::new(255.0, 255.0, 0.0),
+        Vec3::new(255.0, 0.0, 0.0),
+        Vec3::new(255.0, 255.0, 255.0),
+    ];
+    let scaled = value.clamp(0.0, 1.0) * (STOPS.len() - 1) as f32;
+    let index = (scaled as usize).min(STOPS.len() - 2);
+    let rgb = STOPS[index].lerp(STOPS[index + 1], scaled - index as f32);
+    Color::new(rgb.x as u8, rgb.y as u8, rgb.z as u8, 255)
+}
+
+/// Axis-aligned normal of the face of `voxel` that `ray` entered through.
+fn hit_normal(voxel: IVec3, origin: Vec3, ray: Vec3, hit_distance: f32) -> Vec3 {
+    let local = origin + ray * hit_distance - voxel.as_vec3() - Vec3::splat(0.5);
+    let axis = if local.x.abs() >= local.y.abs() && local.x.abs() >= local.z.abs() {
+        0
+    } else if local.y.abs() >= local.z.abs() {
+        1
+    } else {
+        2
+    };
+    let mut normal = Vec3::ZERO;
+    normal[axis] = -ray[axis].signum();
+    normal
+}
+
+/// Per-pixel color for every `DebugView` except `Shaded`. `steps` and `skips`
+/// are this ray's own counts.
+#[allow(clippy::too_many_arguments)]
+fn debug_color(
+    view: DebugView,
+    trace: &RayTrace,
+    origin: Vec3,
+    ray: Vec3,
+    steps: u64,
+    skips: u32,
+    shading: &Shading,
+    shaded: Color,
+) -> Color {
+    match view {
+        DebugView::Shaded => shaded,
+        DebugView::StepHeatmap => heat_color(steps as f32 / HEATMAP_MAX_STEPS),
+        DebugView::ChunkSkips => heat_color(skips as f32 / HEATMAP_MAX_SKIPS),
+        DebugView::HitDepth => match trace.first_hit {
+            Some(_) => {
+                let near = 1.0 - trace.hit_distance * shading.inv_draw_distance;
+                let level = (near.clamp(0.0, 1.0) * 255.0) as u8;
+                Color::new(level, level, level, 255)
+            }
+            None => Color::BLACK,
+        },
+        DebugView::Normals => match trace.first_hit {
+            Some(voxel) => {
+                let normal = hit_normal(voxel, origin, ray, trace.hit_distance);
+                let rgb = (normal * 0.5 + Vec3::splat(0.5)) * 255.0;
+                Color::new(rgb.x as u8, rgb.y as u8, rgb.z as u8, 255)
+            }
+            None => Color::BLACK,
+        },
+        DebugView::MaterialIds => match trace.first_hit {
+            Some(_) => {
+                let hash = (trace.first_material as u32 + 1).wrapping_mul(0x9E37_79B1);
+                Color::new(
+                    (hash >> 24) as u8 | 0x40,
+                    (hash >> 16) as u8 | 0x40,
+                    (hash >> 8) as u8 | 0x40,
+                    255,
+                )
+            }
+            None => Color::BLACK,
+        },
+        DebugView::ChunkGrid => match trace.first_hit {
+            Some(voxel) => {
+                let normal = hit_normal(voxel, origin, ray, trace.hit_distance);
+                let point = origin + ray * trace.hit_distance;
+                let on_line = (0..3).any(|axis| {
+                    if normal[axis] != 0.0 {
+                        return false;
+                    }
+                    let along = point[axis].rem_euclid(CHUNK_SIZE as f32);
+                    along < CHUNK_GRID_LINE_WIDTH
+                        || along > CHUNK_SIZE as f32 - CHUNK_GRID_LINE_WIDTH
+                });
+                if on_line {
+                    CHUNK_GRID_COLOR
+                } else {
+                    shaded
+                }
+            }
+            None => shaded,
+        },
+    }
+}
+
+fn merge_stats(mut acc: RenderStats, row: RenderStats) -> RenderStats {
+    acc.rays_cast += row.rays_cast;
+    acc.rays_hit += row.rays_hit;
+    acc.voxel_steps += row.voxel_steps;
+    acc.empty_chunk_skips += row.empty_chunk_skips;
+    acc.secondary_rays += row.secondary_rays;
+    acc.secondary_voxel_steps += row.secondary_voxel_steps;
+    acc
+}
+
+/// Refractive index of the medium around `point`: that of a transparent voxel
+/// it lies in, else air's.
+fn medium_ior_at(world: &World, limits: &TraceLimits, point: Vec3) -> f32 {
+    let voxel = point.floor().as_ivec3();
+    if voxel.min_element() < 0 || voxel.max_element() >= limits.world_dim {
+        return AIR_IOR;
+    }
+    let material_id = world.get_voxel_material_unchecked_i32(voxel.x, voxel.y, voxel.z);
+    let material = world.get_material(material_id);
+    if material_id == limits.hidden_material || !material.is_transparent {
+        AIR_IOR
+    } else {
+        material.ior
+    }
+}
+
+/// Schlick's approximation of the share of light reflected at an interface.
+fn fresnel_reflectance(from_ior: f32, to_ior: f32, cos_incident: f32, cos_transmitted: f32) -> f32 {
+    let r0 = ((from_ior - to_ior) / (from_ior + to_ior)).powi(2);
+    let cos = if from_ior > to_ior {
+        cos_transmitted
+    } else {
+        cos_incident
+    };
+    r0 + (1.0 - r0) * (1.0 - cos).powi(5)
+}
+
+/// Traces and shades one ray. At the first change of refractive index (up to
+/// `MAX_SECONDARY_DEPTH` deep) it spawns a reflected and a refracted ray and
+/// blends them by Fresnel. `travelled` is the distance from the eye to
+/// `origin`, for the distance falloff.
+#[allow(clippy::too_many_arguments)]
+fn shade_ray(
+    world: &World,
+    limits: &TraceLimits,
+    shading: &Shading,
+    origin: Vec3,
+    ray: Vec3,
+    medium_ior: f32,
+    depth: u32,
+    travelled: f32,
+    stats: &mut RenderStats,
+) -> (RayTrace, Vec3) {
+    let stop_at_interfaces = depth < MAX_SECONDARY_DEPTH;
+    let mut trace = trace_ray_in(
+        world,
+        limits,
+        origin,
+        ray,
+        medium_ior,
+        stop_at_interfaces,
+        stats,
+    );
+    let Some(interface) = trace.interface else {
+        trace.hit_distance += travelled;
+        let rgb = shade_trace(&trace, origin, ray, shading, stats);
+        trace.hit_distance -= travelled;
+        return (trace, rgb);
+    };
+
+    let mut rgb = Vec3::ZERO;
+    if trace.first_hit.is_some() {
+        trace.hit_distance += travelled;
+        rgb = shade_trace(&trace, origin, ray, shading, stats);
+        trace.hit_distance -= travelled;
+    }
+
+    let normal = hit_normal(interface.voxel, origin, ray, interface.t);
+    let point = origin + ray * interface.t;
+    let travelled = travelled + interface.t;
+    let cos_incident = -ray.dot(normal);
+    let reflected = shade_secondary(
+        world,
+        limits,
+        shading,
+        point + normal * SECONDARY_RAY_OFFSET,
+        ray + normal * (2.0 * cos_incident),
+        interface.from_ior,
+        depth + 1,
+        travelled,
+        stats,
+    );
+
+    let eta = interface.from_ior / interface.to_ior;
+    let sin2_transmitted = eta * eta * (1.0 - cos_incident * cos_incident);
+    let split = if sin2_transmitted >= 1.0 {
+        // Total internal reflection.
+        reflected
+    } else {
+        let cos_transmitted = (1.0 - sin2_transmitted).sqrt();
+        let refracted = shade_secondary(
+            world,
+            limits,
+            shading,
+            point - normal * SECONDARY_RAY_OFFSET,
+            ray * eta + normal * (eta * cos_incident - cos_transmitted),
+            interface.to_ior,
+            depth + 1,
+            travelled,
+            stats,
+        );
+        let reflectance = fresnel_reflectance(
+            interface.from_ior,
+            interface.to_ior,
+            cos_incident,
+            cos_transmitted,
+        );
+        reflected * reflectance + refracted * (1.0 - reflectance)
+    };
+    rgb += split * trace.transmittance;
+    (trace, rgb)
+}
+
+/// `shade_ray` for a reflected or refracted ray, counted in the parent's
+/// secondary stats rather than its own.
+#[allow(clippy::too_many_arguments)]
+fn shade_secondary(
+    world: &World,
+    limits: &TraceLimits,
+    shading: &Shading,
+    origin: Vec3,
+    ray: Vec3,
+    medium_ior: f32,
+    depth: u32,
+    travelled: f32,
+    stats: &mut RenderStats,
+) -> Vec3 {
+    let mut secondary = RenderStats {
+        rays_cast: 1,
+        ..RenderStats::default()
+    };
+    let (_, rgb) = shade_ray(
+        world,
+        limits,
+        shading,
+        origin,
+        ray.normalize(),
+        medium_ior,
+        depth,
+        travelled,
+        &mut secondary,
+    );
+    stats.secondary_rays += secondary.rays_cast + secondary.secondary_rays;
+    stats.secondary_voxel_steps += secondary.voxel_steps + secondary.secondary_voxel_steps;
+    rgb
+}
+
+/// First non-air voxel along a ray within `max_distance`, with its hit distance.
+pub fn raycast_first_hit(
+    world: &World,
+    origin: Vec3,
+    dir: Vec3,
+    max_distance: f32,
+) -> Option<(IVec3, f32)> {
+    let limits = TraceLimits::new(world, max_distance, MIN_STEP_BUDGET, None);
+    let mut stats = RenderStats::default();
+    let trace = trace_ray(world, &limits, origin, dir.normalize(), &mut stats);
+    trace.first_hit.map(|voxel| (voxel, trace.hit_distance))
+}
+
 pub fn draw_voxels(
     input: RaymarchInput<'_>,
     pixels: &mut [u8],
     width: i32,
     height: i32,
 ) -> RenderStats {
-    debug_assert_eq!(pixels.len(), (width as usize) * (height as usize) * 4);
+    trace_view(input, pixels, None, width, height)
+}
+
+/// `draw_voxels` that also writes each pixel's hit distance to `depth`
+/// (the nearest of its supersamples; infinity where every ray missed).
+pub fn draw_voxels_with_depth(
+    input: RaymarchInput<'_>,
+    pixels: &mut [u8],
+    depth: &mut [f32],
+    width: i32,
+    height: i32,
+) -> RenderStats {
+    debug_assert_eq!(depth.len(), (width as usize) * (height as usize));
+    trace_view(input, pixels, Some(depth), width, height)
+}
 
-    let step_budget = input
-        .voxel_step_budget
-        .max(MIN_STEP_BUDGET)
-        .min(MAX_STEP_BUDGET);
-    let mut num_ray_steps = (input.draw_distance / step_budget).ceil() as i32;
-    num_ray_steps = num_ray_steps.max(1).min(MAX_RAY_STEPS);
+fn trace_view(
+    input: RaymarchInput<'_>,
+    pixels: &mut [u8],
+    depth: Option<&mut [f32]>,
+    width: i32,
+    height: i32,
+) -> RenderStats {
+    debug_assert_eq!(pixels.len(), (width as usize) * (height as usize) * 4);
 
-    let draw_distance = num_ray_steps as f32 * step_budget;
-    let inv_draw_distance = 1.0 / draw_distance.max(0.0001);
-    let sky_limit = input.world.get_above_floor_level() as f32;
-    let world_min = Vec3::ZERO;
-    let world_max = Vec3::splat(input.world.dim as f32 - DDA_EPSILON);
+    let limits = TraceLimits::new(
+        input.world,
+        input.draw_distance,
+        input.voxel_step_budget,
+        input.hidden_material,
+    );
+    let shading = Shading::new(input.world, &limits);
 
+    let extent = input.viewplane.extent_for(input.projection);
     let tl = input
         .viewplane
-        .top_left_corner_from_perspective_of(input.camera);
+        .top_left_corner_with_extent(input.camera, extent);
     let right = input.viewplane.get_right_from_perspective_of(input.camera);
     let down = input.viewplane.get_down_from_perspective_of(input.camera);
-    let pixel_size = input.viewplane.size / glam::Vec2::new(width as f32, height as f32);
+    let pixel_size = extent / Vec2::new(width as f32, height as f32);
     let right_step = right * pixel_size.x;
     let down_step = down * pixel_size.y;
-    let row_start = tl + right_step * 0.5 + down_step * 0.5;
+    let row_start = tl + right_step * (0.5 + input.jitter.x) + down_step * (0.5 + input.jitter.y);
 
     let cam = input.camera.pos;
-    let cam_y = cam.y;
-    let world_dim = input.world.dim as i32;
+    let orthographic = matches!(input.projection, Projection::Orthographic { .. });
+    let ortho_dir = input.camera.dir();
     let row_stride = (width as usize) * 4;
+    let sample_offsets = input.supersampling.offsets();
+
+    let render_row = |y: usize, row: &mut [u8], mut depth_row: Option<&mut [f32]>| {
+        let mut stats = RenderStats::default();
+        let mut target = row_start + down_step * y as f32;
+        for x in 0..width as usize {
+            let mut sum = [0u32; 3];
+            let mut nearest_depth = f32::INFINITY;
+            for offset in sample_offsets {
+                stats.rays_cast += 1;
+                let sample_target = target + right_step * offset.x + down_step * offset.y;
+                let (origin, ray) = if orthographic {
+                    (sample_target, ortho_dir)
+                } else {
+                    (cam, (sample_target - cam).normalize())
+                };
+                let steps_before = stats.voxel_steps;
+                let skips_before = stats.empty_chunk_skips;
+                let medium_ior = medium_ior_at(input.world, &limits, origin);
+                let (trace, rgb) = shade_ray(
+                    input.world,
+                    &limits,
+                    &shading,
+                    origin,
+                    ray,
+                    medium_ior,
+                    0,
+                    0.0,
+                    &mut stats,
+                );
+                let mut color = rgb_color(rgb);
+                if input.debug_view != DebugView::Shaded {
+                    color = debug_color(
+                        input.debug_view,
+                        &trace,
+                        origin,
+                        ray,
+                        stats.voxel_steps - steps_before,
+                        stats.empty_chunk_skips - skips_before,
+                        &shading,
+                        color,
+                    );
+                }
+                if trace.first_hit.is_some() {
+                    nearest_depth = nearest_depth.min(trace.hit_distance);
+                }
+                sum[0] += color.r as u32;
+                sum[1] += color.g as u32;
+                sum[2] += color.b as u32;
+            }
+            if let Some(depth_row) = depth_row.as_deref_mut() {
+                depth_row[x] = nearest_depth;
+            }
+            let samples = sample_offsets.len() as u32;
+            let color = Color::new(
+                ((sum[0] + samples / 2) / samples) as u8,
+                ((sum[1] + samples / 2) / samples) as u8,
+                ((sum[2] + samples / 2) / samples) as u8,
+                255,
+            );
+
+            let pixel_index = x * 4;
+            row[pixel_index] = color.r;
+            row[pixel_index + 1] = color.g;
+            row[pixel_index + 2] = color.b;
+            row[pixel_index + 3] = 255;
+            target += right_step;
+        }
+        stats
+    };
+
+    match depth {
+        Some(depth) => pixels
+            .par_chunks_exact_mut(row_stride)
+            .zip(depth.par_chunks_exact_mut(width as usize))
+            .enumerate()
+            .map(|(y, (row, depth_row))| render_row(y, row, Some(depth_row)))
+            .reduce(RenderStats::default, merge_stats),
+        None => pixels
+            .par_chunks_exact_mut(row_stride)
+            .enumerate()
+            .map(|(y, row)| render_row(y, row, None))
+            .reduce(RenderStats::default, merge_stats),
+    }
+}
+
+/// Inputs for 360° captures around a single point; no viewplane involved.
+pub struct PanoramaInput<'a> {
+    pub world: &'a World,
+    pub origin: Vec3,
+    /// Turns the equirectangular image; cubemap faces stay world-aligned.
+    pub orientation: Quat,
+    pub draw_distance: f32,
+    pub voxel_step_budget: f32,
+    pub hidden_material: Option<MaterialId>,
+}
+
+/// Cubemap faces named by the world axis they look along. The world is
+/// y-down, so `NegY` is the sky face and `PosY` the ground face.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum CubeFace {
+    PosX,
+    NegX,
+    PosY,
+    NegY,
+    PosZ,
+    NegZ,
+}
+
+impl CubeFace {
+    pub const ALL: [CubeFace; 6] = [
+        CubeFace::PosX,
+        CubeFace::NegX,
+        CubeFace::PosY,
+        CubeFace::NegY,
+        CubeFace::PosZ,
+        CubeFace::NegZ,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::PosX => "pos_x",
+            Self::NegX => "neg_x",
+            Self::PosY => "pos_y",
+            Self::NegY => "neg_y",
+            Self::PosZ => "pos_z",
+            Self::NegZ => "neg_z",
+        }
+    }
+
+    /// `(forward, right, down)` for the face image. Side faces are upright;
+    /// the sky face has `PosZ` below it and the ground face has `PosZ` above.
+    fn basis(self) -> (Vec3, Vec3, Vec3) {
+        match self {
+            Self::PosX => (Vec3::X, Vec3::NEG_Z, Vec3::Y),
+            Self::NegX => (Vec3::NEG_X, Vec3::Z, Vec3::Y),
+            Self::PosZ => (Vec3::Z, Vec3::X, Vec3::Y),
+            Self::NegZ => (Vec3::NEG_Z, Vec3::NEG_X, Vec3::Y),
+            Self::NegY => (Vec3::NEG_Y, Vec3::X, Vec3::Z),
+            Self::PosY => (Vec3::Y, Vec3::X, Vec3::NEG_Z),
+        }
+    }
+}
+
+/// Traces one ray per pixel from `input.origin`; `ray_for_pixel` maps pixel
+/// centers in `[0, 1]^2` to directions.
+fn draw_rays_from_point<F>(
+    input: &PanoramaInput<'_>,
+    pixels: &mut [u8],
+    width: i32,
+    height: i32,
+    ray_for_pixel: F,
+) -> RenderStats
+where
+    F: Fn(f32, f32) -> Vec3 + Sync,
+{
+    debug_assert_eq!(pixels.len(), (width as usize) * (height as usize) * 4);
+
+    let limits = TraceLimits::new(
+        input.world,
+        input.draw_distance,
+        input.voxel_step_budget,
+        input.hidden_material,
+    );
+    let shading = Shading::new(input.world, &limits);
+    let origin = input.origin;
+    let medium_ior = medium_ior_at(input.world, &limits, origin);
 
     pixels
-        .par_chunks_exact_mut(row_stride)
+        .par_chunks_exact_mut((width as usize) * 4)
         .enumerate()
         .map(|(y, row)| {
             let mut stats = RenderStats::default();
-            let mut target = row_start + down_step * y as f32;
+            let v = (y as f32 + 0.5) / height as f32;
             for x in 0..width as usize {
                 stats.rays_cast += 1;
-                let ray = (target - cam).normalize();
-                let mut hit_anything = false;
-                let mut hit_distance = draw_distance;
-
-                let mut accumulated_r = 0.0;
-                let mut accumulated_g = 0.0;
-                let mut accumulated_b = 0.0;
-                let mut transmittance = 1.0;
-
-                if let Some((mut t_enter, mut t_exit)) =
-                    ray_aabb_intersection(cam, ray, world_min, world_max)
-                {
-                    t_enter = t_enter.max(0.0);
-                    t_exit = t_exit.min(draw_distance);
-
-                    if t_enter <= t_exit {
-                        let mut t = t_enter;
-                        let mut dda = init_dda(cam, ray, t);
-                        let mut remaining_steps = num_ray_steps;
-                        let mut last_chunk_x = i32::MIN;
-                        let mut last_chunk_y = i32::MIN;
-                        let mut last_chunk_z = i32::MIN;
-                        let mut current_chunk_empty = false;
-                        let mut current_chunk_has_transparency = false;
-
-                        while t <= t_exit && remaining_steps > 0 {
-                            if dda.voxel_x < 0
-                                || dda.voxel_y < 0
-                                || dda.voxel_z < 0
-                                || dda.voxel_x >= world_dim
-                                || dda.voxel_y >= world_dim
-                                || dda.voxel_z >= world_dim
-                            {
-                                break;
-                            }
-
-                            let chunk_x = dda.voxel_x.div_euclid(CHUNK_SIZE as i32);
-                            let chunk_y = dda.voxel_y.div_euclid(CHUNK_SIZE as i32);
-                            let chunk_z = dda.voxel_z.div_euclid(CHUNK_SIZE as i32);
-                            if chunk_x != last_chunk_x
-                                || chunk_y != last_chunk_y
-                                || chunk_z != last_chunk_z
-                            {
-                                let Some(chunk_meta) =
-                                    input.world.chunk_meta(chunk_x, chunk_y, chunk_z)
-                                else {
-                                    break;
-                                };
-                                current_chunk_empty = chunk_meta.is_empty();
-                                current_chunk_has_transparency = chunk_meta.has_transparency;
-                                last_chunk_x = chunk_x;
-                                last_chunk_y = chunk_y;
-                                last_chunk_z = chunk_z;
-                            }
-
-                            if current_chunk_empty {
-                                stats.empty_chunk_skips += 1;
-                                t = chunk_exit_t(cam, ray, chunk_x, chunk_y, chunk_z, t)
-                                    + DDA_EPSILON;
-                                if t > t_exit {
-                                    break;
-                                }
-                                dda = init_dda(cam, ray, t);
-                                continue;
-                            }
-
-                            stats.voxel_steps += 1;
-                            remaining_steps -= 1;
-
-                            let material_id = input.world.get_voxel_material_unchecked_i32(
-                                dda.voxel_x,
-                                dda.voxel_y,
-                                dda.voxel_z,
-                            );
-                            if material_id != AIR_MATERIAL_ID {
-                                if !hit_anything {
-                                    hit_anything = true;
-                                    hit_distance = t.max(0.0);
-                                }
-
-                                let material = input.world.get_material(material_id);
-                                if !current_chunk_has_transparency {
-                                    let color = material.color;
-                                    accumulated_r = color.r as f32;
-                                    accumulated_g = color.g as f32;
-                                    accumulated_b = color.b as f32;
-                                    break;
-                                }
-
-                                accumulated_r += material.premul_r * transmittance;
-                                accumulated_g += material.premul_g * transmittance;
-                                accumulated_b += material.premul_b * transmittance;
-                                transmittance *= 1.0 - material.alpha;
-                                if transmittance <= 0.01 {
-                                    break;
-                                }
-                            }
-
-                            t = step_dda(&mut dda);
-                        }
-                    }
-                }
-
-                let mut color = Color::BLACK;
-                if hit_anything {
-                    stats.rays_hit += 1;
-                    let mut brightness = 1.0 - hit_distance * inv_draw_distance;
-                    brightness = brightness.max(0.0).min(1.0);
-                    let lit_scale = 0.25 + brightness * 0.75;
-                    color = Color::new(
-                        (accumulated_r * lit_scale).max(0.0).min(255.0) as u8,
-                        (accumulated_g * lit_scale).max(0.0).min(255.0) as u8,
-                        (accumulated_b * lit_scale).max(0.0).min(255.0) as u8,
-                        255,
-                    );
-                } else {
-                    let sky_probe_y = cam_y + ray.y * draw_distance;
-                    if sky_probe_y < sky_limit {
-                        const BLUE: Vec3 = Vec3::new(0.0, 0.0, 255.0);
-                        let blue = BLUE * 0.1;
-                        color = Color::new(blue.x as u8, blue.y as u8, blue.z as u8, 255);
-                    }
-                }
+                let u = (x as f32 + 0.5) / width as f32;
+                let ray = ray_for_pixel(u, v).normalize();
+                let (_, rgb) = shade_ray(
+                    input.world,
+                    &limits,
+                    &shading,
+                    origin,
+                    ray,
+                    medium_ior,
+                    0,
+                    0.0,
+                    &mut stats,
+                );
+                let color = rgb_color(rgb);
 
                 let pixel_index = x * 4;
                 row[pixel_index] = color.r;
                 row[pixel_index + 1] = color.g;
                 row[pixel_index + 2] = color.b;
                 row[pixel_index + 3] = 255;
-                target += right_step;
             }
             stats
         })
-        .reduce(RenderStats::default, |mut acc, row| {
-            acc.rays_cast += row.rays_cast;
-            acc.rays_hit += row.rays_hit;
-            acc.voxel_steps += row.voxel_steps;
-            acc.empty_chunk_skips += row.empty_chunk_skips;
-            acc
-        })
+        .reduce(RenderStats::default, merge_stats)
+}
+
+/// 360°x180° equirectangular image; the center column looks along
+/// `input.orientation * +Z` and the top row along `input.orientation * -Y`.
+/// Use a 2:1 `width:height`.
+pub fn draw_equirectangular(
+    input: &PanoramaInput<'_>,
+    pixels: &mut [u8],
+    width: i32,
+    height: i32,
+) -> RenderStats {
+    draw_rays_from_point(input, pixels, width, height, |u, v| {
+        let longitude = (u - 0.5) * std::f32::consts::TAU;
+        let latitude = (0.5 - v) * std::f32::consts::PI;
+        input.orientation
+            * Vec3::new(
+                latitude.cos() * longitude.sin(),
+                -latitude.sin(),
+                latitude.cos() * longitude.cos(),
+            )
+    })
+}
+
+/// One square 90° cubemap face of `size` x `size` pixels.
+pub fn draw_cube_face(
+    input: &PanoramaInput<'_>,
+    face: CubeFace,
+    pixels: &mut [u8],
+    size: i32,
+) -> RenderStats {
+    let (forward, right, down) = face.basis();
+    draw_rays_from_point(input, pixels, size, size, |u, v| {
+        forward + right * (u * 2.0 - 1.0) + down * (v * 2.0 - 1.0)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::DVec3;
+    use raylib::color::Color;
+
+    use super::*;
+    use crate::erosion::SplitMix64;
+
+    const TEST_WORLD_SIZE: usize = CHUNK_SIZE * 3;
+    // Hits this close in entry distance (or chords this short) are ties or
+    // grazes where either answer is acceptable.
+    const TIE_TOLERANCE: f64 = 5e-3;
+    // Pads voxels so f32 rays that touch only an edge still count as grazing.
+    const GRAZE_PADDING: f64 = 1e-4;
+
+    fn range(rng: &mut SplitMix64, min: f32, max: f32) -> f32 {
+        min + (max - min) * rng.next_f32()
+    }
+
+    fn below(rng: &mut SplitMix64, n: u32) -> u32 {
+        (rng.next_u64() % n as u64) as u32
+    }
+
+    /// Sparse random voxels, some empty chunks, and one-voxel walls on chunk
+    /// borders where an epsilon skip would hurt most.
+    fn random_world(rng: &mut SplitMix64) -> World {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let solid = world.intern_material(Color::new(200, 120, 80, 255));
+        let dim = TEST_WORLD_SIZE as i32;
+        let chunk = CHUNK_SIZE as i32;
+
+        for chunk_x in 0..3 {
+            for chunk_y in 0..3 {
+                for chunk_z in 0..3 {
+                    if below(rng, 3) == 0 {
+                        continue;
+                    }
+                    for _ in 0..below(rng, 24) {
+                        world.set_voxel_material_i32(
+                            chunk_x * chunk + below(rng, CHUNK_SIZE as u32) as i32,
+                            chunk_y * chunk + below(rng, CHUNK_SIZE as u32) as i32,
+                            chunk_z * chunk + below(rng, CHUNK_SIZE as u32) as i32,
+                            solid,
+                        );
+                    }
+                }
+            }
+        }
+
+        for _ in 0..below(rng, 4) {
+            let axis = below(rng, 3) as usize;
+            let plane = chunk * (1 + below(rng, 2) as i32) - below(rng, 2) as i32;
+            let a0 = below(rng, dim as u32) as i32;
+            let b0 = below(rng, dim as u32) as i32;
+            for a in a0..(a0 + 6).min(dim) {
+                for b in b0..(b0 + 6).min(dim) {
+                    let mut p = [0; 3];
+                    p[axis] = plane;
+                    p[(axis + 1) % 3] = a;
+                    p[(axis + 2) % 3] = b;
+                    world.set_voxel_material_i32(p[0], p[1], p[2], solid);
+                }
+            }
+        }
+        world
+    }
+
+    fn voxel_slab(origin: DVec3, dir: DVec3, voxel: IVec3) -> Option<(f64, f64)> {
+        let voxel = voxel.as_dvec3();
+        box_slab(
+            origin,
+            dir,
+            voxel - GRAZE_PADDING,
+            voxel + (1.0 + GRAZE_PADDING),
+        )
+    }
+
+    fn box_slab(origin: DVec3, dir: DVec3, min: DVec3, max: DVec3) -> Option<(f64, f64)> {
+        let mut t_min = f64::NEG_INFINITY;
+        let mut t_max = f64::INFINITY;
+        for axis in 0..3 {
+            let (lo, hi) = (min[axis], max[axis]);
+            if dir[axis] == 0.0 {
+                // Half-open like `floor`: a ray along a face belongs to the voxel on its
+                // positive side.
+                if origin[axis] < lo || origin[axis] >= hi {
+                    return None;
+                }
+                continue;
+            }
+            let mut t1 = (lo - origin[axis]) / dir[axis];
+            let mut t2 = (hi - origin[axis]) / dir[axis];
+            if t1 > t2 {
+                std::mem::swap(&mut t1, &mut t2);
+            }
+            t_min = t_min.max(t1);
+            t_max = t_max.min(t2);
+        }
+        (t_max >= t_min && t_max >= 0.0).then_some((t_min.max(0.0), t_max))
+    }
+
+    fn solid_voxels(world: &World) -> Vec<IVec3> {
+        let dim = world.dim as i32;
+        let mut solids = Vec::new();
+        for x in 0..dim {
+            for y in 0..dim {
+                for z in 0..dim {
+                    if world.get_voxel_material_unchecked_i32(x, y, z) != AIR_MATERIAL_ID {
+                        solids.push(IVec3::new(x, y, z));
+                    }
+                }
+            }
+        }
+        solids
+    }
+
+    /// Nearest of `solids` whose box the ray enters within `max_t`, by testing
+    /// every voxel on its own rather than walking the grid.
+    fn reference_first_hit(solids: &[IVec3], origin: Vec3, dir: Vec3, max_t: f32) -> Option<IVec3> {
+        let (origin, dir) = (origin.as_dvec3(), dir.as_dvec3());
+        solids
+            .iter()
+            .filter_map(|&voxel| {
+                let min = voxel.as_dvec3();
+                let (t_enter, _) = box_slab(origin, dir, min, min + 1.0)?;
+                (t_enter <= max_t as f64).then_some((t_enter, voxel))
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, voxel)| voxel)
+    }
+
+    fn chord(origin: Vec3, dir: Vec3, voxel: IVec3) -> Option<(f64, f64)> {
+        voxel_slab(origin.as_dvec3(), dir.as_dvec3(), voxel).map(|(t0, t1)| (t0, t1 - t0))
+    }
+
+    fn check_ray(world: &World, solids: &[IVec3], origin: Vec3, dir: Vec3, case: &str) {
+        let dir = dir.normalize();
+        let limits = TraceLimits::new(world, 200.0, MIN_STEP_BUDGET, None);
+        let mut stats = RenderStats::default();
+        let traced = trace_ray(world, &limits, origin, dir, &mut stats).first_hit;
+        let reference = reference_first_hit(solids, origin, dir, limits.draw_distance);
+        if traced == reference {
+            return;
+        }
+
+        let traced_chord = traced.and_then(|voxel| chord(origin, dir, voxel));
+        let reference_chord = reference.and_then(|voxel| chord(origin, dir, voxel));
+        let acceptable = match (traced_chord, reference_chord) {
+            (Some((_, length)), None) | (None, Some((_, length))) => length < TIE_TOLERANCE,
+            (Some((t_traced, traced_len)), Some((t_reference, reference_len))) => {
+                (t_traced - t_reference).abs() < TIE_TOLERANCE
+                    || (t_traced < t_reference && traced_len < TIE_TOLERANCE)
+                    || (t_reference < t_traced && reference_len < TIE_TOLERANCE)
+            }
+            (None, None) => false,
+        };
+        assert!(
+            acceptable,
+            "{case}: origin {origin:?} dir {dir:?}: traversal hit {traced:?} {traced_chord:?}, \
+             reference hit {reference:?} {reference_chord:?}"
+        );
+    }
+
+    fn random_unit(rng: &mut SplitMix64) -> Vec3 {
+        loop {
+            let v = Vec3::new(
+                range(rng, -1.0, 1.0),
+                range(rng, -1.0, 1.0),
+                range(rng, -1.0, 1.0),
+            );
+            let len_sq = v.length_squared();
+            if len_sq > 1e-3 && len_sq <= 1.0 {
+                return v / len_sq.sqrt();
+            }
+        }
+    }
+
+    fn random_origin(rng: &mut SplitMix64) -> Vec3 {
+        // Mostly inside the world, sometimes outside so the AABB entry runs too.
+        let span = TEST_WORLD_SIZE as f32;
+        if below(rng, 4) == 0 {
+            Vec3::new(
+                range(rng, -span * 0.5, span * 1.5),
+                range(rng, -span * 0.5, span * 1.5),
+                range(rng, -span * 0.5, span * 1.5),
+            )
+        } else {
+            Vec3::new(
+                range(rng, 0.0, span),
+                range(rng, 0.0, span),
+                range(rng, 0.0, span),
+            )
+        }
+    }
+
+    #[test]
+    fn random_rays_match_reference() {
+        let rng = &mut SplitMix64(0x5EED_0031);
+        for _ in 0..12 {
+            let world = random_world(rng);
+            let solids = solid_voxels(&world);
+            for _ in 0..150 {
+                let origin = random_origin(rng);
+                let dir = random_unit(rng);
+                check_ray(&world, &solids, origin, dir, "random");
+            }
+        }
+    }
+
+    #[test]
+    fn axis_aligned_rays_match_reference() {
+        let rng = &mut SplitMix64(0xA815_0031);
+        let axes = [
+            Vec3::X,
+            Vec3::NEG_X,
+            Vec3::Y,
+            Vec3::NEG_Y,
+            Vec3::Z,
+            Vec3::NEG_Z,
+        ];
+        for _ in 0..8 {
+            let world = random_world(rng);
+            let solids = solid_voxels(&world);
+            for _ in 0..60 {
+                let mut origin = random_origin(rng);
+                // Half the rays run exactly along voxel faces or chunk planes.
+                if below(rng, 2) == 0 {
+                    origin = origin.floor();
+                }
+                if below(rng, 3) == 0 {
+                    origin.x = (CHUNK_SIZE * (1 + below(rng, 2) as usize)) as f32;
+                }
+                for dir in axes {
+                    check_ray(&world, &solids, origin, dir, "axis-aligned");
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn rays_grazing_chunk_corners_match_reference() {
+        let rng = &mut SplitMix64(0xC0DE_0031);
+        for _ in 0..8 {
+            let world = random_world(rng);
+            let solids = solid_voxels(&world);
+            for _ in 0..120 {
+                let corner = Vec3::new(
+                    (CHUNK_SIZE * (1 + below(rng, 2) as usize)) as f32,
+                    (CHUNK_SIZE * (1 + below(rng, 2) as usize)) as f32,
+                    (CHUNK_SIZE * (1 + below(rng, 2) as usize)) as f32,
+                );
+                let nudge = Vec3::new(
+                    range(rng, -1e-3, 1e-3),
+                    range(rng, -1e-3, 1e-3),
+                    range(rng, -1e-3, 1e-3),
+                ) * below(rng, 2) as f32;
+                let origin = random_origin(rng);
+                let dir = corner + nudge - origin;
+                if dir.length_squared() < 1e-6 {
+                    continue;
+                }
+                check_ray(&world, &solids, origin, dir, "chunk corner");
+            }
+        }
+    }
+
+    #[test]
+    fn empty_chunks_are_skipped() {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let solid = world.intern_material(Color::new(255, 255, 255, 255));
+        world.set_voxel_material_i32(40, 8, 8, solid);
+        let limits = TraceLimits::new(&world, 200.0, MIN_STEP_BUDGET, None);
+        let mut stats = RenderStats::default();
+        let traced = trace_ray(
+            &world,
+            &limits,
+            Vec3::new(0.5, 8.5, 8.5),
+            Vec3::X,
+            &mut stats,
+        );
+        assert_eq!(traced.first_hit, Some(IVec3::new(40, 8, 8)));
+        assert!(stats.empty_chunk_skips >= 2);
+        assert!(stats.voxel_steps <= CHUNK_SIZE as u64 + 1);
+    }
+
+    #[test]
+    fn transparent_voxels_absorb_by_path_length() {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let water = world.intern_material(Color::new(50, 120, 190, 130));
+        for x in 40..44 {
+            world.set_voxel_material_i32(x, 8, 8, water);
+        }
+        let material = world.get_material(water);
+        let limits = TraceLimits::new(&world, 200.0, MIN_STEP_BUDGET, None);
+        for (start_x, length) in [(0.5, 4.0), (41.5, 2.5)] {
+            let mut stats = RenderStats::default();
+            let traced = trace_ray(
+                &world,
+                &limits,
+                Vec3::new(start_x, 8.5, 8.5),
+                Vec3::X,
+                &mut stats,
+            );
+            let expected = material.scatter * (Vec3::ONE - (-material.absorption * length).exp());
+            assert!(
+                (traced.accumulated - expected).abs().max_element() < 0.5,
+                "{:?} vs {:?}",
+                traced.accumulated,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn zero_alpha_voxels_stay_opaque() {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let block = world.intern_material(Color::new(200, 30, 40, 0));
+        world.set_voxel_material_i32(40, 8, 8, block);
+        assert!(!world.get_material(block).is_transparent);
+        let limits = TraceLimits::new(&world, 200.0, MIN_STEP_BUDGET, None);
+        let mut stats = RenderStats::default();
+        let traced = trace_ray(
+            &world,
+            &limits,
+            Vec3::new(0.5, 8.5, 8.5),
+            Vec3::X,
+            &mut stats,
+        );
+        assert_eq!(traced.accumulated, Vec3::new(200.0, 30.0, 40.0));
+    }
+
+    #[test]
+    fn glass_spawns_fresnel_weighted_secondary_rays() {
+        // Head-on into glass only ~4% is reflected; at grazing angles nearly all.
+        let head_on = fresnel_reflectance(AIR_IOR, 1.5, 1.0, 1.0);
+        assert!((head_on - 0.04).abs() < 1e-3, "{}", head_on);
+        assert!(fresnel_reflectance(AIR_IOR, 1.5, 0.01, 0.7) > 0.9);
+
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let glass = world.intern_material(Color::new(180, 220, 255, 95));
+        world.set_material_ior(glass, 1.5);
+        for x in 40..44 {
+            world.set_voxel_material_i32(x, 8, 8, glass);
+        }
+        let limits = TraceLimits::new(&world, 200.0, MIN_STEP_BUDGET, None);
+        let shading = Shading::new(&world, &limits);
+        let mut stats = RenderStats::default();
+        shade_ray(
+            &world,
+            &limits,
+            &shading,
+            Vec3::new(0.5, 8.5, 8.5),
+            Vec3::X,
+            AIR_IOR,
+            0,
+            0.0,
+            &mut stats,
+        );
+        // Entry splits the ray; only the refracted half meets the exit and
+        // splits again. Its reflection back into the glass does not.
+        assert_eq!(stats.secondary_rays, 2 + 2);
+        assert!(stats.secondary_voxel_steps > 0);
+    }
+
+    #[test]
+    fn debug_normals_face_the_ray() {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        let solid = world.intern_material(Color::new(255, 255, 255, 255));
+        world.set_voxel_material_i32(40, 8, 8, solid);
+        world.set_voxel_material_i32(8, 40, 8, solid);
+        let limits = TraceLimits::new(&world, 200.0, MIN_STEP_BUDGET, None);
+        for (origin, ray, normal) in [
+            (Vec3::new(0.5, 8.5, 8.5), Vec3::X, Vec3::NEG_X),
+            (Vec3::new(8.5, 0.5, 8.5), Vec3::Y, Vec3::NEG_Y),
+            (Vec3::new(60.5, 8.5, 8.5), Vec3::NEG_X, Vec3::X),
+        ] {
+            let mut stats = RenderStats::default();
+            let traced = trace_ray(&world, &limits, origin, ray, &mut stats);
+            let voxel = traced.first_hit.expect("ray should hit");
+            assert_eq!(hit_normal(voxel, origin, ray, traced.hit_distance), normal);
+        }
+    }
 }
@@ -1,70 +1,138 @@
-use glam::{UVec2, Vec2, Vec3};
+use glam::{Quat, UVec2, Vec2, Vec3};
 use raylib::prelude::*;
+use rayon::prelude::*;
 use std::time::Instant;
 
-use crate::raymarch::{self, RaymarchInput};
-use crate::state::{ResolutionScale, State};
+use crate::raymarch::{self, DebugView, Supersampling};
+use crate::state::{ResolutionScale, State, Upscaler};
+use crate::temporal_upscale::{TemporalUpscaler, ViewFrame};
 use crate::ui_overlay;
+use crate::viewplane::Projection;
 
 #[derive(Copy, Clone, PartialEq)]
 struct RenderSignature {
     world_revision: u64,
+    edit_revision: u64,
     camera_pos: Vec3,
-    camera_dir: Vec3,
+    camera_orientation: Quat,
     viewplane_size: Vec2,
     viewplane_distance: f32,
+    projection: Projection,
     draw_distance: f32,
     voxel_step_budget: f32,
+    xray: bool,
+    debug_view: DebugView,
+    supersampling: Supersampling,
+    upscaler: Upscaler,
+    /// Jitter position of the temporal upscaler; 0 otherwise.
+    jitter_index: u32,
     render_width: u32,
     render_height: u32,
 }
 
+impl RenderSignature {
+    /// Whether everything but the camera and jitter matches, so accumulated
+    /// temporal history still shades the same way. A user edit changes what
+    /// history shows even where depths still match, so it counts too; streamed
+    /// terrain only fills in columns and keeps the history.
+    fn same_shading(&self, other: &Self) -> bool {
+        self.edit_revision == other.edit_revision
+            && self.draw_distance == other.draw_distance
+            && self.xray == other.xray
+            && self.debug_view == other.debug_view
+            && self.supersampling == other.supersampling
+            && self.upscaler == other.upscaler
+    }
+}
+
+/// Whether a frame with `signature` must be rendered rather than reused;
+/// drops temporal history that no longer shades like `signature`.
+fn needs_render(
+    last: Option<RenderSignature>,
+    signature: &RenderSignature,
+    temporal: &mut TemporalUpscaler,
+) -> bool {
+    let Some(last) = last else {
+        return true;
+    };
+    if last == *signature {
+        return false;
+    }
+    if !last.same_shading(signature) {
+        temporal.reset();
+    }
+    true
+}
+
 pub struct Renderer {
     pub dims: UVec2,
     texture: Texture2D,
     ray_buffer: Vec<u8>,
     upload_buffer: Vec<u8>,
+    /// Hit distance per ray, for the temporal upscaler.
+    depth_buffer: Vec<f32>,
+    temporal: TemporalUpscaler,
     last_signature: Option<RenderSignature>,
 }
 
 impl Renderer {
     pub fn new(rl: &mut RaylibHandle, thread: &RaylibThread, dims: UVec2) -> Self {
-        let image = Image::gen_image_color(dims.x as i32, dims.y as i32, Color::BLACK);
-        let texture = rl
-            .load_texture_from_image(thread, &image)
-            .unwrap_or_else(|e| {
-                println!("Error creating render texture: {}", e);
-                std::process::exit(1);
-            });
-
         Self {
             dims,
-            texture,
+            texture: create_texture(rl, thread, dims),
             ray_buffer: vec![0; (dims.x * dims.y * 4) as usize],
             upload_buffer: vec![0; (dims.x * dims.y * 4) as usize],
+            depth_buffer: Vec::new(),
+            temporal: TemporalUpscaler::default(),
             last_signature: None,
         }
     }
 
+    /// Reallocates the texture and buffers for a new window size; the next
+    /// `draw_scene` renders from scratch.
+    pub fn resize(&mut self, rl: &mut RaylibHandle, thread: &RaylibThread, dims: UVec2) {
+        if dims == self.dims {
+            return;
+        }
+        self.dims = dims;
+        self.texture = create_texture(rl, thread, dims);
+        self.upload_buffer = vec![0; (dims.x * dims.y * 4) as usize];
+        self.temporal.reset();
+        self.last_signature = None;
+    }
+
     pub fn draw_scene(&mut self, state: &mut State) {
-        let render_width = scaled_dimension(self.dims.x, state.resolution_scale);
-        let render_height = scaled_dimension(self.dims.y, state.resolution_scale);
+        let temporal = state.upscaler == Upscaler::Temporal;
+        let scale = state.render_scale_factor();
+        let render_width = scaled_dimension(self.dims.x, scale);
+        let render_height = scaled_dimension(self.dims.y, scale);
         state.render_width = render_width;
         state.render_height = render_height;
 
         let signature = RenderSignature {
             world_revision: state.world.revision(),
+            edit_revision: state.world.edit_revision,
             camera_pos: state.camera.pos,
-            camera_dir: state.camera.dir,
+            camera_orientation: state.camera.orientation,
             viewplane_size: state.viewplane.size,
             viewplane_distance: state.camera.viewplane_distance,
+            projection: state.projection,
             draw_distance: state.draw_distance,
             voxel_step_budget: state.voxel_step_budget,
+            xray: state.xray,
+            debug_view: state.debug_view,
+            supersampling: state.supersampling,
+            upscaler: state.upscaler,
+            jitter_index: if temporal {
+                self.temporal.jitter_index()
+            } else {
+                0
+            },
             render_width,
             render_height,
         };
 
-        if self.last_signature == Some(signature) {
+        if !needs_render(self.last_signature, &signature, &mut self.temporal) {
             state.last_frame_timings.reused_render = true;
             state.last_frame_timings.raymarch_ms = 0.0;
             state.last_frame_timings.upload_ms = 0.0;
@@ -80,34 +148,77 @@ impl Renderer {
         }
 
         let raymarch_start = Instant::now();
-        state.last_render_stats = raymarch::draw_voxels(
-            RaymarchInput {
-                world: &state.world,
-                camera: &state.camera,
-                viewplane: &state.viewplane,
-                draw_distance: state.draw_distance,
-                voxel_step_budget: state.voxel_step_budget,
-            },
-            &mut self.ray_buffer,
-            render_width as i32,
-            render_height as i32,
-        );
+        state.last_render_stats = if temporal || state.upscaler == Upscaler::EdgeAware {
+            self.depth_buffer
+                .resize(render_width as usize * render_height as usize, 0.0);
+            let mut input = state.raymarch_input();
+            if temporal {
+                input.jitter = self.temporal.jitter();
+            }
+            raymarch::draw_voxels_with_depth(
+                input,
+                &mut self.ray_buffer,
+                &mut self.depth_buffer,
+                render_width as i32,
+                render_height as i32,
+            )
+        } else {
+            raymarch::draw_voxels(
+                state.raymarch_input(),
+                &mut self.ray_buffer,
+                render_width as i32,
+                render_height as i32,
+            )
+        };
         state.last_frame_timings.raymarch_ms = raymarch_start.elapsed().as_secs_f32() * 1000.0;
+        if state.resolution_scale == ResolutionScale::Auto {
+            state
+                .adaptive_resolution
+                .observe(state.last_frame_timings.raymarch_ms);
+        }
 
         let upload_start = Instant::now();
-        if render_width == self.dims.x && render_height == self.dims.y {
-            self.texture.update_texture(&self.ray_buffer);
-        } else {
-            upscale_nearest_rgba(
-                &self.ray_buffer,
-                &mut self.upload_buffer,
-                render_width as usize,
-                render_height as usize,
-                self.dims.x as usize,
-                self.dims.y as usize,
-            );
-            self.texture.update_texture(&self.upload_buffer);
-        }
+        let native = render_width == self.dims.x && render_height == self.dims.y;
+        let upload: &[u8] = match state.upscaler {
+            Upscaler::Temporal => {
+                let view =
+                    ViewFrame::new(&state.camera, &state.viewplane, state.projection, self.dims);
+                self.temporal.resolve(
+                    &self.ray_buffer,
+                    &self.depth_buffer,
+                    UVec2::new(render_width, render_height),
+                    view,
+                    self.dims,
+                    &mut self.upload_buffer,
+                );
+                &self.upload_buffer
+            }
+            _ if native => &self.ray_buffer,
+            Upscaler::Nearest => {
+                upscale_nearest_rgba(
+                    &self.ray_buffer,
+                    &mut self.upload_buffer,
+                    render_width as usize,
+                    render_height as usize,
+                    self.dims.x as usize,
+                    self.dims.y as usize,
+                );
+                &self.upload_buffer
+            }
+            Upscaler::Bilinear | Upscaler::EdgeAware => {
+                upscale_bilinear_rgba(
+                    &self.ray_buffer,
+                    (state.upscaler == Upscaler::EdgeAware).then_some(&self.depth_buffer[..]),
+                    &mut self.upload_buffer,
+                    render_width as usize,
+                    render_height as usize,
+                    self.dims.x as usize,
+                    self.dims.y as usize,
+                );
+                &self.upload_buffer
+            }
+        };
+        self.texture.update_texture(upload);
         state.last_frame_timings.upload_ms = upload_start.elapsed().as_secs_f32() * 1000.0;
 
         self.last_signature = Some(signature);
@@ -145,20 +256,22 @@ impl Renderer {
     }
 }
 
+fn create_texture(rl: &mut RaylibHandle, thread: &RaylibThread, dims: UVec2) -> Texture2D {
+    let image = Image::gen_image_color(dims.x as i32, dims.y as i32, Color::BLACK);
+    rl.load_texture_from_image(thread, &image)
+        .unwrap_or_else(|e| {
+            println!("Error creating render texture: {}", e);
+            std::process::exit(1);
+        })
+}
+
 pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
     ui_overlay::draw_ui_overlay(state, d);
 }
 
 #[inline]
-fn scaled_dimension(size: u32, scale: ResolutionScale) -> u32 {
-    match scale {
-        ResolutionScale::X1 => size,
-        ResolutionScale::XHalf => (size / 2).max(1),
-        ResolutionScale::XQuarter => (size / 4).max(1),
-        ResolutionScale::XEighth => (size / 8).max(1),
-        ResolutionScale::XSixteenth => (size / 16).max(1),
-        ResolutionScale::XThirtySecond => (size / 32).max(1),
-    }
+pub(crate) fn scaled_dimension(size: u32, factor: f32) -> u32 {
+    ((size as f32 * factor) as u32).clamp(1, size.max(1))
 }
 
 fn upscale_nearest_rgba(
@@ -185,3 +298,214 @@ fn upscale_nearest_rgba(
         }
     }
 }
+
+/// Hit distances this far apart (as a fraction of the nearer one, plus
+/// `EDGE_DEPTH_TOLERANCE_VOXELS`) count as different surfaces for the
+/// edge-aware upscale.
+const EDGE_DEPTH_TOLERANCE: f32 = 0.05;
+const EDGE_DEPTH_TOLERANCE_VOXELS: f32 = 0.5;
+
+fn same_surface(a: f32, b: f32) -> bool {
+    if a.is_infinite() || b.is_infinite() {
+        return a == b;
+    }
+    (a - b).abs() <= a.min(b) * EDGE_DEPTH_TOLERANCE + EDGE_DEPTH_TOLERANCE_VOXELS
+}
+
+/// Bilinear upscale. With `depth`, taps on another surface than the nearest
+/// tap are left out, so silhouettes keep a hard edge instead of a blend.
+fn upscale_bilinear_rgba(
+    src: &[u8],
+    depth: Option<&[f32]>,
+    dst: &mut [u8],
+    src_w: usize,
+    src_h: usize,
+    dst_w: usize,
+    dst_h: usize,
+) {
+    debug_assert_eq!(src.len(), src_w * src_h * 4);
+    debug_assert_eq!(dst.len(), dst_w * dst_h * 4);
+
+    // Source position of a destination pixel center, split into the upper-left
+    // tap and the fraction toward the next one.
+    let source_position = |dst_pos: usize, dst_len: usize, src_len: usize| {
+        let pos = ((dst_pos as f32 + 0.5) * src_len as f32 / dst_len as f32 - 0.5)
+            .clamp(0.0, (src_len - 1) as f32);
+        let lower = pos as usize;
+        (lower, (lower + 1).min(src_len - 1), pos - lower as f32)
+    };
+
+    dst.par_chunks_exact_mut(dst_w * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            let (y0, y1, fy) = source_position(y, dst_h, src_h);
+            for x in 0..dst_w {
+                let (x0, x1, fx) = source_position(x, dst_w, src_w);
+                let taps = [
+                    (y0 * src_w + x0, (1.0 - fx) * (1.0 - fy)),
+                    (y0 * src_w + x1, fx * (1.0 - fy)),
+                    (y1 * src_w + x0, (1.0 - fx) * fy),
+                    (y1 * src_w + x1, fx * fy),
+                ];
+                let nearest = taps
+                    .iter()
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map_or(taps[0].0, |tap| tap.0);
+
+                let mut sum = [0.0f32; 3];
+                let mut total = 0.0;
+                for (index, weight) in taps {
+                    if let Some(depth) = depth {
+                        if !same_surface(depth[index], depth[nearest]) {
+                            continue;
+                        }
+                    }
+                    for channel in 0..3 {
+                        sum[channel] += src[index * 4 + channel] as f32 * weight;
+                    }
+                    total += weight;
+                }
+
+                let dst_idx = x * 4;
+                for channel in 0..3 {
+                    row[dst_idx + channel] = if total > 0.0 {
+                        (sum[channel] / total).round() as u8
+                    } else {
+                        src[nearest * 4 + channel]
+                    };
+                }
+                row[dst_idx + 3] = 255;
+            }
+        });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use glam::IVec3;
+
+    use crate::camera::Camera;
+    use crate::temporal_upscale::JITTER_SEQUENCE_LEN;
+    use crate::viewplane::Viewplane;
+    use crate::world::World;
+
+    #[test]
+    fn edge_aware_upscale_keeps_silhouettes_hard() {
+        // A near white voxel next to far black sky, upscaled 1x2 -> 1x8.
+        let src = [255, 255, 255, 255, 0, 0, 0, 255];
+        let depth = [4.0, f32::INFINITY];
+        let mut blended = vec![0u8; 8 * 4];
+        let mut edge = vec![0u8; 8 * 4];
+        upscale_bilinear_rgba(&src, None, &mut blended, 2, 1, 8, 1);
+        upscale_bilinear_rgba(&src, Some(&depth), &mut edge, 2, 1, 8, 1);
+
+        assert!(blended
+            .chunks_exact(4)
+            .any(|texel| texel[0] != 0 && texel[0] != 255));
+        assert!(edge
+            .chunks_exact(4)
+            .all(|texel| texel[0] == 0 || texel[0] == 255));
+        assert_eq!(edge[0], 255);
+        assert_eq!(edge[7 * 4], 0);
+    }
+
+    /// A camera that never moves, fed solid-color frames through `needs_render`
+    /// and the temporal upscaler the way `draw_scene` feeds them.
+    struct StillView {
+        temporal: TemporalUpscaler,
+        last: Option<RenderSignature>,
+        out: Vec<u8>,
+    }
+
+    impl StillView {
+        const DIMS: UVec2 = UVec2::new(8, 8);
+        const RENDER_DIMS: UVec2 = UVec2::new(4, 4);
+
+        fn new() -> Self {
+            Self {
+                temporal: TemporalUpscaler::default(),
+                last: None,
+                out: vec![0; (Self::DIMS.x * Self::DIMS.y * 4) as usize],
+            }
+        }
+
+        fn run(&mut self, world: &World, color: [u8; 4], frames: u32) {
+            let viewplane = Viewplane::new(Vec2::new(3.0, 3.0), 1.0);
+            let camera = Camera::new(Vec3::ZERO, Vec3::Z, 3.0);
+            let view = ViewFrame::new(&camera, &viewplane, Projection::Perspective, Self::DIMS);
+            let texels = (Self::RENDER_DIMS.x * Self::RENDER_DIMS.y) as usize;
+            let pixels = color.repeat(texels);
+            let depth = vec![10.0f32; texels];
+            for _ in 0..frames {
+                let signature = RenderSignature {
+                    world_revision: world.revision(),
+                    edit_revision: world.edit_revision,
+                    camera_pos: camera.pos,
+                    camera_orientation: camera.orientation,
+                    viewplane_size: viewplane.size,
+                    viewplane_distance: camera.viewplane_distance,
+                    projection: Projection::Perspective,
+                    draw_distance: 128.0,
+                    voxel_step_budget: 1.0,
+                    xray: false,
+                    debug_view: DebugView::Shaded,
+                    supersampling: Supersampling::Off,
+                    upscaler: Upscaler::Temporal,
+                    jitter_index: self.temporal.jitter_index(),
+                    render_width: Self::RENDER_DIMS.x,
+                    render_height: Self::RENDER_DIMS.y,
+                };
+                if needs_render(self.last, &signature, &mut self.temporal) {
+                    self.temporal.resolve(
+                        &pixels,
+                        &depth,
+                        Self::RENDER_DIMS,
+                        view,
+                        Self::DIMS,
+                        &mut self.out,
+                    );
+                }
+                self.last = Some(signature);
+            }
+        }
+
+        fn all(&self, color: [u8; 4]) -> bool {
+            self.out.chunks_exact(4).all(|texel| texel == color)
+        }
+    }
+
+    const RED: [u8; 4] = [200, 40, 40, 255];
+    const BLUE: [u8; 4] = [40, 40, 200, 255];
+
+    #[test]
+    fn world_edit_under_a_still_temporal_view_converges_to_the_new_color() {
+        let mut world = World::new(64);
+        let mut view = StillView::new();
+        view.run(&world, RED, JITTER_SEQUENCE_LEN * 3);
+        assert!(view.all(RED));
+        // A `fill` recolors the voxel in place: same depth, new revision.
+        let stone = world.material_by_name("stone").unwrap();
+        world.fill_box(IVec3::splat(40), IVec3::splat(40), stone);
+        view.run(&world, BLUE, JITTER_SEQUENCE_LEN * 3);
+        assert!(view.all(BLUE), "{:?}", &view.out[..4]);
+    }
+
+    #[test]
+    fn streamed_terrain_keeps_temporal_history() {
+        let mut world = World::new(64);
+        let mut view = StillView::new();
+        view.run(&world, RED, JITTER_SEQUENCE_LEN * 3);
+        let revision = world.revision();
+        // A column off screen: the view itself still traces the same colors,
+        // so one differing sample must blend in rather than replace history.
+        let column = world.new_terrain_heightfield().build_surface_heights(3, 3);
+        world.apply_terrain_column_heights(&column);
+        assert_ne!(world.revision(), revision);
+        view.run(&world, BLUE, 1);
+        assert!(
+            view.out.chunks_exact(4).all(|texel| texel[0] > texel[2]),
+            "{:?}",
+            &view.out[..4]
+        );
+    }
+}
@@ -0,0 +1,404 @@
+//! Startup settings: built-in defaults, overridden by `settings.txt`, then by
+//! command-line flags. `settings.txt` uses `key = value` lines and the flags
+//! the same keys as `--key value` or `--key=value`; `--fullscreen` and
+//! `--windowed` need no value. The values in effect at exit are written back.
+
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use glam::UVec2;
+
+use crate::adaptive_resolution::{
+    DEFAULT_RAYMARCH_BUDGET_MS, MAX_RAYMARCH_BUDGET_MS, MIN_RAYMARCH_BUDGET_MS,
+};
+use crate::raymarch::{Supersampling, MAX_STEP_BUDGET, MIN_STEP_BUDGET};
+use crate::state::{
+    ResolutionScale, State, Upscaler, DEFAULT_DRAW_DISTANCE, DEFAULT_FOV_Y_DEG,
+    MAX_CHUNK_GEN_BUDGET, MAX_DRAW_DISTANCE, MAX_FOV_Y_DEG, MIN_DRAW_DISTANCE, MIN_FOV_Y_DEG,
+};
+use crate::terrain_worker::TERRAIN_SEED;
+use crate::world::CHUNK_SIZE;
+use crate::{DIMS, VOXEL_STEP_BUDGET, WORLD_SIZE};
+
+pub const SETTINGS_FILE: &str = "settings.txt";
+
+pub const MIN_WINDOW_DIMS: UVec2 = UVec2::new(320, 180);
+const MAX_WINDOW_DIMS: UVec2 = UVec2::new(7680, 4320);
+const MIN_WORLD_SIZE: usize = 64;
+const MAX_WORLD_SIZE: usize = 1024;
+const MIN_MOUSE_SENSITIVITY: f32 = 0.0001;
+const MAX_MOUSE_SENSITIVITY: f32 = 0.02;
+const MIN_TARGET_FPS: u32 = 10;
+const MAX_TARGET_FPS: u32 = 1000;
+
+/// Key and description of every setting, in file order.
+const SETTING_TABLE: &[(&str, &str)] = &[
+    ("window_width", "window width in pixels"),
+    ("window_height", "window height in pixels"),
+    ("fullscreen", "true or false"),
+    ("world_size", "voxels per side, a multiple of 16"),
+    ("seed", "terrain seed"),
+    (
+        "erosion",
+        "true or false; droplet erosion of the terrain (slower generation)",
+    ),
+    ("render_scale", "1x, 1/2x, 1/4x, 1/8x, 1/16x, 1/32x or auto"),
+    (
+        "render_budget_ms",
+        "raymarch milliseconds the auto render scale stays under",
+    ),
+    ("upscale", "nearest, bilinear, edge or temporal"),
+    (
+        "supersampling",
+        "rays per pixel: off, 2x2 or rgss (4x rotated grid)",
+    ),
+    ("draw_distance", "in voxels"),
+    ("step_budget", "voxel step budget per ray"),
+    ("fov", "vertical field of view in degrees"),
+    ("mouse_sensitivity", "radians per pixel of mouse movement"),
+    ("chunk_gen_budget", "terrain chunks generated per step"),
+    ("target_fps", "frame rate cap"),
+];
+
+/// Keys `apply_runtime` can change while the viewer runs.
+pub const RUNTIME_SETTINGS: &[&str] = &[
+    "render_scale",
+    "render_budget_ms",
+    "upscale",
+    "supersampling",
+    "draw_distance",
+    "step_budget",
+    "fov",
+    "mouse_sensitivity",
+    "chunk_gen_budget",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Settings {
+    pub window_dims: UVec2,
+    pub fullscreen: bool,
+    pub world_size: usize,
+    pub seed: u32,
+    pub erosion: bool,
+    pub resolution_scale: ResolutionScale,
+    pub raymarch_budget_ms: f32,
+    pub upscaler: Upscaler,
+    pub supersampling: Supersampling,
+    pub draw_distance: f32,
+    pub voxel_step_budget: f32,
+    pub fov_y_deg: f32,
+    pub mouse_sensitivity: f32,
+    pub chunk_gen_budget_per_step: usize,
+    pub target_fps: u32,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            window_dims: DIMS,
+            fullscreen: false,
+            world_size: WORLD_SIZE,
+            seed: TERRAIN_SEED,
+            erosion: false,
+            resolution_scale: ResolutionScale::XQuarter,
+            raymarch_budget_ms: DEFAULT_RAYMARCH_BUDGET_MS,
+            upscaler: Upscaler::Nearest,
+            supersampling: Supersampling::Off,
+            draw_distance: DEFAULT_DRAW_DISTANCE,
+            voxel_step_budget: VOXEL_STEP_BUDGET,
+            fov_y_deg: DEFAULT_FOV_Y_DEG,
+            mouse_sensitivity: 0.0015,
+            chunk_gen_budget_per_step: 2,
+            target_fps: 144,
+        }
+    }
+}
+
+fn parse_in_range<T>(value: &str, min: T, max: T) -> Result<T, String>
+where
+    T: FromStr + PartialOrd + Display + Copy,
+{
+    let parsed = value
+        .parse::<T>()
+        .map_err(|_| format!("`{}` is not a number", value))?;
+    // Written so NaN, which fails every comparison, is out of range too.
+    if !(parsed >= min && parsed <= max) {
+        return Err(format!("{} is outside {}..={}", parsed, min, max));
+    }
+    Ok(parsed)
+}
+
+impl Settings {
+    /// Defaults overridden by `path` if it exists, plus every problem found.
+    /// Lines with a problem keep the default value.
+    pub fn load(path: &Path) -> (Self, Vec<String>) {
+        match fs::read_to_string(path) {
+            Ok(text) => Self::parse(&text),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => (Self::default(), Vec::new()),
+            Err(e) => (
+                Self::default(),
+                vec![format!("cannot read {}: {}", path.display(), e)],
+            ),
+        }
+    }
+
+    fn parse(text: &str) -> (Self, Vec<String>) {
+        let mut settings = Self::default();
+        let mut problems = Vec::new();
+        for (line_number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line_number = line_number + 1;
+            let Some((key, value)) = line.split_once('=') else {
+                problems.push(format!("line {}: expected `key = value`", line_number));
+                continue;
+            };
+            if let Err(e) = settings.set(key.trim(), value.trim()) {
+                problems.push(format!("line {}: {}", line_number, e));
+            }
+        }
+        (settings, problems)
+    }
+
+    /// Applies command-line flags on top of the loaded values.
+    pub fn apply_args(&mut self, args: &[String]) -> Result<(), String> {
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let Some(flag) = arg.strip_prefix("--") else {
+                return Err(format!("unexpected argument `{}`", arg));
+            };
+            let (key, inline_value) = match flag.split_once('=') {
+                Some((key, value)) => (key, Some(value.to_string())),
+                None => (flag, None),
+            };
+            let key = key.replace('-', "_");
+            let value = match (key.as_str(), inline_value) {
+                (_, Some(value)) => value,
+                ("fullscreen", None) => "true".to_string(),
+                ("windowed", None) => {
+                    self.fullscreen = false;
+                    continue;
+                }
+                (_, None) => args
+                    .next()
+                    .cloned()
+                    .ok_or_else(|| format!("--{} needs a value", flag))?,
+            };
+            self.set(&key, &value)
+                .map_err(|e| format!("--{}: {}", key, e))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn set(&mut self, key: &str, value: &str) -> Result<(), String> {
+        match key {
+            "window_width" => {
+                self.window_dims.x = parse_in_range(value, MIN_WINDOW_DIMS.x, MAX_WINDOW_DIMS.x)?
+            }
+            "window_height" => {
+                self.window_dims.y = parse_in_range(value, MIN_WINDOW_DIMS.y, MAX_WINDOW_DIMS.y)?
+            }
+            "fullscreen" => {
+                self.fullscreen = value
+                    .parse()
+                    .map_err(|_| format!("expected true or false, not `{}`", value))?
+            }
+            "world_size" => {
+                let size = parse_in_range(value, MIN_WORLD_SIZE, MAX_WORLD_SIZE)?;
+                if size % CHUNK_SIZE != 0 {
+                    return Err(format!("{} is not a multiple of {}", size, CHUNK_SIZE));
+                }
+                self.world_size = size;
+            }
+            "seed" => self.seed = parse_in_range(value, 0, u32::MAX)?,
+            "erosion" => {
+                self.erosion = value
+                    .parse()
+                    .map_err(|_| format!("expected true or false, not `{}`", value))?
+            }
+            "render_scale" => {
+                self.resolution_scale = ResolutionScale::from_label(value)
+                    .ok_or_else(|| format!("unknown render scale `{}`", value))?
+            }
+            "render_budget_ms" => {
+                self.raymarch_budget_ms =
+                    parse_in_range(value, MIN_RAYMARCH_BUDGET_MS, MAX_RAYMARCH_BUDGET_MS)?
+            }
+            "upscale" => {
+                self.upscaler = Upscaler::from_label(value)
+                    .ok_or_else(|| format!("unknown upscale filter `{}`", value))?
+            }
+            "supersampling" => {
+                self.supersampling = Supersampling::from_label(value)
+                    .ok_or_else(|| format!("unknown supersampling mode `{}`", value))?
+            }
+            "draw_distance" => {
+                self.draw_distance = parse_in_range(value, MIN_DRAW_DISTANCE, MAX_DRAW_DISTANCE)?
+            }
+            "step_budget" => {
+                self.voxel_step_budget = parse_in_range(value, MIN_STEP_BUDGET, MAX_STEP_BUDGET)?
+            }
+            "fov" => self.fov_y_deg = parse_in_range(value, MIN_FOV_Y_DEG, MAX_FOV_Y_DEG)?,
+            "mouse_sensitivity" => {
+                self.mouse_sensitivity =
+                    parse_in_range(value, MIN_MOUSE_SENSITIVITY, MAX_MOUSE_SENSITIVITY)?
+            }
+            "chunk_gen_budget" => {
+                self.chunk_gen_budget_per_step = parse_in_range(value, 1, MAX_CHUNK_GEN_BUDGET)?
+            }
+            "target_fps" => {
+                self.target_fps = parse_in_range(value, MIN_TARGET_FPS, MAX_TARGET_FPS)?
+            }
+            _ => return Err(format!("unknown setting `{}`", key)),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn value(&self, key: &str) -> String {
+        match key {
+            "window_width" => self.window_dims.x.to_string(),
+            "window_height" => self.window_dims.y.to_string(),
+            "fullscreen" => self.fullscreen.to_string(),
+            "world_size" => self.world_size.to_string(),
+            "seed" => self.seed.to_string(),
+            "erosion" => self.erosion.to_string(),
+            "render_scale" => self.resolution_scale.label().to_string(),
+            "render_budget_ms" => self.raymarch_budget_ms.to_string(),
+            "upscale" => self.upscaler.label().to_string(),
+            "supersampling" => self.supersampling.label().to_string(),
+            "draw_distance" => self.draw_distance.to_string(),
+            "step_budget" => self.voxel_step_budget.to_string(),
+            "fov" => self.fov_y_deg.to_string(),
+            "mouse_sensitivity" => self.mouse_sensitivity.to_string(),
+            "chunk_gen_budget" => self.chunk_gen_budget_per_step.to_string(),
+            "target_fps" => self.target_fps.to_string(),
+            _ => unreachable!("`{}` is not in SETTING_TABLE", key),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        SETTING_TABLE
+            .iter()
+            .map(|(key, description)| format!("# {}\n{} = {}\n", description, key, self.value(key)))
+            .collect()
+    }
+
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    /// Takes over what was adjusted at runtime so it persists.
+    pub fn capture(&mut self, state: &State) {
+        if !self.fullscreen {
+            self.window_dims = state.window_dims.clamp(MIN_WINDOW_DIMS, MAX_WINDOW_DIMS);
+        }
+        self.seed = state.world.seed;
+        self.resolution_scale = state.resolution_scale;
+        self.raymarch_budget_ms = state.adaptive_resolution.budget_ms;
+        self.upscaler = state.upscaler;
+        self.supersampling = state.supersampling;
+        self.draw_distance = state.draw_distance;
+        self.voxel_step_budget = state.voxel_step_budget;
+        self.fov_y_deg = state.fov_y_deg;
+        self.mouse_sensitivity = state.mouse_sensitivity;
+        self.chunk_gen_budget_per_step = state.chunk_gen_budget_per_step;
+    }
+
+    /// Counterpart of `capture`: pushes the `RUNTIME_SETTINGS` into `state`.
+    pub fn apply_runtime(&self, state: &mut State) {
+        state.resolution_scale = self.resolution_scale;
+        state.adaptive_resolution.budget_ms = self.raymarch_budget_ms;
+        state.upscaler = self.upscaler;
+        state.supersampling = self.supersampling;
+        state.draw_distance = self.draw_distance;
+        state.voxel_step_budget = self.voxel_step_budget;
+        state.apply_fov_y_deg(self.fov_y_deg);
+        state.mouse_sensitivity = self.mouse_sensitivity;
+        state.chunk_gen_budget_per_step = self.chunk_gen_budget_per_step;
+    }
+
+    pub fn usage() -> String {
+        let mut usage = String::from("usage: voxel-raymarcher-rust [--key value]...\n\n");
+        for (key, description) in SETTING_TABLE {
+            usage += &format!("  --{:<20} {}\n", key.replace('_', "-"), description);
+        }
+        usage += "  --windowed             same as --fullscreen=false\n";
+        usage += &format!(
+            "\nSettings are also read from and saved to {}.\n",
+            SETTINGS_FILE
+        );
+        usage
+    }
+}
+
+/// Handles `--help`; `None` when `args` is something else.
+pub fn run_cli(args: &[String]) -> Option<i32> {
+    if !args.iter().any(|arg| arg == "--help" || arg == "-h") {
+        return None;
+    }
+    print!("{}", Settings::usage());
+    Some(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn file_then_flags_override_defaults_and_round_trip() {
+        let (mut settings, problems) = Settings::parse(
+            "# comment\nworld_size = 128\nrender_scale = 1/8x\nfov = 70\nfullscreen = true\n",
+        );
+        assert!(problems.is_empty(), "{:?}", problems);
+        settings
+            .apply_args(&args(
+                "--seed 42 --draw-distance=300 --windowed --window-width 1920",
+            ))
+            .unwrap();
+
+        assert_eq!(settings.world_size, 128);
+        assert_eq!(settings.resolution_scale, ResolutionScale::XEighth);
+        assert_eq!(settings.fov_y_deg, 70.0);
+        assert_eq!(settings.seed, 42);
+        assert_eq!(settings.draw_distance, 300.0);
+        assert!(!settings.fullscreen);
+        assert_eq!(settings.window_dims, UVec2::new(1920, DIMS.y));
+
+        let (reloaded, problems) = Settings::parse(&settings.to_text());
+        assert!(problems.is_empty(), "{:?}", problems);
+        assert_eq!(reloaded, settings);
+    }
+
+    #[test]
+    fn rejects_values_outside_the_clamp_ranges() {
+        let (settings, problems) = Settings::parse(
+            "draw_distance = 1\nstep_budget = 99\nfov = 10\nworld_size = 100\nbogus = 1\n",
+        );
+        assert_eq!(settings, Settings::default());
+        assert_eq!(problems.len(), 5, "{:?}", problems);
+
+        let mut settings = Settings::default();
+        assert!(settings.apply_args(&args("--fov 200")).is_err());
+        assert!(settings.apply_args(&args("--seed")).is_err());
+        assert!(settings.apply_args(&args("seed 3")).is_err());
+        assert_eq!(settings.fov_y_deg, DEFAULT_FOV_Y_DEG);
+
+        let (settings, problems) =
+            Settings::parse("mouse_sensitivity = nan\nrender_budget_ms = NaN\n");
+        assert_eq!(settings, Settings::default());
+        assert_eq!(problems.len(), 2, "{:?}", problems);
+        let mut settings = Settings::default();
+        assert!(settings
+            .apply_args(&args("--mouse-sensitivity nan"))
+            .is_err());
+    }
+}
@@ -1,13 +1,11 @@
-use glam::Vec3;
-use raylib::prelude::*;
-
 use crate::state::{Mode, State};
 use crate::world::CHUNK_SIZE;
+use crate::TIMESTEP;
 
 const MIN_CHUNK_GEN_RADIUS: i32 = 1;
 const MAX_CHUNK_GEN_BUDGET: usize = 32;
 
-pub fn step(rl: &mut RaylibHandle, state: &mut State) {
+pub fn step(state: &mut State) {
     let cam_chunk_x = (state.camera.pos.x / CHUNK_SIZE as f32).floor() as i32;
     let cam_chunk_z = (state.camera.pos.z / CHUNK_SIZE as f32).floor() as i32;
     let chunk_dim = state.world.chunk_dim as i32;
@@ -31,9 +29,8 @@ pub fn step(rl: &mut RaylibHandle, state: &mut State) {
         {
             continue;
         }
-        state
-            .world
-            .apply_terrain_column_heights(column.chunk_x, column.chunk_z, &column.surface_y);
+        state.world.apply_terrain_column_heights(&column);
+        state.last_frame_timings.chunk_columns_applied += 1;
     }
 
     let mut candidates: Vec<(i32, u32, u32)> = Vec::new();
@@ -77,17 +74,33 @@ pub fn step(rl: &mut RaylibHandle, state: &mut State) {
         queued += 1;
     }
 
-    if state.mode == Mode::Orbit {
-        let t = rl.get_time();
-        let orbit_radius: f32 = 10.0;
-        let orbit_center = state.world.get_center();
-        let cam_height = state.camera.pos.y;
-        state.camera.pos = Vec3::new(
-            t.sin() as f32 * orbit_radius,
-            0.0,
-            t.cos() as f32 * orbit_radius,
-        ) + orbit_center;
-        state.camera.dir = (state.world.get_center() - state.camera.pos).normalize();
-        state.camera.pos.y = cam_height;
+    if let Some(time) = state.path_playback_time {
+        match state.camera_path.sample(time) {
+            Some(key) if time <= state.camera_path.duration() => {
+                state.camera.pos = key.pos;
+                state.camera.orientation = key.orientation;
+                state.apply_fov_y_deg(key.fov_y_deg);
+                state.path_playback_time = Some(time + TIMESTEP);
+            }
+            _ => state.path_playback_time = None,
+        }
+    } else if state.mode == Mode::Walk {
+        // Hold still until the terrain under the player has streamed in.
+        let max_chunk = state.world.chunk_dim as i32 - 1;
+        let chunk_x = (state.player.pos.x / CHUNK_SIZE as f32).floor() as i32;
+        let chunk_z = (state.player.pos.z / CHUNK_SIZE as f32).floor() as i32;
+        if state.world.is_terrain_column_generated(
+            chunk_x.clamp(0, max_chunk) as u32,
+            chunk_z.clamp(0, max_chunk) as u32,
+        ) {
+            state.player.step(&state.world, &state.walk_input, TIMESTEP);
+        }
+        state.camera.pos = state.player.eye_pos();
+    } else if state.mode == Mode::Orbit {
+        if !state.orbit.paused {
+            state.orbit.azimuth += state.orbit.angular_speed * TIMESTEP;
+        }
+        state.camera.pos = state.orbit.camera_pos();
+        state.camera.set_dir(state.orbit.target - state.camera.pos);
     }
 }
@@ -1,24 +1,52 @@
-use glam::{Vec2, Vec3};
+use glam::{UVec2, Vec2, Vec3};
+use std::fs;
+use std::io;
+use std::path::Path;
 
-use crate::camera::Camera;
-use crate::raymarch::{self, RenderStats};
+use crate::adaptive_resolution::AdaptiveResolution;
+use crate::bindings::{Bindings, BINDINGS_FILE};
+use crate::camera::{isometric_dir, Camera};
+use crate::camera_path::{CameraPath, PathExportJob, CAMERA_PATH_FILE};
+use crate::console::Console;
+use crate::frame_history::FrameHistory;
+use crate::player::{Player, WalkInput};
+use crate::raymarch::{
+    self, raycast_first_hit, DebugView, RaymarchInput, RenderStats, Supersampling,
+};
+use crate::settings::Settings;
 use crate::terrain_worker::TerrainGenWorker;
-use crate::viewplane::Viewplane;
-use crate::world::{Block, World};
-use crate::{DIMS, VOXEL_STEP_BUDGET, WORLD_SIZE};
+use crate::viewplane::{size_for_fov_y, Projection, Viewplane};
+use crate::world::{Block, MaterialId, World};
+use crate::world_generation::GLASS_IOR;
 
 pub const FRAMES_PER_SECOND: u32 = 60;
 pub const DEFAULT_DRAW_DISTANCE: f32 = 128.0;
 
-const MIN_DRAW_DISTANCE: f32 = 2.0;
-const MAX_DRAW_DISTANCE: f32 = 2000.0;
-const MIN_FOV_Y_DEG: f32 = 25.0;
-const MAX_FOV_Y_DEG: f32 = 120.0;
+pub const DEFAULT_FOV_Y_DEG: f32 = 53.130104;
+pub const MAX_CHUNK_GEN_BUDGET: usize = 32;
+
+pub(crate) const MIN_DRAW_DISTANCE: f32 = 2.0;
+pub(crate) const MAX_DRAW_DISTANCE: f32 = 2000.0;
+pub(crate) const MIN_FOV_Y_DEG: f32 = 25.0;
+pub(crate) const MAX_FOV_Y_DEG: f32 = 120.0;
+const ISOMETRIC_CAMERA_DISTANCE: f32 = 96.0;
+const ISOMETRIC_FALLBACK_FOCUS_DISTANCE: f32 = 32.0;
+const DEFAULT_ORBIT_RADIUS: f32 = 10.0;
+const MIN_ORBIT_RADIUS: f32 = 2.0;
+const MAX_ORBIT_RADIUS: f32 = 512.0;
+const MAX_ORBIT_ELEVATION_DEG: f32 = 85.0;
+/// Radians per second, either direction.
+pub const MAX_ORBIT_SPEED: f32 = 3.0;
 
 #[derive(Debug, Eq, PartialEq)]
 pub enum Mode {
     Orbit,
+    /// Free flight that stays upright: yaw around world up, pitch stops short of vertical.
     Fly,
+    /// Six degrees of freedom: pitch, yaw and roll around the camera's own axes.
+    SixDof,
+    /// On foot: gravity and collision with the world, camera at eye height.
+    Walk,
 }
 
 #[derive(Debug, Copy, Clone, Eq, PartialEq)]
@@ -29,6 +57,8 @@ pub enum ResolutionScale {
     XEighth,
     XSixteenth,
     XThirtySecond,
+    /// Continuous scale chosen by `AdaptiveResolution`.
+    Auto,
 }
 
 impl ResolutionScale {
@@ -41,8 +71,81 @@ impl ResolutionScale {
             Self::XEighth => "1/8x",
             Self::XSixteenth => "1/16x",
             Self::XThirtySecond => "1/32x",
+            Self::Auto => "auto",
+        }
+    }
+
+    /// Render size as a fraction of the window; `None` for `Auto`, whose
+    /// factor lives in `State::adaptive_resolution`.
+    pub fn factor(self) -> Option<f32> {
+        match self {
+            Self::X1 => Some(1.0),
+            Self::XHalf => Some(1.0 / 2.0),
+            Self::XQuarter => Some(1.0 / 4.0),
+            Self::XEighth => Some(1.0 / 8.0),
+            Self::XSixteenth => Some(1.0 / 16.0),
+            Self::XThirtySecond => Some(1.0 / 32.0),
+            Self::Auto => None,
         }
     }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        [
+            Self::X1,
+            Self::XHalf,
+            Self::XQuarter,
+            Self::XEighth,
+            Self::XSixteenth,
+            Self::XThirtySecond,
+            Self::Auto,
+        ]
+        .into_iter()
+        .find(|scale| scale.label() == label)
+    }
+}
+
+/// How a render smaller than the window is scaled up to it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Upscaler {
+    Nearest,
+    Bilinear,
+    /// Bilinear, except across depth discontinuities, which keep the nearest
+    /// sample's color so silhouettes stay crisp.
+    EdgeAware,
+    /// Jittered samples accumulated over frames by `TemporalUpscaler`.
+    Temporal,
+}
+
+impl Upscaler {
+    pub const ALL: [Upscaler; 4] = [
+        Upscaler::Nearest,
+        Upscaler::Bilinear,
+        Upscaler::EdgeAware,
+        Upscaler::Temporal,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Nearest => "nearest",
+            Self::Bilinear => "bilinear",
+            Self::EdgeAware => "edge",
+            Self::Temporal => "temporal",
+        }
+    }
+
+    pub fn from_label(label: &str) -> Option<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|upscaler| upscaler.label() == label)
+    }
+
+    pub fn next(self) -> Self {
+        let index = Self::ALL
+            .iter()
+            .position(|&upscaler| upscaler == self)
+            .unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
 }
 
 #[derive(Debug, Copy, Clone, Default)]
@@ -52,6 +155,62 @@ pub struct FrameTimings {
     pub upload_ms: f32,
     pub frame_ms: f32,
     pub reused_render: bool,
+    /// Terrain columns painted into the world during the frame's simulation steps.
+    pub chunk_columns_applied: u32,
+}
+
+/// Orbit-mode camera rig. `simulation::step` advances `azimuth` by
+/// `angular_speed` every tick, so pausing or a slow frame never makes it jump.
+#[derive(Debug, Clone)]
+pub struct Orbit {
+    pub target: Vec3,
+    pub radius: f32,
+    /// Radians above the target's horizontal plane.
+    pub elevation: f32,
+    pub azimuth: f32,
+    pub angular_speed: f32,
+    pub paused: bool,
+}
+
+impl Orbit {
+    /// Orbit around `target` that starts exactly at `camera_pos`.
+    pub fn through(target: Vec3, camera_pos: Vec3, angular_speed: f32) -> Self {
+        let offset = camera_pos - target;
+        let mut orbit = Self {
+            target,
+            radius: 0.0,
+            elevation: (-offset.y).atan2(offset.x.hypot(offset.z)),
+            azimuth: offset.x.atan2(offset.z),
+            angular_speed,
+            paused: false,
+        };
+        orbit.set_radius(offset.length());
+        orbit.set_elevation(orbit.elevation);
+        orbit
+    }
+
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius.clamp(MIN_ORBIT_RADIUS, MAX_ORBIT_RADIUS);
+    }
+
+    pub fn set_elevation(&mut self, elevation: f32) {
+        let limit = MAX_ORBIT_ELEVATION_DEG.to_radians();
+        self.elevation = elevation.clamp(-limit, limit);
+    }
+
+    pub fn set_angular_speed(&mut self, angular_speed: f32) {
+        self.angular_speed = angular_speed.clamp(-MAX_ORBIT_SPEED, MAX_ORBIT_SPEED);
+    }
+
+    pub fn camera_pos(&self) -> Vec3 {
+        let horizontal = self.elevation.cos() * self.radius;
+        self.target
+            + Vec3::new(
+                self.azimuth.sin() * horizontal,
+                -self.elevation.sin() * self.radius,
+                self.azimuth.cos() * horizontal,
+            )
+    }
 }
 
 pub struct State {
@@ -63,105 +222,188 @@ pub struct State {
     pub viewplane: Box<Viewplane>,
 
     pub mode: Mode,
+    pub projection: Projection,
     pub draw_distance: f32,
     pub voxel_step_budget: f32,
     pub fov_y_deg: f32,
     pub fps: i32,
     pub resolution_scale: ResolutionScale,
+    pub adaptive_resolution: AdaptiveResolution,
+    pub upscaler: Upscaler,
+    pub supersampling: Supersampling,
+    pub window_dims: UVec2,
     pub render_width: u32,
     pub render_height: u32,
     pub chunk_gen_budget_per_step: usize,
     pub mouse_look_locked: bool,
+    /// Radians of camera turn per pixel of mouse movement.
+    pub mouse_sensitivity: f32,
+    pub xray: bool,
+    pub debug_view: DebugView,
     pub last_render_stats: RenderStats,
     pub last_frame_timings: FrameTimings,
+    pub frame_history: FrameHistory,
     pub terrain_worker: TerrainGenWorker,
+    pub camera_path: CameraPath,
+    /// Seconds into `camera_path` while it is playing back.
+    pub path_playback_time: Option<f32>,
+    pub path_export: Option<PathExportJob>,
+    pub player: Player,
+    pub walk_input: WalkInput,
+    pub orbit: Orbit,
+    pub bindings: Bindings,
+    pub console: Console,
 }
 
-impl State {
-    pub fn new() -> Self {
-        let mut world = Box::new(World::new(WORLD_SIZE));
-        world.gen_floor(Block::new(255, 255, 255, 255));
-        world.gen_cube(
-            Vec3::new(1.0, world.get_above_floor_level() as f32 - 1.0, 1.0),
-            Vec3::new(1.0, 2.0, 1.0),
-            Block::new(255, 0, 0, 255),
-        );
+/// Floor, marker cubes and glass slab the viewer starts with.
+pub fn build_demo_world(dim: usize) -> Box<World> {
+    let mut world = Box::new(World::new(dim));
+    world.gen_floor(Block::new(255, 255, 255, 255));
+    world.gen_cube(
+        Vec3::new(1.0, world.get_above_floor_level() as f32 - 1.0, 1.0),
+        Vec3::new(1.0, 2.0, 1.0),
+        Block::new(255, 0, 0, 255),
+    );
 
-        let cube_color = Block::new(255, 255, 255, 255);
-        world.gen_cube(
-            Vec3::new(0.0, world.get_above_floor_level() as f32 - 1.0, 0.0),
-            Vec3::new(1.0, 1.0, 1.0),
-            cube_color,
-        );
-        world.gen_cube(
-            Vec3::new(
-                0.0,
-                world.get_above_floor_level() as f32 - 1.0,
-                world.dim as f32 - 1.0,
-            ),
-            Vec3::new(1.0, 1.0, 1.0),
-            cube_color,
-        );
-        world.gen_cube(
-            Vec3::new(
-                world.dim as f32 - 1.0,
-                world.get_above_floor_level() as f32 - 1.0,
-                0.0,
-            ),
-            Vec3::new(1.0, 1.0, 1.0),
-            cube_color,
-        );
-        world.gen_cube(
-            Vec3::new(
-                world.dim as f32 - 1.0,
-                world.get_above_floor_level() as f32 - 1.0,
-                world.dim as f32 - 1.0,
-            ),
-            Vec3::new(1.0, 1.0, 1.0),
-            cube_color,
-        );
+    let cube_color = Block::new(255, 255, 255, 255);
+    world.gen_cube(
+        Vec3::new(0.0, world.get_above_floor_level() as f32 - 1.0, 0.0),
+        Vec3::new(1.0, 1.0, 1.0),
+        cube_color,
+    );
+    world.gen_cube(
+        Vec3::new(
+            0.0,
+            world.get_above_floor_level() as f32 - 1.0,
+            world.dim as f32 - 1.0,
+        ),
+        Vec3::new(1.0, 1.0, 1.0),
+        cube_color,
+    );
+    world.gen_cube(
+        Vec3::new(
+            world.dim as f32 - 1.0,
+            world.get_above_floor_level() as f32 - 1.0,
+            0.0,
+        ),
+        Vec3::new(1.0, 1.0, 1.0),
+        cube_color,
+    );
+    world.gen_cube(
+        Vec3::new(
+            world.dim as f32 - 1.0,
+            world.get_above_floor_level() as f32 - 1.0,
+            world.dim as f32 - 1.0,
+        ),
+        Vec3::new(1.0, 1.0, 1.0),
+        cube_color,
+    );
 
-        let glass = Block::new(180, 220, 255, 85);
-        world.gen_cube(
-            Vec3::new(8.0, world.get_above_floor_level() as f32 - 10.0, 8.0),
-            Vec3::new(1.0, 10.0, 8.0),
-            glass,
-        );
+    let glass = Block::new(180, 220, 255, 85);
+    world.gen_cube(
+        Vec3::new(8.0, world.get_above_floor_level() as f32 - 10.0, 8.0),
+        Vec3::new(1.0, 10.0, 8.0),
+        glass,
+    );
+    let glass_material = world.intern_material(glass);
+    world.set_material_ior(glass_material, GLASS_IOR);
+    world
+}
 
-        let world_center = world.get_center();
-        let camera_pos = Vec3::new(
-            world_center.x,
-            world.get_floor_level() as f32 - 28.0,
-            world_center.z - 24.0,
-        );
-        let camera_dir = Vec3::new(0.0, 0.12, 1.0).normalize();
-        let camera = Box::new(Camera::new(camera_pos, camera_dir, 3.0));
+/// Camera placement `State::new` starts from.
+pub fn demo_camera(world: &World) -> Camera {
+    let world_center = world.get_center();
+    let camera_pos = Vec3::new(
+        world_center.x,
+        world.get_floor_level() as f32 - 28.0,
+        world_center.z - 24.0,
+    );
+    let camera_dir = Vec3::new(0.0, 0.12, 1.0).normalize();
+    Camera::new(camera_pos, camera_dir, 3.0)
+}
+
+/// The path recorded in an earlier session, if `CAMERA_PATH_FILE` exists in
+/// the working directory.
+fn load_camera_path() -> CameraPath {
+    let file = Path::new(CAMERA_PATH_FILE);
+    match CameraPath::load(file) {
+        Ok(path) => {
+            println!(
+                "loaded {} camera keyframes from {}",
+                path.keyframes.len(),
+                fs::canonicalize(file)
+                    .unwrap_or(file.to_path_buf())
+                    .display()
+            );
+            path
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => CameraPath::default(),
+        Err(e) => {
+            eprintln!("ignoring {}: {}", file.display(), e);
+            CameraPath::default()
+        }
+    }
+}
+
+impl State {
+    pub fn new(settings: &Settings) -> Self {
+        let mut world = build_demo_world(settings.world_size);
+        world.seed = settings.seed;
+        world.terrain_shape = world.terrain_shape.with_erosion(settings.erosion);
+        let camera = Box::new(demo_camera(&world));
         let viewplane = Box::new(Viewplane::new(Vec2::new(4.0, 3.0), 4.0 / 3.0));
 
-        let fov_y_deg =
-            (2.0 * ((viewplane.size.y * 0.5) / camera.viewplane_distance).atan()).to_degrees();
-        let terrain_worker = TerrainGenWorker::new(world.get_floor_level() as i32);
+        let terrain_worker = TerrainGenWorker::new(world.new_terrain_heightfield());
+        let player = Player::spawn_at_eye(&world, camera.pos);
+        let world_center = world.get_center();
+        let (bindings, binding_problems) = Bindings::load(Path::new(BINDINGS_FILE));
+        for problem in binding_problems {
+            eprintln!("{}: {}", BINDINGS_FILE, problem);
+        }
 
-        Self {
+        let mut state = Self {
             running: true,
             time_since_last_update: 0.0,
             world,
             camera,
             viewplane,
             mode: Mode::Fly,
-            draw_distance: DEFAULT_DRAW_DISTANCE,
-            voxel_step_budget: VOXEL_STEP_BUDGET,
-            fov_y_deg,
+            projection: Projection::Perspective,
+            draw_distance: settings.draw_distance,
+            voxel_step_budget: settings.voxel_step_budget,
+            fov_y_deg: settings.fov_y_deg,
             fps: 0,
-            resolution_scale: ResolutionScale::XQuarter,
-            render_width: DIMS.x,
-            render_height: DIMS.y,
-            chunk_gen_budget_per_step: 2,
+            resolution_scale: settings.resolution_scale,
+            adaptive_resolution: AdaptiveResolution::new(settings.raymarch_budget_ms),
+            upscaler: settings.upscaler,
+            supersampling: settings.supersampling,
+            window_dims: settings.window_dims,
+            render_width: settings.window_dims.x,
+            render_height: settings.window_dims.y,
+            chunk_gen_budget_per_step: settings.chunk_gen_budget_per_step,
             mouse_look_locked: true,
+            mouse_sensitivity: settings.mouse_sensitivity,
+            xray: false,
+            debug_view: DebugView::Shaded,
             last_render_stats: RenderStats::default(),
             last_frame_timings: FrameTimings::default(),
+            frame_history: FrameHistory::default(),
             terrain_worker,
-        }
+            camera_path: load_camera_path(),
+            path_playback_time: None,
+            path_export: None,
+            player,
+            walk_input: WalkInput::default(),
+            orbit: Orbit::through(
+                world_center,
+                world_center + Vec3::Z * DEFAULT_ORBIT_RADIUS,
+                1.0,
+            ),
+            bindings,
+            console: Console::default(),
+        };
+        state.apply_fov_y_deg(settings.fov_y_deg);
+        state
     }
 
     pub fn current_fov_y_deg(&self) -> f32 {
@@ -175,11 +417,106 @@ impl State {
 
     pub fn apply_fov_y_deg(&mut self, new_fov_y_deg: f32) {
         self.fov_y_deg = new_fov_y_deg.max(MIN_FOV_Y_DEG).min(MAX_FOV_Y_DEG);
-        let distance = self.camera.viewplane_distance.max(0.001);
-        let aspect = DIMS.x as f32 / DIMS.y as f32;
-        let half_height = (self.fov_y_deg.to_radians() * 0.5).tan() * distance;
-        let viewplane_height = half_height * 2.0;
-        self.viewplane.size = Vec2::new(viewplane_height * aspect, viewplane_height);
+        let aspect = self.window_dims.x as f32 / self.window_dims.y as f32;
+        self.viewplane.size =
+            size_for_fov_y(self.fov_y_deg, self.camera.viewplane_distance, aspect);
+    }
+
+    /// Adopts a new window size; the viewplane follows its aspect ratio.
+    pub fn resize_window(&mut self, window_dims: UVec2) {
+        self.window_dims = window_dims.max(UVec2::ONE);
+        self.viewplane
+            .set_aspect_ratio(self.window_dims.x as f32 / self.window_dims.y as f32);
+    }
+
+    /// Rebuilds the world with a new terrain seed; the camera stays where it is.
+    pub fn regenerate_world(&mut self, seed: u32) {
+        let mut world = build_demo_world(self.world.dim);
+        world.seed = seed;
+        world.terrain_shape = self.world.terrain_shape;
+        world.edit_revision = self.world.edit_revision + 1;
+        self.terrain_worker = TerrainGenWorker::new(world.new_terrain_heightfield());
+        self.world = world;
+        if self.mode == Mode::Walk {
+            self.player = Player::spawn_at_eye(&self.world, self.camera.pos);
+        }
+    }
+
+    /// Render size as a fraction of the window for the current scale mode.
+    pub fn render_scale_factor(&self) -> f32 {
+        self.resolution_scale
+            .factor()
+            .unwrap_or(self.adaptive_resolution.scale)
+    }
+
+    /// Overlay text for the render scale; auto mode shows the chosen factor.
+    pub fn render_scale_label(&self) -> String {
+        match self.resolution_scale {
+            ResolutionScale::Auto => format!("auto {:.2}x", self.adaptive_resolution.scale),
+            scale => scale.label().to_string(),
+        }
+    }
+
+    /// What the main view traces with the current camera and render budget.
+    pub fn raymarch_input(&self) -> RaymarchInput<'_> {
+        RaymarchInput {
+            world: &self.world,
+            camera: &self.camera,
+            viewplane: &self.viewplane,
+            projection: self.projection,
+            draw_distance: self.draw_distance,
+            voxel_step_budget: self.voxel_step_budget,
+            hidden_material: self.hidden_material(),
+            debug_view: self.debug_view,
+            jitter: Vec2::ZERO,
+            supersampling: self.supersampling,
+        }
+    }
+
+    /// Material the x-ray view hides, if it is on.
+    pub fn hidden_material(&self) -> Option<MaterialId> {
+        if self.xray {
+            self.world.terrain_materials.map(|ids| ids.stone)
+        } else {
+            None
+        }
+    }
+
+    /// Center of the first voxel along `dir`, or `fallback_distance` along it on a miss.
+    pub fn pick_point(&self, origin: Vec3, dir: Vec3, fallback_distance: f32) -> Vec3 {
+        raycast_first_hit(&self.world, origin, dir, self.draw_distance)
+            .map(|(voxel, _)| voxel.as_vec3() + Vec3::splat(0.5))
+            .unwrap_or(origin + dir * fallback_distance)
+    }
+
+    /// Starts orbiting `target` from wherever the camera is now.
+    pub fn orbit_target(&mut self, target: Vec3) {
+        let paused = self.orbit.paused;
+        self.orbit = Orbit::through(target, self.camera.pos, self.orbit.angular_speed);
+        self.orbit.paused = paused;
+    }
+
+    /// Orbits what the camera currently looks at.
+    pub fn orbit_look_at_point(&mut self) {
+        let target = self.pick_point(self.camera.pos, self.camera.dir(), DEFAULT_ORBIT_RADIUS);
+        self.orbit_target(target);
+    }
+
+    /// Switches to an orthographic isometric view centered on what the camera looks at.
+    pub fn apply_isometric_preset(&mut self) {
+        let focus = self.pick_point(
+            self.camera.pos,
+            self.camera.dir(),
+            ISOMETRIC_FALLBACK_FOCUS_DISTANCE,
+        );
+        let zoom = match self.projection {
+            Projection::Orthographic { zoom } => zoom,
+            Projection::Perspective => 1.0,
+        };
+
+        self.projection = Projection::Orthographic { zoom };
+        self.camera.set_dir(isometric_dir());
+        self.camera.pos = focus - isometric_dir() * ISOMETRIC_CAMERA_DISTANCE;
     }
 
     pub fn clamp_render_budget(&mut self) {
@@ -194,3 +531,25 @@ impl State {
         self.fov_y_deg = self.fov_y_deg.max(MIN_FOV_Y_DEG).min(MAX_FOV_Y_DEG);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn orbit_starts_at_the_camera_and_advances_with_azimuth() {
+        let target = Vec3::new(40.0, 200.0, 12.0);
+        let camera_pos = Vec3::new(52.0, 191.0, -3.0);
+        let mut orbit = Orbit::through(target, camera_pos, 0.5);
+        assert!((orbit.camera_pos() - camera_pos).length() < 1e-3);
+        assert!(orbit.elevation > 0.0, "camera above the target (smaller y)");
+
+        orbit.azimuth += std::f32::consts::PI;
+        let opposite = orbit.camera_pos();
+        assert!((opposite.y - camera_pos.y).abs() < 1e-3);
+        assert!(
+            ((opposite + camera_pos) * 0.5 - Vec3::new(target.x, camera_pos.y, target.z)).length()
+                < 1e-3
+        );
+    }
+}
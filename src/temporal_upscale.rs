@@ -0,0 +1,294 @@
+//! Temporal upsampler: jitters the low-resolution samples each frame and
+//! accumulates them into a window-sized history, reprojected with per-pixel
+//! hit depth so a slowly moving camera converges to a sharp image.
+
+use glam::{UVec2, Vec2, Vec3};
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::viewplane::{Projection, Viewplane};
+
+/// Jitter positions cycled through; a still camera stops re-rendering after one cycle.
+pub const JITTER_SEQUENCE_LEN: u32 = 16;
+/// Cap on accumulated history weight, so new samples keep at least a small share.
+const MAX_HISTORY_WEIGHT: f32 = 12.0;
+/// Floor on a sample's weight so a pixel with rejected history still gets a color.
+const MIN_SAMPLE_WEIGHT: f32 = 0.01;
+/// History whose depth differs from the reprojected depth by more than this
+/// fraction (plus `DEPTH_TOLERANCE_VOXELS`) belongs to another surface.
+const DEPTH_TOLERANCE: f32 = 0.05;
+const DEPTH_TOLERANCE_VOXELS: f32 = 0.25;
+
+/// Camera and viewplane of one frame, measured in window pixels.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct ViewFrame {
+    pos: Vec3,
+    dir: Vec3,
+    top_left: Vec3,
+    right_step: Vec3,
+    down_step: Vec3,
+    orthographic: bool,
+}
+
+impl ViewFrame {
+    pub fn new(
+        camera: &Camera,
+        viewplane: &Viewplane,
+        projection: Projection,
+        dims: UVec2,
+    ) -> Self {
+        let extent = viewplane.extent_for(projection);
+        let pixel_size = extent / dims.as_vec2();
+        Self {
+            pos: camera.pos,
+            dir: camera.dir(),
+            top_left: viewplane.top_left_corner_with_extent(camera, extent),
+            right_step: viewplane.get_right_from_perspective_of(camera) * pixel_size.x,
+            down_step: viewplane.get_down_from_perspective_of(camera) * pixel_size.y,
+            orthographic: matches!(projection, Projection::Orthographic { .. }),
+        }
+    }
+
+    /// Origin and unit direction of the ray through `pixel` (`(0, 0)` is the
+    /// top-left corner of the window), as `draw_voxels` traces it.
+    fn ray(&self, pixel: Vec2) -> (Vec3, Vec3) {
+        let target = self.top_left + self.right_step * pixel.x + self.down_step * pixel.y;
+        if self.orthographic {
+            (target, self.dir)
+        } else {
+            (self.pos, (target - self.pos).normalize())
+        }
+    }
+
+    /// Window pixel position of `point` and its hit distance along that pixel's
+    /// ray; `None` behind the camera.
+    fn project(&self, point: Vec3) -> Option<(Vec2, f32)> {
+        let (on_plane, depth) = if self.orthographic {
+            let depth = (point - self.top_left).dot(self.dir);
+            (point - self.dir * depth, depth)
+        } else {
+            let offset = point - self.pos;
+            let along = offset.dot(self.dir);
+            if along <= 0.0 {
+                return None;
+            }
+            let plane_distance = (self.top_left - self.pos).dot(self.dir);
+            (
+                self.pos + offset * (plane_distance / along),
+                offset.length(),
+            )
+        };
+        let local = on_plane - self.top_left;
+        let pixel = Vec2::new(
+            local.dot(self.right_step) / self.right_step.length_squared(),
+            local.dot(self.down_step) / self.down_step.length_squared(),
+        );
+        Some((pixel, depth))
+    }
+}
+
+/// Accumulated color, its weight and the hit depth of one window pixel.
+#[derive(Debug, Copy, Clone, Default)]
+struct HistoryTexel {
+    color: Vec3,
+    weight: f32,
+    depth: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct TemporalUpscaler {
+    dims: UVec2,
+    history: Vec<HistoryTexel>,
+    /// Written by `resolve`, then swapped with `history`.
+    next_history: Vec<HistoryTexel>,
+    last_view: Option<ViewFrame>,
+    frame: u32,
+    /// Consecutive resolves from an unchanged view.
+    still_frames: u32,
+}
+
+impl TemporalUpscaler {
+    /// Forgets the history, e.g. after the shading of the whole view changed.
+    pub fn reset(&mut self) {
+        self.history.clear();
+        self.last_view = None;
+        self.still_frames = 0;
+    }
+
+    /// Position in the jitter sequence; it stops advancing once a still view has
+    /// gathered every jitter position, which lets the renderer reuse the frame.
+    pub fn jitter_index(&self) -> u32 {
+        self.frame % JITTER_SEQUENCE_LEN
+    }
+
+    /// Subpixel offset for this frame's low-resolution rays.
+    pub fn jitter(&self) -> Vec2 {
+        let index = self.jitter_index() + 1;
+        Vec2::new(halton(index, 2), halton(index, 3)) - Vec2::splat(0.5)
+    }
+
+    /// Blends a low-resolution frame traced with `jitter()` into the history
+    /// and writes the window-sized result to `out` as RGBA.
+    #[allow(clippy::too_many_arguments)]
+    pub fn resolve(
+        &mut self,
+        color: &[u8],
+        depth: &[f32],
+        render_dims: UVec2,
+        view: ViewFrame,
+        dims: UVec2,
+        out: &mut [u8],
+    ) {
+        debug_assert_eq!(color.len(), (render_dims.x * render_dims.y * 4) as usize);
+        debug_assert_eq!(depth.len(), (render_dims.x * render_dims.y) as usize);
+        debug_assert_eq!(out.len(), (dims.x * dims.y * 4) as usize);
+
+        let texel_count = (dims.x * dims.y) as usize;
+        if self.dims != dims || self.history.len() != texel_count {
+            self.dims = dims;
+            self.history = vec![HistoryTexel::default(); texel_count];
+            self.last_view = None;
+        }
+        self.next_history
+            .resize(texel_count, HistoryTexel::default());
+
+        let jitter = self.jitter();
+        let sample_spacing = dims.as_vec2() / render_dims.as_vec2();
+        let history = &self.history;
+        let last_view = self.last_view;
+        let width = dims.x as usize;
+
+        out.par_chunks_exact_mut(width * 4)
+            .zip(self.next_history.par_chunks_exact_mut(width))
+            .enumerate()
+            .for_each(|(y, (out_row, history_row))| {
+                for (x, texel) in history_row.iter_mut().enumerate() {
+                    let pixel = Vec2::new(x as f32 + 0.5, y as f32 + 0.5);
+                    // Nearest low-resolution sample; sample (i, j) sits at
+                    // (i + 0.5 + jitter) * sample_spacing.
+                    let sample = (pixel / sample_spacing - jitter)
+                        .floor()
+                        .clamp(Vec2::ZERO, (render_dims - UVec2::ONE).as_vec2());
+                    let sample_index =
+                        sample.y as usize * render_dims.x as usize + sample.x as usize;
+                    let sample_pixel = (sample + Vec2::splat(0.5) + jitter) * sample_spacing;
+                    let distance = ((pixel - sample_pixel) / sample_spacing).length();
+                    let sample_weight = (1.0 - distance * 1.4).max(0.0).powi(2) + MIN_SAMPLE_WEIGHT;
+                    let sample_color = Vec3::new(
+                        color[sample_index * 4] as f32,
+                        color[sample_index * 4 + 1] as f32,
+                        color[sample_index * 4 + 2] as f32,
+                    );
+                    let sample_depth = depth[sample_index];
+
+                    let previous =
+                        last_view
+                            .filter(|_| sample_depth.is_finite())
+                            .and_then(|last_view| {
+                                let (origin, ray) = view.ray(pixel);
+                                let (last_pixel, expected_depth) =
+                                    last_view.project(origin + ray * sample_depth)?;
+                                if last_pixel.x < 0.0
+                                    || last_pixel.y < 0.0
+                                    || last_pixel.x >= dims.x as f32
+                                    || last_pixel.y >= dims.y as f32
+                                {
+                                    return None;
+                                }
+                                let texel =
+                                    history[last_pixel.y as usize * width + last_pixel.x as usize];
+                                let tolerance =
+                                    expected_depth * DEPTH_TOLERANCE + DEPTH_TOLERANCE_VOXELS;
+                                ((texel.depth - expected_depth).abs() <= tolerance).then_some(texel)
+                            });
+
+                    let (history_color, history_weight) = previous
+                        .map(|texel| (texel.color, texel.weight.min(MAX_HISTORY_WEIGHT)))
+                        .unwrap_or((Vec3::ZERO, 0.0));
+                    let weight = history_weight + sample_weight;
+                    let blended =
+                        (history_color * history_weight + sample_color * sample_weight) / weight;
+
+                    *texel = HistoryTexel {
+                        color: blended,
+                        weight,
+                        depth: sample_depth,
+                    };
+                    let out_index = x * 4;
+                    out_row[out_index] = blended.x.round().clamp(0.0, 255.0) as u8;
+                    out_row[out_index + 1] = blended.y.round().clamp(0.0, 255.0) as u8;
+                    out_row[out_index + 2] = blended.z.round().clamp(0.0, 255.0) as u8;
+                    out_row[out_index + 3] = 255;
+                }
+            });
+
+        std::mem::swap(&mut self.history, &mut self.next_history);
+        if self.last_view == Some(view) {
+            self.still_frames += 1;
+        } else {
+            self.still_frames = 0;
+        }
+        self.last_view = Some(view);
+        if self.still_frames < JITTER_SEQUENCE_LEN {
+            self.frame = self.frame.wrapping_add(1);
+        }
+    }
+}
+
+/// Radical inverse of `index` in `base`, in 0..1.
+fn halton(mut index: u32, base: u32) -> f32 {
+    let mut result = 0.0;
+    let mut fraction = 1.0 / base as f32;
+    while index > 0 {
+        result += (index % base) as f32 * fraction;
+        index /= base;
+        fraction /= base as f32;
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reprojects_a_point_into_the_pixel_that_traced_it() {
+        let dims = UVec2::new(64, 48);
+        let viewplane = Viewplane::new(Vec2::new(4.0, 3.0), 4.0 / 3.0);
+        for projection in [
+            Projection::Perspective,
+            Projection::Orthographic { zoom: 1.0 },
+        ] {
+            let camera = Camera::new(Vec3::new(10.0, -5.0, 3.0), Vec3::new(0.3, 0.2, 1.0), 3.0);
+            let view = ViewFrame::new(&camera, &viewplane, projection, dims);
+            let pixel = Vec2::new(17.5, 30.25);
+            let (origin, ray) = view.ray(pixel);
+            let (projected, depth) = view.project(origin + ray * 21.0).unwrap();
+            assert!((projected - pixel).length() < 1e-3, "{:?}", projected);
+            assert!((depth - 21.0).abs() < 1e-3, "{}", depth);
+        }
+    }
+
+    #[test]
+    fn still_view_converges_and_stops_jittering() {
+        let dims = UVec2::new(8, 8);
+        let render_dims = UVec2::new(2, 2);
+        let viewplane = Viewplane::new(Vec2::new(3.0, 3.0), 1.0);
+        let camera = Camera::new(Vec3::ZERO, Vec3::Z, 3.0);
+        let view = ViewFrame::new(&camera, &viewplane, Projection::Perspective, dims);
+        let color = [200u8, 100, 50, 255].repeat(4);
+        let depth = [10.0f32; 4];
+        let mut out = vec![0u8; (dims.x * dims.y * 4) as usize];
+
+        let mut upscaler = TemporalUpscaler::default();
+        for _ in 0..JITTER_SEQUENCE_LEN * 2 {
+            upscaler.resolve(&color, &depth, render_dims, view, dims, &mut out);
+        }
+        assert!(out
+            .chunks_exact(4)
+            .all(|texel| texel == [200, 100, 50, 255]));
+        let index = upscaler.jitter_index();
+        upscaler.resolve(&color, &depth, render_dims, view, dims, &mut out);
+        assert_eq!(upscaler.jitter_index(), index);
+    }
+}
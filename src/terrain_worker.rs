@@ -2,6 +2,8 @@ use crossbeam::channel::{unbounded, Receiver, Sender};
 use noise::{NoiseFn, Perlin};
 use std::collections::HashSet;
 
+use crate::erosion::{self, ErosionSettings};
+use crate::hydrology::{self, HydrologySettings};
 use crate::world::CHUNK_SIZE;
 
 const TERRAIN_BASE_OFFSET: f64 = -18.0;
@@ -17,7 +19,16 @@ const TERRAIN_RIDGE_AMP: f64 = 3.0;
 
 const MIN_SURFACE_OFFSET: i32 = -52;
 const MAX_SURFACE_OFFSET: i32 = -2;
-const TERRAIN_SEED: u32 = 0;
+pub const TERRAIN_SEED: u32 = 0;
+
+// Erosion and hydrology run once over the whole world footprint, so drainage
+// basins and droplet paths never end at an internal border. The margin lets
+// droplets and flow enter from outside the world edges.
+const SHAPING_MARGIN: i32 = 32;
+/// Area `ErosionSettings::iterations` is tuned for; larger worlds get
+/// proportionally more droplets.
+const EROSION_REFERENCE_AREA: u64 = 192 * 192;
+const MAX_SEDIMENT_DEPTH: i32 = 3;
 
 type TerrainRequest = (u32, u32);
 const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
@@ -27,6 +38,195 @@ pub struct TerrainColumnHeights {
     pub chunk_x: u32,
     pub chunk_z: u32,
     pub surface_y: [i32; CHUNK_AREA],
+    /// Voxels of deposited sediment (painted as clay) on top of each surface.
+    pub sediment_depth: [u8; CHUNK_AREA],
+    /// Top of river/lake water per cell; `i32::MAX` where there is none.
+    pub water_y: [i32; CHUNK_AREA],
+}
+
+/// Optional region passes applied to the raw noise heightfield.
+#[derive(Copy, Clone, Debug)]
+pub struct TerrainShapeSettings {
+    /// Off by default: it costs a whole region's droplets before the first
+    /// column, and `fill`/path export generate columns on the calling thread.
+    pub erosion: Option<ErosionSettings>,
+    pub hydrology: Option<HydrologySettings>,
+}
+
+impl Default for TerrainShapeSettings {
+    fn default() -> Self {
+        Self {
+            erosion: None,
+            hydrology: Some(HydrologySettings::default()),
+        }
+    }
+}
+
+impl TerrainShapeSettings {
+    pub fn with_erosion(mut self, enabled: bool) -> Self {
+        self.erosion = enabled.then(ErosionSettings::default);
+        self
+    }
+
+    #[inline]
+    fn needs_regions(&self) -> bool {
+        self.erosion.is_some() || self.hydrology.is_some()
+    }
+}
+
+/// Shaped columns of the whole world, `dim * dim`, indexed `x + z * dim`.
+#[derive(Debug, Clone)]
+struct ShapedRegion {
+    surface_y: Vec<i32>,
+    sediment_depth: Vec<u8>,
+    water_y: Vec<i32>,
+}
+
+/// Produces per-column surface heights, optionally running erosion and
+/// hydrology over the whole world on the first request. Shared by the worker
+/// and the sync path so both produce identical columns.
+#[derive(Debug, Clone)]
+pub struct TerrainHeightfield {
+    floor_level: i32,
+    water_level: i32,
+    seed: u32,
+    /// World voxels per side.
+    dim: i32,
+    perlin: Perlin,
+    shape: TerrainShapeSettings,
+    shaped: Option<ShapedRegion>,
+}
+
+impl TerrainHeightfield {
+    pub fn new(
+        floor_level: i32,
+        water_level: i32,
+        seed: u32,
+        dim: usize,
+        shape: TerrainShapeSettings,
+    ) -> Self {
+        Self {
+            floor_level,
+            water_level,
+            seed,
+            dim: dim as i32,
+            perlin: Perlin::new(seed),
+            shape,
+            shaped: None,
+        }
+    }
+
+    pub fn build_surface_heights(&mut self, chunk_x: u32, chunk_z: u32) -> TerrainColumnHeights {
+        let mut column = TerrainColumnHeights {
+            chunk_x,
+            chunk_z,
+            surface_y: [self.floor_level; CHUNK_AREA],
+            sediment_depth: [0; CHUNK_AREA],
+            water_y: [i32::MAX; CHUNK_AREA],
+        };
+        let base_x = chunk_x as i32 * CHUNK_SIZE as i32;
+        let base_z = chunk_z as i32 * CHUNK_SIZE as i32;
+
+        if !self.shape.needs_regions() {
+            for local_x in 0..CHUNK_SIZE as i32 {
+                for local_z in 0..CHUNK_SIZE as i32 {
+                    let idx = local_x as usize + local_z as usize * CHUNK_SIZE;
+                    column.surface_y[idx] = sample_surface_height(
+                        base_x + local_x,
+                        base_z + local_z,
+                        self.floor_level,
+                        &self.perlin,
+                    );
+                }
+            }
+            return column;
+        }
+
+        if base_x + CHUNK_SIZE as i32 > self.dim || base_z + CHUNK_SIZE as i32 > self.dim {
+            return column;
+        }
+        if self.shaped.is_none() {
+            self.shaped = Some(self.shape_world());
+        }
+        let region = self.shaped.as_ref().expect("world shaped");
+
+        for local_x in 0..CHUNK_SIZE as i32 {
+            for local_z in 0..CHUNK_SIZE as i32 {
+                let idx = local_x as usize + local_z as usize * CHUNK_SIZE;
+                let region_idx =
+                    (base_x + local_x) as usize + (base_z + local_z) as usize * self.dim as usize;
+                column.surface_y[idx] = region.surface_y[region_idx];
+                column.sediment_depth[idx] = region.sediment_depth[region_idx];
+                column.water_y[idx] = region.water_y[region_idx];
+            }
+        }
+        column
+    }
+
+    fn shape_world(&self) -> ShapedRegion {
+        let padded = (self.dim + SHAPING_MARGIN * 2) as usize;
+        let origin_x = -SHAPING_MARGIN;
+        let origin_z = -SHAPING_MARGIN;
+
+        // Region passes work on "up is positive" elevations, the negated surface offset.
+        let mut original = vec![0.0f32; padded * padded];
+        for z in 0..padded {
+            for x in 0..padded {
+                let offset =
+                    sample_surface_offset(origin_x + x as i32, origin_z + z as i32, &self.perlin);
+                original[x + z * padded] = -offset as f32;
+            }
+        }
+
+        let mut shaped = original.clone();
+        if let Some(settings) = self.shape.erosion {
+            let iterations =
+                settings.iterations as u64 * (padded * padded) as u64 / EROSION_REFERENCE_AREA;
+            let settings = ErosionSettings {
+                iterations: iterations as u32,
+                ..settings
+            };
+            let erosion_seed = (self.seed as u64) << 32;
+            erosion::erode_heightfield(&mut shaped, padded, padded, &settings, erosion_seed);
+        }
+        let eroded = shaped.clone();
+        let water_map = self.shape.hydrology.map(|settings| {
+            let sea_elevation = (self.floor_level - self.water_level) as f32;
+            hydrology::carve_rivers_and_lakes(&mut shaped, padded, padded, sea_elevation, &settings)
+        });
+
+        let size = self.dim as usize;
+        let margin = SHAPING_MARGIN as usize;
+        let mut region = ShapedRegion {
+            surface_y: vec![self.floor_level; size * size],
+            sediment_depth: vec![0; size * size],
+            water_y: vec![i32::MAX; size * size],
+        };
+        for z in 0..size {
+            for x in 0..size {
+                let idx = x + z * size;
+                let padded_idx = (x + margin) + (z + margin) * padded;
+                let offset = (-shaped[padded_idx]).round() as i32;
+                let surface_y =
+                    self.floor_level + offset.clamp(MIN_SURFACE_OFFSET, MAX_SURFACE_OFFSET);
+                region.surface_y[idx] = surface_y;
+
+                let deposited = (eroded[padded_idx] - original[padded_idx]).floor() as i32;
+                region.sediment_depth[idx] = deposited.clamp(0, MAX_SEDIMENT_DEPTH) as u8;
+
+                if let Some(water_map) = &water_map {
+                    let water = water_map.water[padded_idx];
+                    if water.is_finite() {
+                        let water_y = self.floor_level - water.round() as i32;
+                        if water_y < surface_y {
+                            region.water_y[idx] = water_y;
+                        }
+                    }
+                }
+            }
+        }
+        region
+    }
 }
 
 pub struct TerrainGenWorker {
@@ -36,22 +236,14 @@ pub struct TerrainGenWorker {
 }
 
 impl TerrainGenWorker {
-    pub fn new(floor_level: i32) -> Self {
+    pub fn new(mut heightfield: TerrainHeightfield) -> Self {
         let (request_tx, request_rx) = unbounded::<TerrainRequest>();
         let (result_tx, result_rx) = unbounded::<TerrainColumnHeights>();
 
         std::thread::spawn(move || {
-            let perlin = Perlin::new(TERRAIN_SEED);
             while let Ok((chunk_x, chunk_z)) = request_rx.recv() {
-                let surface_y = build_surface_heights(chunk_x, chunk_z, floor_level, &perlin);
-                if result_tx
-                    .send(TerrainColumnHeights {
-                        chunk_x,
-                        chunk_z,
-                        surface_y,
-                    })
-                    .is_err()
-                {
+                let column = heightfield.build_surface_heights(chunk_x, chunk_z);
+                if result_tx.send(column).is_err() {
                     break;
                 }
             }
@@ -90,29 +282,13 @@ impl TerrainGenWorker {
     }
 }
 
-fn build_surface_heights(
-    chunk_x: u32,
-    chunk_z: u32,
-    floor_level: i32,
-    perlin: &Perlin,
-) -> [i32; CHUNK_AREA] {
-    let mut surface_y = [floor_level; CHUNK_AREA];
-    let base_x = chunk_x as i32 * CHUNK_SIZE as i32;
-    let base_z = chunk_z as i32 * CHUNK_SIZE as i32;
-
-    for local_x in 0..CHUNK_SIZE as i32 {
-        for local_z in 0..CHUNK_SIZE as i32 {
-            let world_x = base_x + local_x;
-            let world_z = base_z + local_z;
-            let idx = local_x as usize + local_z as usize * CHUNK_SIZE;
-            surface_y[idx] = sample_surface_height(world_x, world_z, floor_level, perlin);
-        }
-    }
-
-    surface_y
+pub fn sample_surface_height(world_x: i32, world_z: i32, floor_level: i32, perlin: &Perlin) -> i32 {
+    let offset = sample_surface_offset(world_x, world_z, perlin).round() as i32;
+    floor_level + offset.clamp(MIN_SURFACE_OFFSET, MAX_SURFACE_OFFSET)
 }
 
-pub fn sample_surface_height(world_x: i32, world_z: i32, floor_level: i32, perlin: &Perlin) -> i32 {
+/// Unclamped surface offset from the floor; negative values are higher ground.
+fn sample_surface_offset(world_x: i32, world_z: i32, perlin: &Perlin) -> f64 {
     let x = world_x as f64;
     let z = world_z as f64;
 
@@ -132,6 +308,86 @@ pub fn sample_surface_height(world_x: i32, world_z: i32, floor_level: i32, perli
     ]);
     let ridge = (1.0 - ridge_raw.abs()) * TERRAIN_RIDGE_AMP;
 
-    let offset = (TERRAIN_BASE_OFFSET + macro_shape + detail + micro + ridge).round() as i32;
-    floor_level + offset.clamp(MIN_SURFACE_OFFSET, MAX_SURFACE_OFFSET)
+    TERRAIN_BASE_OFFSET + macro_shape + detail + micro + ridge
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::World;
+
+    const TEST_WORLD_SIZE: usize = 256;
+    const TEST_CHUNKS: u32 = 4;
+
+    /// Surface and water heights of a `TEST_CHUNKS`² block of columns, in the
+    /// order given, as a grid indexed `x + z * side`.
+    fn column_grid(
+        heightfield: &mut TerrainHeightfield,
+        order: impl Iterator<Item = (u32, u32)>,
+    ) -> (Vec<i32>, Vec<i32>) {
+        let side = TEST_CHUNKS as usize * CHUNK_SIZE;
+        let mut surface_y = vec![0; side * side];
+        let mut water_y = vec![i32::MAX; side * side];
+        for (chunk_x, chunk_z) in order {
+            let column = heightfield.build_surface_heights(chunk_x, chunk_z);
+            for local_z in 0..CHUNK_SIZE {
+                for local_x in 0..CHUNK_SIZE {
+                    let x = chunk_x as usize * CHUNK_SIZE + local_x;
+                    let z = chunk_z as usize * CHUNK_SIZE + local_z;
+                    surface_y[x + z * side] = column.surface_y[local_x + local_z * CHUNK_SIZE];
+                    water_y[x + z * side] = column.water_y[local_x + local_z * CHUNK_SIZE];
+                }
+            }
+        }
+        (surface_y, water_y)
+    }
+
+    #[test]
+    fn columns_match_in_any_order_and_stay_continuous_across_chunk_borders() {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        world.terrain_shape = world.terrain_shape.with_erosion(true);
+        let chunks = || (0..TEST_CHUNKS).flat_map(|x| (0..TEST_CHUNKS).map(move |z| (x, z)));
+        // The sync path walks columns in order; the worker gets them in
+        // whatever order the camera asks, from its own heightfield.
+        let sync = column_grid(&mut world.new_terrain_heightfield(), chunks());
+        let worker = column_grid(
+            &mut world.new_terrain_heightfield(),
+            chunks().collect::<Vec<_>>().into_iter().rev(),
+        );
+        assert!(sync == worker, "column order changed the terrain");
+
+        // The step between neighbors in different chunk columns must look like
+        // any other step nearby, for both axes.
+        let side = TEST_CHUNKS as usize * CHUNK_SIZE;
+        let (surface_y, water_y) = &sync;
+        for (grid, name) in [(surface_y, "surface_y"), (water_y, "water_y")] {
+            let mut interior_max = 0;
+            let mut border_max = 0;
+            for a in 0..side {
+                for b in 1..side {
+                    for (here, before) in [
+                        (grid[b + a * side], grid[b - 1 + a * side]),
+                        (grid[a + b * side], grid[a + (b - 1) * side]),
+                    ] {
+                        if here == i32::MAX || before == i32::MAX {
+                            continue;
+                        }
+                        let step = (here - before).abs();
+                        if b % CHUNK_SIZE == 0 {
+                            border_max = border_max.max(step);
+                        } else {
+                            interior_max = interior_max.max(step);
+                        }
+                    }
+                }
+            }
+            assert!(
+                border_max <= interior_max,
+                "{} jumps by {} at a chunk border but at most {} elsewhere",
+                name,
+                border_max,
+                interior_max
+            );
+        }
+    }
 }
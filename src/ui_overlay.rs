@@ -1,9 +1,11 @@
 use glam::Vec2;
 use raylib::prelude::*;
+use std::sync::atomic::Ordering;
 
+use crate::console::Console;
+use crate::frame_history::{FrameHistory, Stage, FRAME_HISTORY_LEN};
 use crate::raymarch::{MAX_RAY_STEPS, MAX_STEP_BUDGET, MIN_STEP_BUDGET};
 use crate::state::{Mode, State};
-use crate::DIMS;
 
 pub struct UiLayout {
     pub panel: Rectangle,
@@ -15,6 +17,16 @@ pub struct UiLayout {
     pub fov_inc: Rectangle,
 }
 
+const GRAPH_BAR_WIDTH: i32 = 2;
+const GRAPH_HEIGHT: i32 = 90;
+const GRAPH_MARKER_HEIGHT: i32 = 3;
+/// Frame time of the dashed reference line in the graph (60 FPS).
+const GRAPH_REFERENCE_MS: f32 = 1000.0 / 60.0;
+const REUSED_RENDER_COLOR: Color = Color::new(90, 170, 255, 255);
+const CHUNK_GEN_COLOR: Color = Color::new(240, 210, 60, 255);
+const CONSOLE_FONT_SIZE: i32 = 18;
+const CONSOLE_LINE_HEIGHT: i32 = 20;
+
 pub fn ui_layout(screen_width: i32, _screen_height: i32) -> UiLayout {
     let screen_w = screen_width.max(1) as f32;
     let screen_h = _screen_height.max(1) as f32;
@@ -127,9 +139,21 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         Color::new(180, 180, 180, 255),
     );
 
+    let orbit_label;
     let mode_label = match state.mode {
-        Mode::Orbit => "Orbit",
+        Mode::Orbit => {
+            orbit_label = format!(
+                "Orbit r{:.0} {:+.2}/s{}",
+                state.orbit.radius,
+                state.orbit.angular_speed,
+                if state.orbit.paused { " II" } else { "" }
+            );
+            &orbit_label
+        }
         Mode::Fly => "Fly",
+        Mode::SixDof => "6-DOF",
+        Mode::Walk if state.player.in_water => "Walk (swimming)",
+        Mode::Walk => "Walk",
     };
 
     d.draw_text("Perf Overlay", text_x, panel_y + pad - 2, 18, Color::WHITE);
@@ -141,12 +165,27 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         Color::GREEN,
     );
     d.draw_text(
-        &format!("Scale: {}", state.resolution_scale.label()),
+        &format!("Scale: {}", state.render_scale_label()),
         text_x + (layout.panel.width as i32 / 2) - 8,
         panel_y + pad + 44,
         18,
         Color::WHITE,
     );
+    d.draw_text(
+        if state.xray {
+            "X-Ray: ON"
+        } else {
+            "X-Ray: OFF"
+        },
+        text_x,
+        panel_y + pad + 44,
+        18,
+        if state.xray {
+            Color::ORANGE
+        } else {
+            Color::WHITE
+        },
+    );
     d.draw_text(
         &format!("Mode: {}", mode_label),
         text_x + (layout.panel.width as i32 / 2) - 8,
@@ -177,7 +216,12 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         Color::WHITE,
     );
     d.draw_text(
-        &format!("Render Scale: {}", state.resolution_scale.label()),
+        &format!(
+            "Render Scale: {} {}  {}",
+            state.render_scale_label(),
+            state.upscaler.label(),
+            state.projection.label()
+        ),
         text_x,
         metric_start_y,
         16,
@@ -185,8 +229,12 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
     );
     d.draw_text(
         &format!(
-            "Render Res: {}x{} / {}x{}",
-            state.render_width, state.render_height, DIMS.x, DIMS.y
+            "Render Res: {}x{} / {}x{}  SSAA: {}",
+            state.render_width,
+            state.render_height,
+            state.window_dims.x,
+            state.window_dims.y,
+            state.supersampling.label()
         ),
         text_x,
         metric_start_y + 20,
@@ -228,7 +276,10 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         Color::new(200, 200, 200, 255),
     );
     d.draw_text(
-        &format!("Empty Chunk Skips: {}", stats.empty_chunk_skips),
+        &format!(
+            "Empty Chunk Skips: {}  Secondary: {}/{}",
+            stats.empty_chunk_skips, stats.secondary_rays, stats.secondary_voxel_steps
+        ),
         text_x,
         metric_start_y + 120,
         16,
@@ -249,12 +300,13 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
     );
     d.draw_text(
         &format!(
-            "Render Reuse: {}",
+            "Render Reuse: {}  View: {}",
             if state.last_frame_timings.reused_render {
                 "ON"
             } else {
                 "OFF"
-            }
+            },
+            state.debug_view.label()
         ),
         text_x,
         metric_start_y + 160,
@@ -262,6 +314,26 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         Color::new(200, 200, 200, 255),
     );
 
+    if let Some(job) = &state.path_export {
+        let frame_count = job.progress.frame_count.load(Ordering::Relaxed);
+        let status = if frame_count == 0 {
+            "Path export: generating terrain".to_string()
+        } else {
+            format!(
+                "Path export: frame {}/{}",
+                job.progress.frames_done.load(Ordering::Relaxed),
+                frame_count
+            )
+        };
+        d.draw_text(
+            &status,
+            text_x,
+            panel_y + layout.panel.height as i32 + 8,
+            18,
+            CHUNK_GEN_COLOR,
+        );
+    }
+
     draw_button(d, layout.dist_dec, "-");
     draw_button(d, layout.dist_inc, "+");
     draw_button(d, layout.step_dec, "-");
@@ -270,7 +342,7 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
     draw_button(d, layout.fov_inc, "+");
 
     d.draw_text(
-        "Keys: Tab, [-]/[+], [,]/[.] StepBudget, [[/]], F1..F6 Scale, F7/F8 Gen, F12 Shot, Backspace",
+        &state.bindings.key_hint(),
         16,
         screen_height - 28,
         18,
@@ -293,8 +365,189 @@ pub fn draw_ui_overlay(state: &State, d: &mut RaylibDrawHandle) {
         },
     );
 
+    draw_frame_graph(&state.frame_history, d, 16, screen_height - 48);
+
     let cx = screen_width / 2;
     let cy = screen_height / 2;
     d.draw_line(cx - 8, cy, cx + 8, cy, Color::GREEN);
     d.draw_line(cx, cy - 8, cx, cy + 8, Color::GREEN);
+
+    if state.console.open {
+        draw_console(&state.console, d, screen_width, screen_height);
+    }
+}
+
+fn stage_color(stage: Stage) -> Color {
+    match stage {
+        Stage::Simulation => Color::new(110, 200, 110, 255),
+        Stage::Raymarch => Color::new(230, 90, 80, 255),
+        Stage::Upload => Color::new(170, 120, 230, 255),
+        Stage::Other => Color::new(150, 150, 150, 255),
+    }
+}
+
+/// Stacked per-stage bars for recent frames, oldest on the left, with
+/// reused-render and chunk-generation markers underneath and min/avg/p99
+/// per stage to the right. `bottom` is the y of the lowest marker row.
+fn draw_frame_graph(history: &FrameHistory, d: &mut RaylibDrawHandle, x: i32, bottom: i32) {
+    let graph_width = FRAME_HISTORY_LEN as i32 * GRAPH_BAR_WIDTH;
+    let markers_top = bottom - 2 * GRAPH_MARKER_HEIGHT - 1;
+    let graph_bottom = markers_top - 2;
+    let graph_top = graph_bottom - GRAPH_HEIGHT;
+    let stats_x = x + graph_width + 12;
+    d.draw_rectangle(
+        x - 6,
+        graph_top - 6,
+        graph_width + 300,
+        bottom - graph_top + 12,
+        Color::new(0, 0, 0, 170),
+    );
+
+    let frame_stats = history.stats(|f| f.frame_ms);
+    let scale_ms = (frame_stats.p99_ms * 1.25).max(GRAPH_REFERENCE_MS * 1.25);
+    let px_per_ms = GRAPH_HEIGHT as f32 / scale_ms;
+    for (index, frame) in history.iter().enumerate() {
+        let bar_x = x + index as i32 * GRAPH_BAR_WIDTH;
+        let mut stacked_ms = 0.0;
+        for stage in Stage::ALL {
+            let ms = stage.ms(frame);
+            let y0 = graph_bottom - (stacked_ms * px_per_ms) as i32;
+            stacked_ms = (stacked_ms + ms).min(scale_ms);
+            let y1 = graph_bottom - (stacked_ms * px_per_ms) as i32;
+            if y0 > y1 {
+                d.draw_rectangle(bar_x, y1, GRAPH_BAR_WIDTH, y0 - y1, stage_color(stage));
+            }
+        }
+        if frame.reused_render {
+            d.draw_rectangle(
+                bar_x,
+                markers_top,
+                GRAPH_BAR_WIDTH,
+                GRAPH_MARKER_HEIGHT,
+                REUSED_RENDER_COLOR,
+            );
+        }
+        if frame.chunk_columns_applied > 0 {
+            d.draw_rectangle(
+                bar_x,
+                markers_top + GRAPH_MARKER_HEIGHT + 1,
+                GRAPH_BAR_WIDTH,
+                GRAPH_MARKER_HEIGHT,
+                CHUNK_GEN_COLOR,
+            );
+        }
+    }
+
+    let reference_y = graph_bottom - (GRAPH_REFERENCE_MS * px_per_ms) as i32;
+    for dash_x in (x..x + graph_width).step_by(8) {
+        d.draw_line(
+            dash_x,
+            reference_y,
+            dash_x + 4,
+            reference_y,
+            Color::new(220, 220, 220, 160),
+        );
+    }
+    d.draw_text(
+        &format!("{:.1} ms", scale_ms),
+        x,
+        graph_top,
+        12,
+        Color::new(200, 200, 200, 255),
+    );
+
+    d.draw_text(
+        "ms     min    avg    p99",
+        stats_x,
+        graph_top - 4,
+        14,
+        Color::new(200, 200, 200, 255),
+    );
+    d.draw_text(
+        "reused",
+        stats_x + 210,
+        graph_top - 4,
+        14,
+        REUSED_RENDER_COLOR,
+    );
+    d.draw_text(
+        "chunk gen",
+        stats_x + 210,
+        graph_top + 12,
+        14,
+        CHUNK_GEN_COLOR,
+    );
+
+    let mut line_y = graph_top + 12;
+    for stage in Stage::ALL {
+        let stats = history.stats(|f| stage.ms(f));
+        d.draw_text(
+            &format!(
+                "{:<5} {:>6.2} {:>6.2} {:>6.2}",
+                stage.label(),
+                stats.min_ms,
+                stats.avg_ms,
+                stats.p99_ms
+            ),
+            stats_x,
+            line_y,
+            16,
+            stage_color(stage),
+        );
+        line_y += 16;
+    }
+    d.draw_text(
+        &format!(
+            "Frame {:>6.2} {:>6.2} {:>6.2}",
+            frame_stats.min_ms, frame_stats.avg_ms, frame_stats.p99_ms
+        ),
+        stats_x,
+        line_y,
+        16,
+        Color::WHITE,
+    );
+}
+
+/// Covers the top of the screen: the newest log lines above the input line.
+fn draw_console(
+    console: &Console,
+    d: &mut RaylibDrawHandle,
+    screen_width: i32,
+    screen_height: i32,
+) {
+    let height = (screen_height * 2 / 5).max(CONSOLE_LINE_HEIGHT * 3);
+    d.draw_rectangle(0, 0, screen_width, height, Color::new(0, 0, 0, 215));
+    d.draw_line(
+        0,
+        height,
+        screen_width,
+        height,
+        Color::new(180, 180, 180, 255),
+    );
+
+    let input_y = height - CONSOLE_LINE_HEIGHT - 4;
+    d.draw_text(
+        &format!("> {}_", console.input),
+        10,
+        input_y,
+        CONSOLE_FONT_SIZE,
+        Color::WHITE,
+    );
+
+    let visible_lines = (input_y / CONSOLE_LINE_HEIGHT - 1).max(0) as usize;
+    let first = console.log.len().saturating_sub(visible_lines);
+    for (row, line) in console.log[first..].iter().enumerate() {
+        let color = if line.starts_with("error:") {
+            Color::ORANGE
+        } else {
+            Color::new(200, 200, 200, 255)
+        };
+        d.draw_text(
+            line,
+            10,
+            input_y - (console.log.len() - first - row) as i32 * CONSOLE_LINE_HEIGHT,
+            CONSOLE_FONT_SIZE,
+            color,
+        );
+    }
 }
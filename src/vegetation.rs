@@ -0,0 +1,347 @@
+use glam::IVec3;
+use raylib::color::Color;
+
+use crate::world::{DecorationMaterialIds, MaterialId, World, CHUNK_SIZE};
+
+const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
+const AIR_MATERIAL: MaterialId = 0;
+
+// Trees sit on a jittered grid so trunks never crowd each other.
+const TREE_CELL_SIZE: i32 = 7;
+const TREE_CELL_CHANCE: u32 = 55; // percent of cells that grow a tree
+const GRASS_TUFT_CHANCE: u32 = 18; // percent of open grass voxels
+
+// Canopies may spill into neighboring columns, but never past them.
+const MAX_CANOPY_RADIUS: i32 = 3;
+
+const TREE_SALT: u32 = 0x7EE5;
+const TUFT_SALT: u32 = 0x70F7;
+const LEAF_SALT: u32 = 0x1EAF;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum TreeSpecies {
+    Oak,
+    Birch,
+    Pine,
+    Shrub,
+}
+
+#[inline]
+fn decoration_hash(seed: u32, x: i32, z: i32, salt: u32) -> u32 {
+    let mut h = seed ^ salt.wrapping_mul(0x27D4EB2F);
+    h = h.wrapping_add((x as u32).wrapping_mul(0x9E3779B1));
+    h = h.rotate_left(15) ^ (z as u32).wrapping_mul(0x85EBCA77);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0xC2B2AE3D);
+    h ^ (h >> 16)
+}
+
+/// Species depends on altitude first, then on the per-tree hash.
+fn pick_species(height_above_floor: i32, hash: u32) -> TreeSpecies {
+    let roll = (hash >> 20) % 100;
+    if height_above_floor <= 10 {
+        if roll < 60 {
+            TreeSpecies::Shrub
+        } else {
+            TreeSpecies::Oak
+        }
+    } else if height_above_floor <= 22 {
+        if roll < 55 {
+            TreeSpecies::Oak
+        } else if roll < 85 {
+            TreeSpecies::Birch
+        } else {
+            TreeSpecies::Shrub
+        }
+    } else if roll < 70 {
+        TreeSpecies::Pine
+    } else {
+        TreeSpecies::Birch
+    }
+}
+
+/// Unique per material so that overlapping structures resolve to the same voxel
+/// (the higher priority wins) no matter which column decorates first.
+fn decoration_priority(ids: &DecorationMaterialIds, material: MaterialId) -> Option<u8> {
+    if material == AIR_MATERIAL {
+        Some(0)
+    } else if material == ids.grass_tuft {
+        Some(1)
+    } else if material == ids.shrub_leaves {
+        Some(2)
+    } else if material == ids.pine_leaves {
+        Some(3)
+    } else if material == ids.birch_leaves {
+        Some(4)
+    } else if material == ids.oak_leaves {
+        Some(5)
+    } else if material == ids.pine_log {
+        Some(6)
+    } else if material == ids.birch_log {
+        Some(7)
+    } else if material == ids.oak_log {
+        Some(8)
+    } else {
+        None
+    }
+}
+
+impl World {
+    pub(crate) fn decoration_material_ids(&mut self) -> DecorationMaterialIds {
+        if let Some(ids) = self.decoration_materials {
+            return ids;
+        }
+        let ids = DecorationMaterialIds {
+            oak_log: self.intern_material(Color::new(102, 76, 52, 255)),
+            oak_leaves: self.intern_material(Color::new(58, 140, 58, 215)),
+            birch_log: self.intern_material(Color::new(214, 208, 196, 255)),
+            birch_leaves: self.intern_material(Color::new(128, 178, 72, 200)),
+            pine_log: self.intern_material(Color::new(74, 52, 38, 255)),
+            pine_leaves: self.intern_material(Color::new(34, 96, 64, 230)),
+            shrub_leaves: self.intern_material(Color::new(88, 150, 60, 255)),
+            grass_tuft: self.intern_material(Color::new(92, 200, 110, 255)),
+        };
+        self.decoration_materials = Some(ids);
+        ids
+    }
+
+    /// Decoration is deferred until all 8 neighboring columns are painted, so
+    /// spilled canopies can never be overwritten by later terrain fills.
+    pub(crate) fn queue_column_decoration(
+        &mut self,
+        chunk_x: u32,
+        chunk_z: u32,
+        surface_y: &[i32; CHUNK_AREA],
+    ) {
+        self.pending_decorations
+            .insert((chunk_x, chunk_z), *surface_y);
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let nx = chunk_x as i32 + dx;
+                let nz = chunk_z as i32 + dz;
+                if nx < 0 || nz < 0 || nx >= self.chunk_dim as i32 || nz >= self.chunk_dim as i32 {
+                    continue;
+                }
+                self.try_decorate_column(nx as u32, nz as u32);
+            }
+        }
+    }
+
+    fn try_decorate_column(&mut self, chunk_x: u32, chunk_z: u32) {
+        if !self.pending_decorations.contains_key(&(chunk_x, chunk_z)) {
+            return;
+        }
+        for dz in -1..=1 {
+            for dx in -1..=1 {
+                let nx = chunk_x as i32 + dx;
+                let nz = chunk_z as i32 + dz;
+                if nx < 0 || nz < 0 || nx >= self.chunk_dim as i32 || nz >= self.chunk_dim as i32 {
+                    continue;
+                }
+                if !self.is_terrain_column_generated(nx as u32, nz as u32) {
+                    return;
+                }
+            }
+        }
+        let Some(surface_y) = self.pending_decorations.remove(&(chunk_x, chunk_z)) else {
+            return;
+        };
+        self.decorate_column(chunk_x, chunk_z, &surface_y);
+    }
+
+    fn decorate_column(&mut self, chunk_x: u32, chunk_z: u32, surface_y: &[i32; CHUNK_AREA]) {
+        debug_assert!(MAX_CANOPY_RADIUS < CHUNK_SIZE as i32);
+        let terrain = self.terrain_material_ids();
+        let ids = self.decoration_material_ids();
+        let floor = self.get_floor_level() as i32;
+        let lower_void = self.get_lower_void() as i32;
+        let col_min_x = chunk_x as i32 * CHUNK_SIZE as i32;
+        let col_min_z = chunk_z as i32 * CHUNK_SIZE as i32;
+        let col_max_x = col_min_x + CHUNK_SIZE as i32 - 1;
+        let col_max_z = col_min_z + CHUNK_SIZE as i32 - 1;
+
+        for local_x in 0..CHUNK_SIZE as i32 {
+            for local_z in 0..CHUNK_SIZE as i32 {
+                let idx = local_x as usize + local_z as usize * CHUNK_SIZE;
+                let world_x = col_min_x + local_x;
+                let world_z = col_min_z + local_z;
+                let surface = surface_y[idx].clamp(0, lower_void);
+                if self.get_voxel_material_unchecked_i32(world_x, surface, world_z) != terrain.grass
+                {
+                    continue;
+                }
+                if decoration_hash(self.seed, world_x, world_z, TUFT_SALT) % 100 < GRASS_TUFT_CHANCE
+                {
+                    self.place_decoration_voxel(
+                        &ids,
+                        world_x,
+                        surface - 1,
+                        world_z,
+                        ids.grass_tuft,
+                    );
+                }
+            }
+        }
+
+        let cell_min_x = col_min_x.div_euclid(TREE_CELL_SIZE);
+        let cell_max_x = col_max_x.div_euclid(TREE_CELL_SIZE);
+        let cell_min_z = col_min_z.div_euclid(TREE_CELL_SIZE);
+        let cell_max_z = col_max_z.div_euclid(TREE_CELL_SIZE);
+        for cell_x in cell_min_x..=cell_max_x {
+            for cell_z in cell_min_z..=cell_max_z {
+                let hash = decoration_hash(self.seed, cell_x, cell_z, TREE_SALT);
+                if hash % 100 >= TREE_CELL_CHANCE {
+                    continue;
+                }
+                // Keep trunks off the cell border so neighboring trees stay apart.
+                let jitter_range = (TREE_CELL_SIZE - 2) as u32;
+                let trunk_x = cell_x * TREE_CELL_SIZE + 1 + ((hash >> 8) % jitter_range) as i32;
+                let trunk_z = cell_z * TREE_CELL_SIZE + 1 + ((hash >> 14) % jitter_range) as i32;
+                if trunk_x < col_min_x
+                    || trunk_x > col_max_x
+                    || trunk_z < col_min_z
+                    || trunk_z > col_max_z
+                {
+                    continue;
+                }
+
+                let idx =
+                    (trunk_x - col_min_x) as usize + (trunk_z - col_min_z) as usize * CHUNK_SIZE;
+                let surface = surface_y[idx].clamp(0, lower_void);
+                if self.get_voxel_material_unchecked_i32(trunk_x, surface, trunk_z) != terrain.grass
+                {
+                    continue;
+                }
+                let species = pick_species(floor - surface, hash);
+                self.plant_tree(&ids, species, trunk_x, surface, trunk_z, hash);
+            }
+        }
+    }
+
+    fn plant_tree(
+        &mut self,
+        ids: &DecorationMaterialIds,
+        species: TreeSpecies,
+        x: i32,
+        surface: i32,
+        z: i32,
+        hash: u32,
+    ) {
+        let size_roll = ((hash >> 24) % 3) as i32;
+        let (log, leaves, trunk_height) = match species {
+            TreeSpecies::Oak => (ids.oak_log, ids.oak_leaves, 4 + size_roll),
+            TreeSpecies::Birch => (ids.birch_log, ids.birch_leaves, 6 + size_roll),
+            TreeSpecies::Pine => (ids.pine_log, ids.pine_leaves, 7 + size_roll),
+            TreeSpecies::Shrub => (ids.oak_log, ids.shrub_leaves, 1),
+        };
+
+        // The trunk must fit in open air (or other decorations); decide from
+        // terrain alone so the outcome does not depend on decoration order.
+        for i in 1..=trunk_height {
+            if surface - i < 0 || !self.is_decoration_open(ids, x, surface - i, z) {
+                return;
+            }
+        }
+        for i in 1..=trunk_height {
+            self.place_decoration_voxel(ids, x, surface - i, z, log);
+        }
+
+        let top = surface - trunk_height;
+        let crown = IVec3::new(x, top - 1, z);
+        match species {
+            TreeSpecies::Oak => self.place_leaf_blob(ids, leaves, crown, 2.6, 2.2),
+            TreeSpecies::Birch => self.place_leaf_blob(ids, leaves, crown, 2.0, 2.8),
+            TreeSpecies::Shrub => self.place_leaf_blob(ids, leaves, crown, 1.6, 1.3),
+            TreeSpecies::Pine => {
+                // Tiered cone: narrow at the tip, widest just above the ground.
+                let canopy_height = trunk_height - 1;
+                for level in 0..=canopy_height {
+                    let y = top - 1 + level;
+                    let mut radius = 0.6 + 2.4 * level as f32 / canopy_height as f32;
+                    if level % 3 == 2 {
+                        radius -= 1.0;
+                    }
+                    let reach = (radius.ceil() as i32).min(MAX_CANOPY_RADIUS);
+                    for dx in -reach..=reach {
+                        for dz in -reach..=reach {
+                            if (dx * dx + dz * dz) as f32 <= radius * radius {
+                                self.place_decoration_voxel(ids, x + dx, y, z + dz, leaves);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn place_leaf_blob(
+        &mut self,
+        ids: &DecorationMaterialIds,
+        leaves: MaterialId,
+        center: IVec3,
+        radius_xz: f32,
+        radius_y: f32,
+    ) {
+        let reach_xz = (radius_xz.ceil() as i32).min(MAX_CANOPY_RADIUS);
+        let reach_y = radius_y.ceil() as i32;
+        for dy in -reach_y..=reach_y {
+            for dx in -reach_xz..=reach_xz {
+                for dz in -reach_xz..=reach_xz {
+                    let nx = dx as f32 / radius_xz;
+                    let ny = dy as f32 / radius_y;
+                    let nz = dz as f32 / radius_xz;
+                    let d = nx * nx + ny * ny + nz * nz;
+                    if d > 1.0 {
+                        continue;
+                    }
+                    let x = center.x + dx;
+                    let y = center.y + dy;
+                    let z = center.z + dz;
+                    // Ragged outer shell so blobs do not read as perfect spheres.
+                    if d > 0.6 && (decoration_hash(self.seed, x ^ (y << 10), z, LEAF_SALT) & 3) == 0
+                    {
+                        continue;
+                    }
+                    self.place_decoration_voxel(ids, x, y, z, leaves);
+                }
+            }
+        }
+    }
+
+    fn is_decoration_open(&self, ids: &DecorationMaterialIds, x: i32, y: i32, z: i32) -> bool {
+        if !self.decoration_in_bounds(x, y, z) {
+            return false;
+        }
+        let existing = self.get_voxel_material_unchecked_i32(x, y, z);
+        decoration_priority(ids, existing).is_some()
+    }
+
+    fn place_decoration_voxel(
+        &mut self,
+        ids: &DecorationMaterialIds,
+        x: i32,
+        y: i32,
+        z: i32,
+        material: MaterialId,
+    ) {
+        if !self.decoration_in_bounds(x, y, z) {
+            return;
+        }
+        let existing = self.get_voxel_material_unchecked_i32(x, y, z);
+        let (Some(existing_priority), Some(new_priority)) = (
+            decoration_priority(ids, existing),
+            decoration_priority(ids, material),
+        ) else {
+            return;
+        };
+        if new_priority > existing_priority {
+            self.set_voxel_material_i32(x, y, z, material);
+        }
+    }
+
+    #[inline]
+    fn decoration_in_bounds(&self, x: i32, y: i32, z: i32) -> bool {
+        let dim = self.dim as i32;
+        x >= 0 && y >= 0 && z >= 0 && x < dim && y < dim && z < dim
+    }
+}
@@ -1,6 +1,35 @@
-use crate::{camera::Camera, UP};
+use crate::camera::Camera;
 use glam::{Vec2, Vec3};
 
+/// World units the viewplane spans per unit of `size` in orthographic mode at zoom 1.
+const ORTHO_UNITS_PER_VIEWPLANE_UNIT: f32 = 16.0;
+pub const MIN_ORTHO_ZOOM: f32 = 0.1;
+pub const MAX_ORTHO_ZOOM: f32 = 16.0;
+
+/// How primary rays leave the viewplane.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Projection {
+    /// Rays converge on `camera.pos`.
+    Perspective,
+    /// Rays run parallel to `camera.dir()` from the viewplane; higher zoom shows less world.
+    Orthographic { zoom: f32 },
+}
+
+impl Projection {
+    pub fn label(self) -> String {
+        match self {
+            Self::Perspective => "Persp".to_string(),
+            Self::Orthographic { zoom } => format!("Ortho x{:.2}", zoom),
+        }
+    }
+}
+
+/// Viewplane size giving a vertical field of view of `fov_y_deg` at `distance`.
+pub fn size_for_fov_y(fov_y_deg: f32, distance: f32, aspect: f32) -> Vec2 {
+    let height = (fov_y_deg.to_radians() * 0.5).tan() * distance.max(0.001) * 2.0;
+    Vec2::new(height * aspect, height)
+}
+
 #[derive(Debug)]
 pub struct Viewplane {
     pub size: Vec2,
@@ -13,25 +42,58 @@ impl Viewplane {
         viewplane
     }
 
-    pub fn top_left_corner_from_perspective_of(&self, camera: &Camera) -> Vec3 {
-        let half_size = self.size / 2.0;
-        let center = camera.pos + camera.dir * camera.viewplane_distance;
+    pub fn top_left_corner_with_extent(&self, camera: &Camera, extent: Vec2) -> Vec3 {
+        let half_size = extent / 2.0;
+        let center = camera.pos + camera.dir() * camera.viewplane_distance;
         let right = self.get_right_from_perspective_of(camera);
         let up = self.get_up_from_perspective_of(camera);
         center - right * half_size.x + up * half_size.y
     }
 
+    /// Origin and direction of the ray through `screen_uv` (`(0, 0)` is the
+    /// top-left corner, `(1, 1)` the bottom-right), matching `draw_voxels`.
+    pub fn ray_through(
+        &self,
+        camera: &Camera,
+        projection: Projection,
+        screen_uv: Vec2,
+    ) -> (Vec3, Vec3) {
+        let extent = self.extent_for(projection);
+        let point = self.top_left_corner_with_extent(camera, extent)
+            + self.get_right_from_perspective_of(camera) * (screen_uv.x * extent.x)
+            + self.get_down_from_perspective_of(camera) * (screen_uv.y * extent.y);
+        match projection {
+            Projection::Perspective => (camera.pos, (point - camera.pos).normalize()),
+            Projection::Orthographic { .. } => (point, camera.dir()),
+        }
+    }
+
     pub fn get_up_from_perspective_of(&self, camera: &Camera) -> Vec3 {
-        let right = self.get_right_from_perspective_of(camera);
-        right.cross(camera.dir).normalize()
+        camera.get_up()
     }
 
     pub fn get_right_from_perspective_of(&self, camera: &Camera) -> Vec3 {
-        camera.dir.cross(UP).normalize()
+        camera.get_right()
     }
 
     pub fn get_down_from_perspective_of(&self, camera: &Camera) -> Vec3 {
-        -self.get_up_from_perspective_of(camera)
+        camera.get_down()
+    }
+
+    /// World-space size of the area primary rays cover.
+    pub fn extent_for(&self, projection: Projection) -> Vec2 {
+        match projection {
+            Projection::Perspective => self.size,
+            Projection::Orthographic { zoom } => {
+                self.size * (ORTHO_UNITS_PER_VIEWPLANE_UNIT / zoom.max(MIN_ORTHO_ZOOM))
+            }
+        }
+    }
+
+    /// Widens or narrows the viewplane to `aspect_ratio`, keeping its height
+    /// and so the vertical field of view.
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.size.x = self.size.y * aspect_ratio;
     }
 
     pub fn validate_aspect_ratio(&self, ratio: f32) {
@@ -3,6 +3,9 @@ use std::collections::HashMap;
 use glam::Vec3;
 use raylib::color::Color;
 
+use crate::ores::{default_ore_table, OreSettings};
+use crate::terrain_worker::{TerrainHeightfield, TerrainShapeSettings, TERRAIN_SEED};
+
 pub type Block = Color;
 pub type MaterialId = u16;
 pub const CHUNK_SIZE: usize = 16;
@@ -10,17 +13,41 @@ pub const CHUNK_SIZE: usize = 16;
 const CHUNK_SHIFT: usize = 4;
 const CHUNK_MASK: i32 = CHUNK_SIZE as i32 - 1;
 const CHUNK_VOLUME: usize = CHUNK_SIZE * CHUNK_SIZE * CHUNK_SIZE;
+const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
 const AIR_COLOR: Color = Color::new(0, 0, 0, 0);
 const AIR_MATERIAL: MaterialId = 0;
+/// Relative extinction of a channel the color is full of (one it lacks gets
+/// this plus 1); lower values tint thick layers more strongly.
+const ABSORPTION_TINT_FLOOR: f32 = 0.5;
+
+/// Alpha 0 keeps the pre-alpha convention of an opaque block, not an invisible one.
+fn is_transparent_color(color: Color) -> bool {
+    color.a > 0 && color.a < 255
+}
+
+/// Extinction whose average over the channels lets `color.a` of the light be
+/// absorbed across one voxel, matching the old per-voxel alpha blend. Channels
+/// the color lacks absorb faster, so deep water darkens toward its own blue.
+fn absorption_for(color: Color) -> Vec3 {
+    if !is_transparent_color(color) {
+        return Vec3::INFINITY;
+    }
+    let alpha = color.a as f32 / 255.0;
+    let rgb = Vec3::new(color.r as f32, color.g as f32, color.b as f32) / 255.0;
+    let tint = Vec3::splat(ABSORPTION_TINT_FLOOR + 1.0) - rgb;
+    -(1.0 - alpha).ln() * tint / tint.element_sum() * 3.0
+}
 
 #[derive(Copy, Clone, Debug)]
 pub struct Material {
     pub color: Color,
     pub is_transparent: bool,
-    pub alpha: f32,
-    pub premul_r: f32,
-    pub premul_g: f32,
-    pub premul_b: f32,
+    /// Beer–Lambert extinction per voxel of ray length, per channel.
+    pub absorption: Vec3,
+    /// Color a transparent medium adds in proportion to the light it absorbs.
+    pub scatter: Vec3,
+    /// Refractive index; 1.0, like air, lets rays pass straight through.
+    pub ior: f32,
 }
 
 #[derive(Copy, Clone, Debug, Default)]
@@ -54,7 +81,19 @@ pub struct FeatureMaterialIds {
     pub glow: MaterialId,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
+pub struct DecorationMaterialIds {
+    pub oak_log: MaterialId,
+    pub oak_leaves: MaterialId,
+    pub birch_log: MaterialId,
+    pub birch_leaves: MaterialId,
+    pub pine_log: MaterialId,
+    pub pine_leaves: MaterialId,
+    pub shrub_leaves: MaterialId,
+    pub grass_tuft: MaterialId,
+}
+
+#[derive(Debug, Clone)]
 struct ChunkData {
     voxels: Option<Vec<MaterialId>>,
     meta: ChunkMeta,
@@ -69,17 +108,27 @@ impl ChunkData {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct World {
     pub dim: usize,
     pub chunk_dim: usize,
     chunks: Vec<ChunkData>,
     terrain_columns_generated: Vec<bool>,
     revision: u64,
+    /// Bumped by user edits such as `fill_box`, not by terrain streaming.
+    pub(crate) edit_revision: u64,
     materials: Vec<Material>,
     material_lookup: HashMap<u32, MaterialId>,
     pub(crate) terrain_materials: Option<TerrainMaterialIds>,
     pub(crate) feature_materials: Option<FeatureMaterialIds>,
+    pub(crate) decoration_materials: Option<DecorationMaterialIds>,
+    pub(crate) pending_decorations: HashMap<(u32, u32), [i32; CHUNK_AREA]>,
+    pub terrain_shape: TerrainShapeSettings,
+    /// Drives terrain noise, erosion, vegetation and ore placement; set before generating.
+    pub seed: u32,
+    pub ores: Vec<OreSettings>,
+    pub(crate) ore_materials: Option<Vec<MaterialId>>,
+    pub(crate) sync_heightfield: Option<TerrainHeightfield>,
 }
 
 impl World {
@@ -97,20 +146,38 @@ impl World {
             chunks: (0..chunk_count).map(|_| ChunkData::new()).collect(),
             terrain_columns_generated: vec![false; chunk_dim * chunk_dim],
             revision: 0,
+            edit_revision: 0,
             materials: vec![Material {
                 color: AIR_COLOR,
                 is_transparent: false,
-                alpha: 0.0,
-                premul_r: 0.0,
-                premul_g: 0.0,
-                premul_b: 0.0,
+                absorption: Vec3::ZERO,
+                scatter: Vec3::ZERO,
+                ior: 1.0,
             }],
             material_lookup,
             terrain_materials: None,
             feature_materials: None,
+            decoration_materials: None,
+            pending_decorations: HashMap::new(),
+            terrain_shape: TerrainShapeSettings::default(),
+            seed: TERRAIN_SEED,
+            ores: default_ore_table(),
+            ore_materials: None,
+            sync_heightfield: None,
         }
     }
 
+    /// Heightfield generator matching this world's floor, sea level and shape settings.
+    pub fn new_terrain_heightfield(&self) -> TerrainHeightfield {
+        TerrainHeightfield::new(
+            self.get_floor_level() as i32,
+            self.get_water_level() as i32,
+            self.seed,
+            self.dim,
+            self.terrain_shape,
+        )
+    }
+
     #[inline]
     pub fn get_material(&self, material_id: MaterialId) -> Material {
         self.materials
@@ -126,19 +193,24 @@ impl World {
         }
 
         let new_id = self.materials.len() as MaterialId;
-        let alpha = color.a as f32 / 255.0;
         self.materials.push(Material {
             color,
-            is_transparent: color.a > 0 && color.a < 255,
-            alpha,
-            premul_r: color.r as f32 * alpha,
-            premul_g: color.g as f32 * alpha,
-            premul_b: color.b as f32 * alpha,
+            is_transparent: is_transparent_color(color),
+            absorption: absorption_for(color),
+            scatter: Vec3::new(color.r as f32, color.g as f32, color.b as f32),
+            ior: 1.0,
         });
         self.material_lookup.insert(key, new_id);
         new_id
     }
 
+    /// Makes a transparent material reflect and refract rays at its surface.
+    pub fn set_material_ior(&mut self, material_id: MaterialId, ior: f32) {
+        if let Some(material) = self.materials.get_mut(material_id as usize) {
+            material.ior = ior;
+        }
+    }
+
     #[inline]
     pub fn chunk_meta(&self, chunk_x: i32, chunk_y: i32, chunk_z: i32) -> Option<ChunkMeta> {
         if chunk_x < 0
@@ -155,6 +227,18 @@ impl World {
         Some(self.chunks[chunk_index].meta)
     }
 
+    /// Raw voxel ids of one chunk; `None` while the chunk is all air.
+    #[cfg(test)]
+    pub(crate) fn chunk_voxels(
+        &self,
+        chunk_x: usize,
+        chunk_y: usize,
+        chunk_z: usize,
+    ) -> Option<&[MaterialId]> {
+        let chunk_index = self.chunk_index(chunk_x, chunk_y, chunk_z);
+        self.chunks[chunk_index].voxels.as_deref()
+    }
+
     #[inline]
     pub fn get_voxel_material_unchecked_i32(&self, x: i32, y: i32, z: i32) -> MaterialId {
         debug_assert!(x >= 0 && y >= 0 && z >= 0);
@@ -174,6 +258,26 @@ impl World {
         voxels[self.voxel_index(in_chunk_x, in_chunk_y, in_chunk_z)]
     }
 
+    /// Material at a voxel; `None` outside the world.
+    pub fn voxel_at(&self, x: i32, y: i32, z: i32) -> Option<MaterialId> {
+        let dim = self.dim as i32;
+        if x < 0 || y < 0 || z < 0 || x >= dim || y >= dim || z >= dim {
+            return None;
+        }
+        Some(self.get_voxel_material_unchecked_i32(x, y, z))
+    }
+
+    /// Terrain water, once terrain generation has interned it.
+    pub fn water_material(&self) -> Option<MaterialId> {
+        self.terrain_materials.map(|ids| ids.water)
+    }
+
+    pub fn is_water_at(&self, pos: Vec3) -> bool {
+        let voxel = pos.floor().as_ivec3();
+        let material = self.voxel_at(voxel.x, voxel.y, voxel.z);
+        material.is_some() && material == self.water_material()
+    }
+
     #[inline]
     pub fn set_voxel_material_i32(&mut self, x: i32, y: i32, z: i32, material_id: MaterialId) {
         if x < 0
@@ -240,6 +344,7 @@ impl World {
         )
     }
 
+    /// Changes whenever any voxel does, streamed terrain included.
     #[inline]
     pub fn revision(&self) -> u64 {
         self.revision
@@ -1,11 +1,27 @@
-use glam::{Vec2, Vec3};
-use noise::Perlin;
+use glam::{IVec3, Vec2, Vec3};
 use raylib::color::Color;
 
-use crate::terrain_worker::sample_surface_height;
+use crate::terrain_worker::TerrainColumnHeights;
 use crate::world::{Block, FeatureMaterialIds, MaterialId, TerrainMaterialIds, World, CHUNK_SIZE};
 
-const CHUNK_AREA: usize = CHUNK_SIZE * CHUNK_SIZE;
+const AIR_MATERIAL: MaterialId = 0;
+const WATER_IOR: f32 = 1.33;
+pub const GLASS_IOR: f32 = 1.5;
+
+/// Names `material_by_name` accepts besides `#rrggbb` and `#rrggbbaa` colors.
+pub const MATERIAL_NAMES: &[&str] = &[
+    "air",
+    "grass",
+    "dirt",
+    "water",
+    "stone",
+    "clay",
+    "basalt",
+    "sandstone",
+    "glass",
+    "glow",
+];
+
 const VERTICAL_TORUS_DEFS: [(f32, f32, f32, f32, f32); 6] = [
     // offset_x, offset_y, offset_z, major_radius, minor_radius
     (-44.0, 7.0, 16.0, 22.0, 4.5),
@@ -87,6 +103,10 @@ impl World {
         self.dim - 2
     }
 
+    pub fn get_water_level(&self) -> usize {
+        self.get_floor_level().saturating_sub(6)
+    }
+
     pub fn gen_floor(&mut self, color: Block) {
         let material = self.intern_material(color);
         let floor_level = self.get_floor_level() as i32;
@@ -97,19 +117,24 @@ impl World {
         }
     }
 
-    pub fn apply_terrain_column_heights(
-        &mut self,
-        chunk_x: u32,
-        chunk_z: u32,
-        surface_y: &[i32; CHUNK_AREA],
-    ) {
-        if chunk_x >= self.chunk_dim as u32 || chunk_z >= self.chunk_dim as u32 {
+    pub fn apply_terrain_column_heights(&mut self, column: &TerrainColumnHeights) {
+        if column.chunk_x >= self.chunk_dim as u32 || column.chunk_z >= self.chunk_dim as u32 {
             return;
         }
-        if self.is_terrain_column_generated(chunk_x, chunk_z) {
+        if self.is_terrain_column_generated(column.chunk_x, column.chunk_z) {
             return;
         }
-        self.paint_terrain_column(chunk_x, chunk_z, surface_y);
+        self.paint_terrain_column(column);
+    }
+
+    /// Generates every terrain column that is still missing, synchronously.
+    pub fn gen_all_terrain_columns(&mut self) {
+        let chunk_dim = self.chunk_dim as u32;
+        for chunk_x in 0..chunk_dim {
+            for chunk_z in 0..chunk_dim {
+                self.gen_terrain_column(chunk_x, chunk_z);
+            }
+        }
     }
 
     pub fn gen_terrain_column(&mut self, chunk_x: u32, chunk_z: u32) {
@@ -120,24 +145,18 @@ impl World {
             return;
         }
 
-        let base_x = chunk_x as i32 * CHUNK_SIZE as i32;
-        let base_z = chunk_z as i32 * CHUNK_SIZE as i32;
-        let floor = self.get_floor_level() as i32;
-        let perlin = Perlin::new(0);
-        let mut surface_y = [floor; CHUNK_AREA];
-
-        for local_x in 0..CHUNK_SIZE as i32 {
-            for local_z in 0..CHUNK_SIZE as i32 {
-                let world_x = base_x + local_x;
-                let world_z = base_z + local_z;
-                let idx = local_x as usize + local_z as usize * CHUNK_SIZE;
-                surface_y[idx] = sample_surface_height(world_x, world_z, floor, &perlin);
-            }
+        if self.sync_heightfield.is_none() {
+            self.sync_heightfield = Some(self.new_terrain_heightfield());
         }
-        self.paint_terrain_column(chunk_x, chunk_z, &surface_y);
+        let column = self
+            .sync_heightfield
+            .as_mut()
+            .expect("sync heightfield initialized")
+            .build_surface_heights(chunk_x, chunk_z);
+        self.paint_terrain_column(&column);
     }
 
-    fn terrain_material_ids(&mut self) -> TerrainMaterialIds {
+    pub(crate) fn terrain_material_ids(&mut self) -> TerrainMaterialIds {
         if let Some(ids) = self.terrain_materials {
             return ids;
         }
@@ -148,6 +167,7 @@ impl World {
             stone: self.intern_material(Color::new(95, 100, 108, 255)),
             clay: self.intern_material(Color::new(138, 116, 100, 255)),
         };
+        self.set_material_ior(ids.water, WATER_IOR);
         self.terrain_materials = Some(ids);
         ids
     }
@@ -162,14 +182,84 @@ impl World {
             glass: self.intern_material(Color::new(180, 220, 255, 95)),
             glow: self.intern_material(Color::new(48, 230, 255, 255)),
         };
+        self.set_material_ior(ids.glass, GLASS_IOR);
         self.feature_materials = Some(ids);
         ids
     }
 
-    fn paint_terrain_column(&mut self, chunk_x: u32, chunk_z: u32, surface_y: &[i32; CHUNK_AREA]) {
+    /// Material called `name` in `MATERIAL_NAMES`, or the one with a hex color.
+    pub fn material_by_name(&mut self, name: &str) -> Option<MaterialId> {
+        let terrain = self.terrain_material_ids();
+        let feature = self.feature_material_ids();
+        let named = match name {
+            "air" => AIR_MATERIAL,
+            "grass" => terrain.grass,
+            "dirt" => terrain.dirt,
+            "water" => terrain.water,
+            "stone" => terrain.stone,
+            "clay" => terrain.clay,
+            "basalt" => feature.basalt,
+            "sandstone" => feature.sandstone,
+            "glass" => feature.glass,
+            "glow" => feature.glow,
+            _ => {
+                let hex = name.strip_prefix('#')?;
+                if !matches!(hex.len(), 6 | 8) || !hex.is_ascii() {
+                    return None;
+                }
+                let channel = |i: usize| {
+                    hex.get(i * 2..i * 2 + 2)
+                        .map_or(Some(255), |digits| u8::from_str_radix(digits, 16).ok())
+                };
+                let color = Color::new(channel(0)?, channel(1)?, channel(2)?, channel(3)?);
+                return Some(if color.a == 0 {
+                    AIR_MATERIAL
+                } else {
+                    self.intern_material(color)
+                });
+            }
+        };
+        Some(named)
+    }
+
+    /// Sets every voxel between `min` and `max` inclusive. Terrain columns
+    /// under the box are generated first so streaming never paints over it.
+    /// Returns the box clipped to the world, or `None` if none of it is inside.
+    pub fn fill_box(
+        &mut self,
+        min: IVec3,
+        max: IVec3,
+        material: MaterialId,
+    ) -> Option<(IVec3, IVec3)> {
+        let last = self.dim as i32 - 1;
+        let lo = min.min(max).max(IVec3::ZERO);
+        let hi = min.max(max).min(IVec3::splat(last));
+        if lo.cmpgt(hi).any() {
+            return None;
+        }
+        let chunk = CHUNK_SIZE as i32;
+        for chunk_x in lo.x / chunk..=hi.x / chunk {
+            for chunk_z in lo.z / chunk..=hi.z / chunk {
+                self.gen_terrain_column(chunk_x as u32, chunk_z as u32);
+            }
+        }
+        for x in lo.x..=hi.x {
+            for y in lo.y..=hi.y {
+                for z in lo.z..=hi.z {
+                    self.set_voxel_material_i32(x, y, z, material);
+                }
+            }
+        }
+        self.edit_revision += 1;
+        Some((lo, hi))
+    }
+
+    fn paint_terrain_column(&mut self, column: &TerrainColumnHeights) {
+        let (chunk_x, chunk_z) = (column.chunk_x, column.chunk_z);
+        let surface_y = &column.surface_y;
         let materials = self.terrain_material_ids();
         let feature_materials = self.feature_material_ids();
-        let water_level = self.get_floor_level().saturating_sub(6) as i32;
+        let water_level = self.get_water_level() as i32;
         let lower_void = self.get_lower_void() as i32;
         let base_x = chunk_x as i32 * CHUNK_SIZE as i32;
         let base_z = chunk_z as i32 * CHUNK_SIZE as i32;
@@ -180,22 +270,28 @@ impl World {
                 let world_x = base_x + local_x;
                 let world_z = base_z + local_z;
                 let surface = surface_y[idx].clamp(0, lower_void);
+                let sediment_depth = column.sediment_depth[idx] as i32;
+                let fresh_water_y = column.water_y[idx];
+                let water_top = water_level.min(fresh_water_y);
+                let lowland = surface > water_top + 2 || fresh_water_y < surface;
 
-                let surface_material = if surface > water_level + 2 {
+                let surface_material = if lowland || sediment_depth > 0 {
                     materials.clay
                 } else {
                     materials.grass
                 };
                 self.set_voxel_material_i32(world_x, surface, world_z, surface_material);
-                if surface > water_level {
-                    for y in water_level..surface {
+                if surface > water_top {
+                    for y in water_top..surface {
                         self.set_voxel_material_i32(world_x, y, world_z, materials.water);
                     }
                 }
                 for y in (surface + 1)..lower_void {
                     let depth = y - surface;
-                    let mat = if depth <= 2 {
-                        if surface > water_level + 2 {
+                    let mat = if depth < sediment_depth {
+                        materials.clay
+                    } else if depth <= 2 {
+                        if lowland {
                             materials.clay
                         } else {
                             materials.dirt
@@ -218,8 +314,10 @@ impl World {
                 }
             }
         }
+        self.stamp_ore_veins_for_column(chunk_x, chunk_z, surface_y, materials.stone);
         self.stamp_fun_features_for_column(chunk_x, chunk_z, feature_materials);
         self.mark_terrain_column_generated(chunk_x, chunk_z);
+        self.queue_column_decoration(chunk_x, chunk_z, surface_y);
     }
 
     fn stamp_fun_features_for_column(
@@ -360,3 +458,184 @@ impl World {
         None
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fmt::Write as _;
+
+    use super::*;
+
+    const GOLDEN_PATH: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/golden/terrain_chunks.txt"
+    );
+    const TEST_WORLD_SIZE: usize = 256;
+    // Spans the middle of the world with erosion on, so shaping and decoration
+    // across columns are both covered.
+    const COLUMNS_X: std::ops::Range<u32> = 6..10;
+    const COLUMNS_Z: std::ops::Range<u32> = 6..10;
+
+    const FNV_OFFSET: u64 = 0xCBF29CE484222325;
+    const FNV_PRIME: u64 = 0x100000001B3;
+
+    fn fnv1a(hash: &mut u64, bytes: &[u8]) {
+        for &byte in bytes {
+            *hash ^= byte as u64;
+            *hash = hash.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn chunk_hash(world: &World, chunk_x: usize, chunk_y: usize, chunk_z: usize) -> u64 {
+        let meta = world
+            .chunk_meta(chunk_x as i32, chunk_y as i32, chunk_z as i32)
+            .expect("chunk in bounds");
+        let mut hash = FNV_OFFSET;
+        fnv1a(
+            &mut hash,
+            &[
+                meta.generated as u8,
+                meta.has_transparency as u8,
+                meta.non_air_voxels as u8,
+                (meta.non_air_voxels >> 8) as u8,
+            ],
+        );
+        match world.chunk_voxels(chunk_x, chunk_y, chunk_z) {
+            Some(voxels) => {
+                for &id in voxels {
+                    fnv1a(&mut hash, &id.to_le_bytes());
+                }
+            }
+            None => fnv1a(&mut hash, b"air"),
+        }
+        hash
+    }
+
+    /// Colors behind every id the chunks reference, so palette drift is caught too.
+    fn palette_hash(world: &World) -> u64 {
+        let mut max_id = 0;
+        for chunk_x in COLUMNS_X {
+            for chunk_z in COLUMNS_Z {
+                for chunk_y in 0..world.chunk_dim {
+                    if let Some(voxels) =
+                        world.chunk_voxels(chunk_x as usize, chunk_y, chunk_z as usize)
+                    {
+                        max_id = max_id.max(voxels.iter().copied().max().unwrap_or(0));
+                    }
+                }
+            }
+        }
+        let mut hash = FNV_OFFSET;
+        for id in 0..=max_id {
+            let color = world.get_material(id).color;
+            fnv1a(&mut hash, &[color.r, color.g, color.b, color.a]);
+        }
+        hash
+    }
+
+    fn golden_listing(world: &World) -> String {
+        let mut listing = String::new();
+        writeln!(listing, "palette {:016x}", palette_hash(world)).unwrap();
+        for chunk_x in COLUMNS_X {
+            for chunk_z in COLUMNS_Z {
+                for chunk_y in 0..world.chunk_dim {
+                    let hash = chunk_hash(world, chunk_x as usize, chunk_y, chunk_z as usize);
+                    writeln!(listing, "{chunk_x} {chunk_y} {chunk_z} {hash:016x}").unwrap();
+                }
+            }
+        }
+        listing
+    }
+
+    fn test_world() -> World {
+        let mut world = World::new(TEST_WORLD_SIZE);
+        world.terrain_shape = world.terrain_shape.with_erosion(true);
+        world
+    }
+
+    fn generate_sync() -> World {
+        let mut world = test_world();
+        for chunk_x in COLUMNS_X {
+            for chunk_z in COLUMNS_Z {
+                world.gen_terrain_column(chunk_x, chunk_z);
+            }
+        }
+        world
+    }
+
+    /// Same columns through a separate heightfield, as `TerrainGenWorker` does,
+    /// in reverse order to also catch order dependence.
+    fn generate_worker_path() -> World {
+        let mut world = test_world();
+        let mut heightfield = world.new_terrain_heightfield();
+        for chunk_x in COLUMNS_X.rev() {
+            for chunk_z in COLUMNS_Z.rev() {
+                let column = heightfield.build_surface_heights(chunk_x, chunk_z);
+                world.apply_terrain_column_heights(&column);
+            }
+        }
+        world
+    }
+
+    #[test]
+    fn terrain_chunks_match_golden_hashes() {
+        let listing = golden_listing(&generate_sync());
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::create_dir_all(std::path::Path::new(GOLDEN_PATH).parent().unwrap()).unwrap();
+            std::fs::write(GOLDEN_PATH, &listing).unwrap();
+            return;
+        }
+
+        let golden = std::fs::read_to_string(GOLDEN_PATH)
+            .expect("missing golden hashes; run with UPDATE_GOLDEN=1 to create them");
+        let mismatches: Vec<String> = golden
+            .lines()
+            .zip(listing.lines())
+            .filter(|(expected, actual)| expected != actual)
+            .map(|(expected, actual)| format!("expected `{expected}`, got `{actual}`"))
+            .collect();
+        assert_eq!(
+            golden.lines().count(),
+            listing.lines().count(),
+            "golden chunk count changed"
+        );
+        assert!(
+            mismatches.is_empty(),
+            "{} chunk hashes differ from {GOLDEN_PATH} (rerun with UPDATE_GOLDEN=1 if intended):\n{}",
+            mismatches.len(),
+            mismatches.join("\n")
+        );
+    }
+
+    #[test]
+    fn worker_and_sync_paths_produce_identical_chunks() {
+        let sync = generate_sync();
+        let worker = generate_worker_path();
+        for chunk_x in 0..sync.chunk_dim {
+            for chunk_y in 0..sync.chunk_dim {
+                for chunk_z in 0..sync.chunk_dim {
+                    let (cx, cy, cz) = (chunk_x as i32, chunk_y as i32, chunk_z as i32);
+                    let sync_meta = sync.chunk_meta(cx, cy, cz).unwrap();
+                    let worker_meta = worker.chunk_meta(cx, cy, cz).unwrap();
+                    assert_eq!(
+                        (
+                            sync_meta.generated,
+                            sync_meta.non_air_voxels,
+                            sync_meta.has_transparency
+                        ),
+                        (
+                            worker_meta.generated,
+                            worker_meta.non_air_voxels,
+                            worker_meta.has_transparency
+                        ),
+                        "chunk meta differs at ({chunk_x}, {chunk_y}, {chunk_z})"
+                    );
+                    assert!(
+                        sync.chunk_voxels(chunk_x, chunk_y, chunk_z)
+                            == worker.chunk_voxels(chunk_x, chunk_y, chunk_z),
+                        "chunk voxels differ at ({chunk_x}, {chunk_y}, {chunk_z})"
+                    );
+                }
+            }
+        }
+    }
+}